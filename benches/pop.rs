@@ -0,0 +1,61 @@
+/* Pointer-chasing pop benchmark, meant to be compared with and without the
+ * `prefetch` feature:
+ *
+ *   cargo bench --bench pop
+ *   cargo bench --bench pop --features prefetch
+ *
+ * The stacks are filled with other allocations in between, so consecutive
+ * nodes don't share cache lines and every `(*top).next` is a likely miss. */
+
+/* Early returns are the house style here */
+#![allow(clippy::needless_return)]
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use stacc::stacc_lockfree_ebr::Local;
+use stacc::stacc_lockfree_hp::LockFreeStacc;
+
+const N: usize = 1 << 18;
+const ROUNDS: usize = 5;
+
+/// Fills with `push`, empties with `pop`, returns the best ns per pop
+fn measure<P: Default + Copy>(mut push: impl FnMut(P), mut pop: impl FnMut() -> Option<P>) -> f64 {
+    let mut best = f64::MAX;
+    for _ in 0..ROUNDS {
+        let mut spacers = Vec::with_capacity(N);
+        for _ in 0..N {
+            push(P::default());
+            spacers.push(black_box(Box::new([0u8; 200])));
+        }
+        /* Free the spacers first, so the nodes stay scattered */
+        drop(spacers);
+
+        let start = Instant::now();
+        while let Some(x) = pop() {
+            black_box(x);
+        }
+        best = best.min(start.elapsed().as_nanos() as f64 / N as f64);
+    }
+    return best;
+}
+
+fn bench<P: Default + Copy + Send>(name: &str) {
+    let mut hp = LockFreeStacc::new();
+    let (mut a, mut b) = (hp.clone(), hp.clone());
+    let hp_ns = measure::<P>(|x| a.push(x), || b.pop());
+    drop((a, b));
+    hp.force_reclaim();
+
+    let mut ebr = Local::new();
+    let mut other = ebr.clone();
+    let ebr_ns = measure::<P>(|x| ebr.push(x), || other.pop());
+
+    println!("{:>10}: hp {:6.1} ns/pop, ebr {:6.1} ns/pop", name, hp_ns, ebr_ns);
+}
+
+fn main() {
+    println!("prefetch: {}", cfg!(feature = "prefetch"));
+    bench::<u64>("u64");
+    bench::<[u64; 16]>("[u64; 16]");
+}
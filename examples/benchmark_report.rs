@@ -0,0 +1,289 @@
+/* Runs the same workloads against every structure on this machine and
+ * prints a markdown table, so the choice between them can be made on the
+ * hardware they will actually run on:
+ *
+ *   cargo run --release --example benchmark_report
+ *   cargo run --release --example benchmark_report -- --ops 1000000 --threads 1,2,8
+ *
+ * Workloads:
+ *   pairs  - every thread pushes one item and pops one, the stack stays small
+ *   bursts - every thread pushes 64 items, then pops 64
+ *   stream - one producer, one consumer, only for the SPSC queue
+ *
+ * Latency is sampled on every 16th operation, so the clock doesn't dominate
+ * the measurement. Allocations are counted by a wrapping global allocator,
+ * over the whole run, including the handle setup. */
+
+/* Early returns are the house style here */
+#![allow(clippy::needless_return)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use stacc::spsc_queue::queue;
+use stacc::stacc::Stacc;
+use stacc::stacc_lockfree_ebr::Local;
+use stacc::stacc_lockfree_hp::LockFreeStacc;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.realloc(ptr, layout, new_size);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/* A power of two, checked with a mask */
+const SAMPLE_EVERY: u64 = 16;
+const BURST: u64 = 64;
+
+struct Config {
+    ops: u64,
+    threads: Vec<usize>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: benchmark_report [--ops N] [--threads N,N,...]");
+    exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        ops: 200_000,
+        threads: vec![1, 2, 4],
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--ops" => config.ops = value.parse().unwrap_or_else(|_| usage()),
+            "--threads" => {
+                config.threads = value.split(',').map(|n| n.parse().unwrap_or_else(|_| usage())).collect();
+            }
+            _ => usage(),
+        }
+    }
+    if config.ops == 0 || config.threads.contains(&0) {
+        usage();
+    }
+    return config;
+}
+
+/// The common ground of the stacks, so one driver runs them all
+trait Handle: Clone + Send {
+    fn push(&mut self, x: u64);
+    fn pop(&mut self) -> Option<u64>;
+}
+
+impl Handle for LockFreeStacc<u64> {
+    fn push(&mut self, x: u64) {
+        LockFreeStacc::push(self, x);
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        LockFreeStacc::pop(self)
+    }
+}
+
+impl Handle for Local<u64> {
+    fn push(&mut self, x: u64) {
+        Local::push(self, x);
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        Local::pop(self)
+    }
+}
+
+impl Handle for Stacc<u64> {
+    fn push(&mut self, x: u64) {
+        /* Sized so it never fills up, see main() */
+        let rejected = Stacc::push(self, x);
+        debug_assert!(rejected.is_none());
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        Stacc::pop(self)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Workload {
+    Pairs,
+    Bursts,
+}
+
+impl Workload {
+    fn name(self) -> &'static str {
+        match self {
+            Workload::Pairs => "pairs",
+            Workload::Bursts => "bursts",
+        }
+    }
+}
+
+/// Sampled latencies and the number of operations behind them
+#[derive(Default)]
+struct Samples {
+    ops: u64,
+    latencies: Vec<u64>,
+}
+
+impl Samples {
+    fn record<R>(&mut self, op: impl FnOnce() -> R) -> R {
+        self.ops += 1;
+        if self.ops & (SAMPLE_EVERY - 1) != 0 {
+            return op();
+        }
+        let start = Instant::now();
+        let r = op();
+        self.latencies.push(start.elapsed().as_nanos() as u64);
+        return r;
+    }
+}
+
+struct Row {
+    structure: &'static str,
+    workload: &'static str,
+    threads: usize,
+    mops: f64,
+    p99_ns: u64,
+    allocs_per_op: f64,
+}
+
+fn summarize(structure: &'static str, workload: &'static str, threads: usize, samples: Vec<Samples>, seconds: f64, allocs: u64) -> Row {
+    let ops: u64 = samples.iter().map(|s| s.ops).sum();
+    let mut latencies: Vec<u64> = samples.into_iter().flat_map(|s| s.latencies).collect();
+    latencies.sort_unstable();
+    let p99_ns = latencies.get(latencies.len() * 99 / 100).copied().unwrap_or(0);
+
+    return Row {
+        structure,
+        workload,
+        threads,
+        mops: ops as f64 / seconds / 1e6,
+        p99_ns,
+        allocs_per_op: allocs as f64 / ops as f64,
+    };
+}
+
+fn run<H: Handle>(structure: &'static str, handle: impl FnOnce() -> H, workload: Workload, threads: usize, ops: u64) -> Row {
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let handle = handle();
+
+    let samples = stacc::threads::with_threads(threads, &handle, |_, mut h| {
+        let mut samples = Samples::default();
+        let mut done = 0;
+        while done < ops {
+            let n = match workload {
+                Workload::Pairs => 1,
+                Workload::Bursts => BURST,
+            };
+            for i in 0..n {
+                samples.record(|| h.push(i));
+            }
+            for _ in 0..n {
+                black_box(samples.record(|| h.pop()));
+            }
+            done += 2 * n;
+        }
+        samples
+    });
+
+    let seconds = start.elapsed().as_secs_f64();
+    drop(handle);
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - allocs;
+    return summarize(structure, workload.name(), threads, samples, seconds, allocs);
+}
+
+fn stream(ops: u64) -> Row {
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let (mut tx, mut rx) = queue::<u64>();
+
+    let samples = thread::scope(|scope| {
+        let producer = scope.spawn(move || {
+            let mut samples = Samples::default();
+            for i in 0..ops / 2 {
+                let mut item = Some(i);
+                while let Some(x) = item.take() {
+                    item = samples.record(|| tx.push(x));
+                    if item.is_some() {
+                        thread::yield_now();
+                    }
+                }
+            }
+            samples
+        });
+
+        let mut samples = Samples::default();
+        let mut popped = 0;
+        while popped < ops / 2 {
+            match samples.record(|| rx.pop()) {
+                Some(x) => {
+                    black_box(x);
+                    popped += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        vec![producer.join().unwrap(), samples]
+    });
+
+    let seconds = start.elapsed().as_secs_f64();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - allocs;
+    return summarize("spsc", "stream", 2, samples, seconds, allocs);
+}
+
+fn main() {
+    let config = parse_args();
+    let mut rows = Vec::new();
+
+    for &threads in &config.threads {
+        for workload in [Workload::Pairs, Workload::Bursts] {
+            rows.push(run("hp", LockFreeStacc::<u64>::new, workload, threads, config.ops));
+            rows.push(run("ebr", Local::<u64>::new, workload, threads, config.ops));
+            /* Room for a full burst from every thread */
+            let capacity = threads * BURST as usize;
+            rows.push(run("stacc", || Stacc::<u64>::new(capacity), workload, threads, config.ops));
+        }
+    }
+    rows.push(stream(config.ops));
+
+    println!("| structure | workload | threads | Mops/s | p99 latency (ns) | allocations/op |");
+    println!("|-----------|----------|--------:|-------:|-----------------:|---------------:|");
+    for row in &rows {
+        println!(
+            "| {} | {} | {} | {:.2} | {} | {:.3} |",
+            row.structure, row.workload, row.threads, row.mops, row.p99_ns, row.allocs_per_op
+        );
+    }
+    println!();
+    println!(
+        "{} ops per thread, {} available cores, prefetch: {}",
+        config.ops,
+        thread::available_parallelism().map_or(1, |n| n.get()),
+        cfg!(feature = "prefetch")
+    );
+}
@@ -0,0 +1,260 @@
+/* Soak test for the structures, meant to run for a long time unattended:
+ *
+ *   cargo run --release --features stress --bin stress -- \
+ *       --structure hp --threads 8 --push 60 --payload 64 --seconds 3600
+ *
+ * Every thread pushes and pops at random (`--push` percent of the ops are
+ * pushes), items carry a unique id and a payload of `--payload` bytes.
+ * At the end the structure is drained and we check that every pushed item
+ * came out exactly once, and that nothing leaked or got dropped twice.
+ * The SPSC queue always runs one producer and one consumer and also checks
+ * that items come out in order. */
+
+/* Early returns are the house style here */
+#![allow(clippy::needless_return)]
+
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use stacc::drop_counter::{DropCounter, DropTracker};
+use stacc::spsc_queue::queue;
+use stacc::stacc::Stacc;
+use stacc::stacc_lockfree_ebr::Local;
+use stacc::stacc_lockfree_hp::LockFreeStacc;
+
+type Item = DropCounter<(u64, Vec<u8>)>;
+
+struct Config {
+    structure: String,
+    threads: usize,
+    push_percent: u64,
+    payload: usize,
+    duration: Duration,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: stress [--structure hp|ebr|stacc|spsc] [--threads N] [--push PERCENT] [--payload BYTES] [--seconds N]");
+    exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        structure: "hp".to_string(),
+        threads: 4,
+        push_percent: 50,
+        payload: 16,
+        duration: Duration::from_secs(10),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        let number = || value.parse::<u64>().unwrap_or_else(|_| usage());
+        match flag.as_str() {
+            "--structure" => config.structure = value.clone(),
+            "--threads" => config.threads = number() as usize,
+            "--push" => config.push_percent = number().min(100),
+            "--payload" => config.payload = number() as usize,
+            "--seconds" => config.duration = Duration::from_secs(number()),
+            _ => usage(),
+        }
+    }
+    if config.threads == 0 {
+        usage();
+    }
+    return config;
+}
+
+/// xorshift, good enough for picking ops
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0;
+    }
+}
+
+/// What a thread did, ids are summed to check that nothing got lost
+#[derive(Default)]
+struct Tally {
+    pushed: u64,
+    popped: u64,
+    pushed_ids: u128,
+    popped_ids: u128,
+}
+
+impl Tally {
+    fn add(&mut self, other: &Tally) {
+        self.pushed += other.pushed;
+        self.popped += other.popped;
+        self.pushed_ids += other.pushed_ids;
+        self.popped_ids += other.popped_ids;
+    }
+
+    fn popped(&mut self, item: Item) {
+        check_payload(&item);
+        self.popped += 1;
+        self.popped_ids += item.0 as u128;
+    }
+}
+
+fn make_item(tracker: &Arc<DropTracker>, id: u64, payload: usize) -> Item {
+    tracker.wrap((id, vec![id as u8; payload]))
+}
+
+fn check_payload(item: &Item) {
+    let (id, payload) = &**item;
+    assert!(payload.iter().all(|&b| b == *id as u8), "payload of item {} got corrupted", id);
+}
+
+/// Runs `op` (one push or pop) on every thread until the deadline
+fn run_threads<H: Clone + Send>(config: &Config, handle: &H, op: impl Fn(&mut H, &mut Rng, &mut Tally, u64) + Sync) -> Tally {
+    let deadline = Instant::now() + config.duration;
+    let tallies = stacc::threads::with_threads(config.threads, handle, |i, mut h| {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15 ^ (i as u64 + 1));
+        let mut tally = Tally::default();
+        let mut seq = 0u64;
+        while Instant::now() < deadline {
+            for _ in 0..1000 {
+                /* Unique across threads: thread number in the top bits */
+                let id = (i as u64) << 40 | seq;
+                op(&mut h, &mut rng, &mut tally, id);
+                seq += 1;
+            }
+        }
+        tally
+    });
+
+    let mut total = Tally::default();
+    for t in &tallies {
+        total.add(t);
+    }
+    return total;
+}
+
+fn stress_hp(config: &Config, tracker: &Arc<DropTracker>) -> Tally {
+    let mut stack = LockFreeStacc::new();
+    let mut total = run_threads(config, &stack, |s, rng, tally, id| {
+        if rng.next() % 100 < config.push_percent {
+            s.push(make_item(tracker, id, config.payload));
+            tally.pushed += 1;
+            tally.pushed_ids += id as u128;
+        } else if let Some(item) = s.pop() {
+            tally.popped(item);
+        }
+    });
+    while let Some(item) = stack.pop() {
+        total.popped(item);
+    }
+    return total;
+}
+
+fn stress_ebr(config: &Config, tracker: &Arc<DropTracker>) -> Tally {
+    let mut stack = Local::new();
+    let mut total = run_threads(config, &stack, |s, rng, tally, id| {
+        if rng.next() % 100 < config.push_percent {
+            s.push(make_item(tracker, id, config.payload));
+            tally.pushed += 1;
+            tally.pushed_ids += id as u128;
+        } else if let Some(item) = s.pop() {
+            tally.popped(item);
+        }
+    });
+    while let Some(item) = stack.pop() {
+        total.popped(item);
+    }
+    return total;
+}
+
+fn stress_stacc(config: &Config, tracker: &Arc<DropTracker>) -> Tally {
+    let stack = Stacc::new(1024);
+    let mut total = run_threads(config, &stack, |s, rng, tally, id| {
+        if rng.next() % 100 < config.push_percent {
+            if s.push(make_item(tracker, id, config.payload)).is_none() {
+                tally.pushed += 1;
+                tally.pushed_ids += id as u128;
+            }
+        } else if let Some(item) = s.pop() {
+            tally.popped(item);
+        }
+    });
+    while let Some(item) = stack.pop() {
+        total.popped(item);
+    }
+    return total;
+}
+
+fn stress_spsc(config: &Config, tracker: &Arc<DropTracker>) -> Tally {
+    let (mut tx, mut rx) = queue::<Item>();
+    let deadline = Instant::now() + config.duration;
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let producer = scope.spawn(|| {
+            let mut tally = Tally::default();
+            let mut id = 0;
+            while Instant::now() < deadline {
+                for _ in 0..1000 {
+                    if tx.push(make_item(tracker, id, config.payload)).is_none() {
+                        tally.pushed += 1;
+                        tally.pushed_ids += id as u128;
+                        id += 1;
+                    }
+                }
+            }
+            done.store(true, Ordering::Release);
+            tally
+        });
+
+        let mut tally = Tally::default();
+        let mut expected = 0;
+        loop {
+            let finished = done.load(Ordering::Acquire);
+            while let Some(item) = rx.pop() {
+                assert_eq!(item.0, expected, "SPSC items out of order");
+                expected += 1;
+                tally.popped(item);
+            }
+            if finished {
+                break;
+            }
+            thread::yield_now();
+        }
+
+        tally.add(&producer.join().unwrap());
+        tally
+    })
+}
+
+fn main() {
+    let config = parse_args();
+    let tracker = DropTracker::new();
+    let started = Instant::now();
+
+    let total = match config.structure.as_str() {
+        "hp" => stress_hp(&config, &tracker),
+        "ebr" => stress_ebr(&config, &tracker),
+        "stacc" => stress_stacc(&config, &tracker),
+        "spsc" => stress_spsc(&config, &tracker),
+        _ => usage(),
+    };
+
+    println!(
+        "{}: {} pushes, {} pops in {:.1?}",
+        config.structure,
+        total.pushed,
+        total.popped,
+        started.elapsed()
+    );
+    assert_eq!(total.pushed, total.popped, "pushed and popped counts differ");
+    assert_eq!(total.pushed_ids, total.popped_ids, "some items got lost or duplicated");
+    /* Rejected pushes were dropped right away, everything else was popped */
+    tracker.assert_balanced();
+    println!("ok");
+}
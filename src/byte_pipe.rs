@@ -0,0 +1,109 @@
+/* std::io adapters over an SPSC queue of bytes, so encoders, framers and
+ * the like can stream through the ring as they would through a socket.
+ *
+ * Both sides are non-blocking: a full ring makes write() fail with
+ * WouldBlock, an empty one does the same for read(). Once the writer is
+ * gone and everything it wrote was read, read() returns 0 (end of file).
+ * Writing after the reader is gone fails with BrokenPipe.
+ */
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::spsc_queue::{queue, QueueConsumer, QueueProducer};
+
+/// Byte ring with the [`Write`] end and the [`Read`] end
+pub fn byte_pipe() -> (ByteWriter, ByteReader) {
+    let (tx, rx) = queue();
+    return (ByteWriter::from(tx), ByteReader::from(rx));
+}
+
+/// [`Write`] end of a byte queue
+#[derive(Debug)]
+pub struct ByteWriter {
+    tx: QueueProducer<u8>,
+}
+
+impl ByteWriter {
+    pub fn into_inner(self) -> QueueProducer<u8> {
+        self.tx
+    }
+}
+
+impl From<QueueProducer<u8>> for ByteWriter {
+    fn from(tx: QueueProducer<u8>) -> Self {
+        Self { tx }
+    }
+}
+
+impl Write for ByteWriter {
+    /// Writes as much of `buf` as fits, made visible with a single store
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if !self.tx.other_side_alive() {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+
+        let mut written = 0;
+        for &b in buf {
+            if self.tx.push_unpublished(b).is_some() {
+                break;
+            }
+            written += 1;
+        }
+        if written == 0 {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.tx.publish();
+        return Ok(written);
+    }
+
+    /// Every write is published right away, nothing to do
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// [`Read`] end of a byte queue
+#[derive(Debug)]
+pub struct ByteReader {
+    rx: QueueConsumer<u8>,
+}
+
+impl ByteReader {
+    pub fn into_inner(self) -> QueueConsumer<u8> {
+        self.rx
+    }
+}
+
+impl From<QueueConsumer<u8>> for ByteReader {
+    fn from(rx: QueueConsumer<u8>) -> Self {
+        Self { rx }
+    }
+}
+
+impl Read for ByteReader {
+    /// Copies out as many bytes as are available, up to `buf.len()`
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        /* Checked first: once the writer is gone, everything it wrote is
+         * visible, so an empty ring after that really is the end */
+        let writer_alive = self.rx.other_side_alive();
+
+        let (first, second) = self.rx.peek();
+        let a = first.len().min(buf.len());
+        buf[..a].copy_from_slice(&first[..a]);
+        let b = second.len().min(buf.len() - a);
+        buf[a..a + b].copy_from_slice(&second[..b]);
+
+        let read = a + b;
+        if read == 0 && writer_alive {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.rx.release(read);
+        return Ok(read);
+    }
+}
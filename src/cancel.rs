@@ -0,0 +1,153 @@
+/* Cooperative cancellation for blocking and async operations.
+ *
+ * Waiting operations register a waker with the token, cancel() wakes all
+ * of them, so shutdown doesn't depend on sentinel values being pushed. */
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::ordering::Ordering;
+
+/// Returned by operations interrupted through a [`CancellationToken`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+struct Inner {
+    cancelled: AtomicBool,
+    /* Wakers of everyone currently waiting, tagged so they can leave */
+    wakers: Mutex<Vec<(usize, Waker)>>,
+    next_id: AtomicUsize,
+}
+
+/// Shared flag that interrupts waiting operations. Clones refer to the
+/// same token, cancelling is permanent.
+///
+/// ```
+/// use stacc::cancel::*;
+/// use stacc::stacc::*;
+///
+/// let v = Stacc::<u32>::with_backoff(4, Backoff::Park);
+/// let token = CancellationToken::new();
+///
+/// let (vc, tc) = (v.clone(), token.clone());
+/// let consumer = std::thread::spawn(move || vc.pop_blocking_cancellable(&tc));
+/// token.cancel();
+/// assert_eq!(consumer.join().unwrap(), Err(Cancelled));
+/// ```
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        let inner = Inner {
+            cancelled: AtomicBool::new(false),
+            wakers: Mutex::new(Vec::new()),
+            next_id: AtomicUsize::new(0),
+        };
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Interrupts every operation waiting on this token, now and later
+    pub fn cancel(&self) {
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        self.inner.cancelled.store(true, Ordering::Release);
+        let wakers = std::mem::take(&mut *wakers);
+
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Wakes `waker` on cancellation. Returns `None` if that already
+    /// happened, otherwise an id for [`deregister`](Self::deregister).
+    pub(crate) fn register(&self, waker: &Waker) -> Option<usize> {
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        /* Checked under the lock, so cancel() can't slip in between */
+        if self.inner.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        wakers.push((id, waker.clone()));
+        return Some(id);
+    }
+
+    pub(crate) fn deregister(&self, id: usize) {
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        wakers.retain(|(i, _)| *i != id);
+    }
+
+    /// Runs `fut` until it completes or the token is cancelled,
+    /// whichever comes first. The inner future is dropped on cancellation,
+    /// e.g. a dropped [`PopFuture`](crate::stacc_lockfree_hp::PopFuture)
+    /// takes nothing from the stack.
+    pub fn run<F: Future>(&self, fut: F) -> WithCancel<'_, F> {
+        WithCancel {
+            token: self,
+            fut: Some(Box::pin(fut)),
+            registered: None,
+        }
+    }
+}
+
+/// Future returned by [`CancellationToken::run`]
+pub struct WithCancel<'a, F> {
+    token: &'a CancellationToken,
+    fut: Option<Pin<Box<F>>>,
+    registered: Option<usize>,
+}
+
+impl<F> WithCancel<'_, F> {
+    fn deregister(&mut self) {
+        if let Some(id) = self.registered.take() {
+            self.token.deregister(id);
+        }
+    }
+}
+
+impl<F: Future> Future for WithCancel<'_, F> {
+    type Output = Result<F::Output, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        /* Pin<Box<F>> is Unpin, so the whole thing is too */
+        let this = self.get_mut();
+        let fut = match this.fut.as_mut() {
+            Some(fut) => fut,
+            None => return Poll::Ready(Err(Cancelled)),
+        };
+
+        if !this.token.is_cancelled() {
+            if let Poll::Ready(x) = fut.as_mut().poll(cx) {
+                this.deregister();
+                return Poll::Ready(Ok(x));
+            }
+
+            /* Re-register every time, the waker may have changed */
+            this.deregister();
+            this.registered = this.token.register(cx.waker());
+            if this.registered.is_some() {
+                return Poll::Pending;
+            }
+        }
+
+        this.fut = None;
+        return Poll::Ready(Err(Cancelled));
+    }
+}
+
+impl<F> Drop for WithCancel<'_, F> {
+    fn drop(&mut self) {
+        self.deregister();
+    }
+}
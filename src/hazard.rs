@@ -0,0 +1,580 @@
+/* Hazard pointer domain: the hazard slots and the reclamation backlog,
+ * shared by every stack built on it.
+ *
+ * With many small stacks, giving each one its own slots means every scan
+ * only covers a tiny retired list, while the slot arrays multiply. Stacks
+ * created with `LockFreeStacc::with_domain` share one set of slots instead,
+ * and nodes that couldn't be freed right away are handed over to the
+ * domain, where a single scan (optionally on a reclaimer thread) covers
+ * the nodes of all stacks at once.
+ *
+ * Other lock-free structures can use the same machinery through
+ * Domain::guard(), which hands out a thread's slots as a HazardGuard.
+ */
+
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::next_id;
+use crate::ordering::Ordering;
+
+/* Slots the stacks use themselves: 0 by pop, 1 for walking past the top.
+ * Domains may give handles more, see Domain::with_hazards() */
+pub(crate) const HAZARDS_PER_THREAD: usize = 2;
+/* Lower bound of the adaptive scan threshold, see Domain::scan_threshold() */
+const MIN_SCAN_THRESHOLD: usize = 42;
+
+/// A node that is unreachable, but may still be protected. The type is
+/// erased, so nodes of different stacks can wait in one list.
+pub(crate) struct Retired {
+    ptr: *mut u8,
+    free: unsafe fn(*mut u8),
+}
+
+unsafe fn free_box<N>(ptr: *mut u8) {
+    drop(Box::from_raw(ptr as *mut N));
+}
+
+unsafe fn drop_arc<T>(ptr: *mut u8) {
+    drop(Arc::from_raw(ptr as *const T));
+}
+
+impl Retired {
+    /// `ptr` must come from `Box::into_raw`, and nothing must be left
+    /// to drop inside it besides the box itself
+    pub(crate) unsafe fn new<N>(ptr: *mut N) -> Self {
+        Self {
+            ptr: ptr as *mut u8,
+            free: free_box::<N>,
+        }
+    }
+
+    /// `ptr` must be freed by `free` and nothing else, once nobody
+    /// protects it anymore
+    pub(crate) unsafe fn with_free<N>(ptr: *mut N, free: unsafe fn(*mut u8)) -> Self {
+        Self {
+            ptr: ptr as *mut u8,
+            free,
+        }
+    }
+
+    /// `ptr` must come from `Arc::into_raw`, the reference it stands for
+    /// is dropped once nobody protects it anymore
+    pub(crate) unsafe fn arc<T>(ptr: *const T) -> Self {
+        Self {
+            ptr: ptr as *mut u8,
+            free: drop_arc::<T>,
+        }
+    }
+}
+
+/* One per live handle, as in Michael's paper: records are pushed onto a
+ * lock-free list and only freed with the domain, so scans can walk the
+ * list without any protection and there is no limit on handles. A dropped
+ * handle marks its record inactive and the next new handle takes it over */
+struct HpRecord {
+    hazards: Box<[AtomicPtr<u8>]>,
+    active: AtomicBool,
+    index: usize,
+    /* Immutable once the record is published */
+    next: *const HpRecord,
+}
+
+/// A handle's hazard slots in a [`Domain`], valid as long as the domain
+pub(crate) struct Slot {
+    record: NonNull<HpRecord>,
+}
+
+/* SAFETY: the record is only accessed through atomics */
+unsafe impl Send for Slot {}
+
+impl Slot {
+    /// Unique within the domain, small and dense, usable as an index
+    pub(crate) fn index(&self) -> usize {
+        self.record().index
+    }
+
+    /// Gives the record back for reuse, the hazards must be cleared already.
+    /// The domain must still be alive, so call it before dropping the Arc.
+    pub(crate) fn release(&self) {
+        debug_assert!(self.record().hazards.iter().all(|h| h.load(Ordering::Relaxed).is_null()));
+        self.record().active.store(false, Ordering::Release);
+    }
+
+    /// Publishes `ptr` in hazard slot `k`. The caller still has to check
+    /// that `ptr` is reachable afterwards, the SeqCst store pairs with the
+    /// fence in [`Domain::hazards`]
+    pub(crate) fn protect<N>(&self, k: usize, ptr: *mut N) {
+        self.hazard(k).store(ptr, Ordering::SeqCst);
+    }
+
+    /// Clears every hazard slot of this handle
+    pub(crate) fn clear(&self) {
+        for h in self.record().hazards.iter() {
+            h.store(ptr::null_mut(), Ordering::Release);
+        }
+    }
+
+    pub(crate) fn hazard<N>(&self, k: usize) -> &AtomicPtr<N> {
+        let slot = &self.record().hazards[k];
+        /* SAFETY: AtomicPtr<N> has the same layout for every N */
+        unsafe { &*(slot as *const AtomicPtr<u8> as *const AtomicPtr<N>) }
+    }
+
+    fn record(&self) -> &HpRecord {
+        /* SAFETY: records live until the domain is dropped, and whoever
+         * holds a Slot keeps the domain alive */
+        unsafe { self.record.as_ref() }
+    }
+}
+
+/// Counters of a [`Domain`], all approximate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DomainStats {
+    /// Handles currently registered
+    pub handles: usize,
+    /// Hazard records allocated, the most handles that were ever alive at once
+    pub records: usize,
+    /// Scans performed by handles and by the domain itself
+    pub scans: usize,
+    /// Nodes found to be no longer protected
+    pub reclaimed: usize,
+    /// Nodes handed over to the domain, waiting for the next domain scan
+    pub pending: usize,
+    /// Failed CASes on the stacks' tops, reported by handles when they
+    /// scan and when they are dropped
+    pub cas_retries: usize,
+}
+
+/// Hazard slots and reclamation backlog shared by many stacks
+pub struct Domain {
+    /* See Domain::id() */
+    id: u64,
+    records: AtomicPtr<HpRecord>,
+    /* Length of every record's hazard array */
+    hazards_per_handle: usize,
+    /* Used to give unique ID for each thread */
+    counter: AtomicUsize,
+
+    retired: Mutex<Vec<Retired>>,
+    retired_len: AtomicUsize,
+    /* While a reclaimer thread runs, handles hand over instead of scanning */
+    reclaimers: AtomicUsize,
+
+    scans: AtomicUsize,
+    reclaimed: AtomicUsize,
+    cas_retries: AtomicUsize,
+}
+
+/* SAFETY: the retired nodes are owned by the domain, their payload was
+ * already moved out, so only the allocation itself crosses threads.
+ * Retired Arcs come from Interner, which requires T: Send + Sync, and
+ * HazardGuard::retire() requires Send */
+unsafe impl Send for Domain {}
+unsafe impl Sync for Domain {}
+
+impl Domain {
+    pub fn new() -> Self {
+        Self::with_hazards(HAZARDS_PER_THREAD)
+    }
+
+    /// Domain that gives every handle `k` hazard slots instead of the two
+    /// the stacks need, for operations that protect more nodes at once
+    pub fn with_hazards(k: usize) -> Self {
+        assert!(k >= HAZARDS_PER_THREAD, "the stacks need at least {} hazard slots", HAZARDS_PER_THREAD);
+        Self {
+            id: next_id(),
+            records: AtomicPtr::new(ptr::null_mut()),
+            hazards_per_handle: k,
+            counter: AtomicUsize::new(0),
+            retired: Mutex::new(Vec::new()),
+            retired_len: AtomicUsize::new(0),
+            reclaimers: AtomicUsize::new(0),
+            scans: AtomicUsize::new(0),
+            reclaimed: AtomicUsize::new(0),
+            cas_retries: AtomicUsize::new(0),
+        }
+    }
+
+    /// Hands out hazard slots for a new handle, reusing released ones
+    pub(crate) fn register(&self) -> Slot {
+        for record in self.records() {
+            /* Acquire pairs with release(): the previous owner is done */
+            if !record.active.load(Ordering::Relaxed)
+                && record.active.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+            {
+                return Slot {
+                    record: NonNull::from(record),
+                };
+            }
+        }
+
+        let record = Box::into_raw(Box::new(HpRecord {
+            hazards: (0..self.hazards_per_handle).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
+            active: AtomicBool::new(true),
+            index: self.counter.fetch_add(1, Ordering::Relaxed),
+            next: ptr::null(),
+        }));
+
+        let mut head = self.records.load(Ordering::Relaxed);
+        loop {
+            /* SAFETY: not published yet, still private to us */
+            unsafe { (*record).next = head };
+            /* Release publishes the record's fields to the scans' acquire load */
+            match self.records.compare_exchange_weak(head, record, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(newhead) => head = newhead,
+            }
+        }
+
+        /* SAFETY: from Box::into_raw, so not null */
+        return Slot {
+            record: unsafe { NonNull::new_unchecked(record) },
+        };
+    }
+
+    /// Hazard slots every handle gets
+    pub fn hazards_per_handle(&self) -> usize {
+        self.hazards_per_handle
+    }
+
+    /// Hazard slots in the domain, `H` in Michael's paper. Records are
+    /// never freed, so this only grows with the most handles ever alive.
+    pub(crate) fn hazard_count(&self) -> usize {
+        self.counter.load(Ordering::Relaxed) * self.hazards_per_handle
+    }
+
+    /// Retired nodes that make a handle scan: as in Michael's paper, twice
+    /// the hazard slots, so a scan frees at least half of what it looks at
+    /// and memory stays bounded however many threads there are. Below 42
+    /// (few handles) the floor keeps scans from happening every few pops.
+    pub(crate) fn scan_threshold(&self) -> usize {
+        MIN_SCAN_THRESHOLD.max(2 * self.hazard_count())
+    }
+
+    /// Hazard slots for the calling thread, to build other lock-free
+    /// structures on the same reclamation as the stacks. Keep the guard
+    /// for as long as the thread works on the structure, registering is
+    /// not free.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicPtr, Ordering};
+    /// use stacc::hazard::Domain;
+    ///
+    /// let domain = Domain::new();
+    /// let config = AtomicPtr::new(Box::into_raw(Box::new(1)));
+    /// let mut guard = domain.guard();
+    ///
+    /// /* Readers protect before dereferencing */
+    /// let p = guard.protect(0, &config);
+    /// assert_eq!(unsafe { *p }, 1);
+    /// guard.clear(0);
+    ///
+    /// /* Writers retire what they unlinked */
+    /// let old = config.swap(Box::into_raw(Box::new(2)), Ordering::AcqRel);
+    /// unsafe { guard.retire(old) };
+    /// # drop(guard);
+    /// # drop(unsafe { Box::from_raw(config.into_inner()) });
+    /// ```
+    pub fn guard(&self) -> HazardGuard<'_> {
+        HazardGuard {
+            domain: self,
+            slot: self.register(),
+            retired: Vec::new(),
+        }
+    }
+
+    /// Every hazard record, records are never unlinked while the domain lives
+    fn records(&self) -> impl Iterator<Item = &HpRecord> {
+        let mut p = self.records.load(Ordering::Acquire) as *const HpRecord;
+        std::iter::from_fn(move || {
+            /* SAFETY: see Slot::record() */
+            let record = unsafe { p.as_ref()? };
+            p = record.next;
+            return Some(record);
+        })
+    }
+
+    /// Every currently published hazard, sorted
+    pub(crate) fn hazards(&self) -> Vec<*const u8> {
+        /* Store-buffering against the protect step of the stacks: they
+         * store a hazard and re-read top, we unlinked a node and now read
+         * hazards. Without a SeqCst fence both sides could miss each other.
+         * One fence per scan is cheap, and it lets the popping CASes be
+         * AcqRel instead of SeqCst */
+        fence(Ordering::SeqCst);
+
+        let mut v: Vec<*const u8> = self
+            .records()
+            .flat_map(|record| record.hazards.iter())
+            .map(|x| x.load(Ordering::Relaxed) as *const u8)
+            .filter(|p| !p.is_null())
+            .collect();
+
+        v.sort_unstable();
+        self.scans.fetch_add(1, Ordering::Relaxed);
+        return v;
+    }
+
+    pub(crate) fn count_reclaimed(&self, n: usize) {
+        self.reclaimed.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_cas_retries(&self, n: usize) {
+        if n != 0 {
+            self.cas_retries.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    /// Takes over nodes a handle couldn't free
+    pub(crate) fn hand_over(&self, nodes: impl Iterator<Item = Retired>) {
+        let mut lock = self.retired.lock().unwrap();
+        lock.extend(nodes);
+        self.retired_len.store(lock.len(), Ordering::Relaxed);
+    }
+
+    /// Like [`hand_over`](Self::hand_over), but gives up without calling
+    /// `f` if the list is locked by someone else
+    pub(crate) fn try_hand_over(&self, f: impl FnOnce(&mut Vec<Retired>)) -> bool {
+        let mut lock = match self.retired.try_lock() {
+            Ok(lock) => lock,
+            Err(TryLockError::WouldBlock) => return false,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        };
+        f(&mut lock);
+        self.retired_len.store(lock.len(), Ordering::Relaxed);
+        return true;
+    }
+
+    /// Whether handles should leave scanning to a reclaimer thread
+    pub(crate) fn offloading(&self) -> bool {
+        self.reclaimers.load(Ordering::Relaxed) != 0
+    }
+
+    pub(crate) fn pending(&self) -> usize {
+        self.retired_len.load(Ordering::Relaxed)
+    }
+
+    /// Frees every handed-over node that is no longer protected,
+    /// returns how many were freed
+    pub fn scan(&self) -> usize {
+        let lock = self.retired.lock().unwrap();
+        return self.scan_locked(lock);
+    }
+
+    /// Like [`scan`](Self::scan), but gives up if another scan is running
+    pub(crate) fn try_scan(&self) -> usize {
+        match self.retired.try_lock() {
+            Ok(lock) => self.scan_locked(lock),
+            Err(TryLockError::WouldBlock) => 0,
+            Err(TryLockError::Poisoned(e)) => self.scan_locked(e.into_inner()),
+        }
+    }
+
+    fn scan_locked(&self, mut lock: MutexGuard<'_, Vec<Retired>>) -> usize {
+        /* Taking the list happens before reading hazards: the nodes are
+         * already unreachable, so nobody can newly protect them after that */
+        let mut rlist = std::mem::take(&mut *lock);
+        drop(lock);
+
+        let hazards = self.hazards();
+        let before = rlist.len();
+        rlist.retain(|r| {
+            if hazards.binary_search(&(r.ptr as *const u8)).is_ok() {
+                return true;
+            }
+            /* SAFETY: see Retired::new, the node is not protected anymore */
+            unsafe { (r.free)(r.ptr) };
+            return false;
+        });
+        let freed = before - rlist.len();
+        self.count_reclaimed(freed);
+
+        self.hand_over(rlist.into_iter());
+        return freed;
+    }
+
+    /// Process-unique id of the domain
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn stats(&self) -> DomainStats {
+        DomainStats {
+            handles: self.records().filter(|r| r.active.load(Ordering::Relaxed)).count(),
+            records: self.counter.load(Ordering::Relaxed),
+            scans: self.scans.load(Ordering::Relaxed),
+            reclaimed: self.reclaimed.load(Ordering::Relaxed),
+            pending: self.pending(),
+            cas_retries: self.cas_retries.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Starts a thread that scans the domain every `interval`. Until the
+    /// returned [`Reclaimer`] is dropped, handles don't scan on their own,
+    /// they hand retired nodes over to the domain in batches.
+    pub fn spawn_reclaimer(self: &Arc<Self>, interval: Duration) -> Reclaimer {
+        let stop = Arc::new(AtomicBool::new(false));
+        self.reclaimers.fetch_add(1, Ordering::Relaxed);
+
+        let domain = Arc::clone(self);
+        let should_stop = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            while !should_stop.load(Ordering::Acquire) {
+                domain.scan();
+                thread::park_timeout(interval);
+            }
+            domain.reclaimers.fetch_sub(1, Ordering::Relaxed);
+            /* Whatever was handed over meanwhile */
+            domain.scan();
+        });
+
+        Reclaimer {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl std::fmt::Debug for Domain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Domain")
+            .field("id", &self.id)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Domain {
+    fn drop(&mut self) {
+        /* No handle is left, so nothing is protected anymore */
+        let v = self.retired.get_mut().unwrap_or_else(|e| e.into_inner());
+        for r in v.drain(..) {
+            /* SAFETY: see Retired::new */
+            unsafe { (r.free)(r.ptr) };
+        }
+
+        let mut p = *self.records.get_mut();
+        while !p.is_null() {
+            /* SAFETY: from Box::into_raw in register(), nobody else is left */
+            let record = unsafe { Box::from_raw(p) };
+            p = record.next as *mut HpRecord;
+        }
+    }
+}
+
+/// One thread's hazard slots in a [`Domain`], see [`Domain::guard`].
+/// Dropping it clears the slots and hands the nodes it couldn't free yet
+/// over to the domain.
+pub struct HazardGuard<'d> {
+    domain: &'d Domain,
+    slot: Slot,
+    /* Scanned once it reaches the domain's scan threshold */
+    retired: Vec<Retired>,
+}
+
+impl HazardGuard<'_> {
+    /// Loads `src` and protects the pointer in slot `k`, retrying until
+    /// `src` still holds it after protecting. The result can be
+    /// dereferenced until slot `k` is cleared or reused, as long as
+    /// whoever unlinks it retires it in the same domain.
+    pub fn protect<N>(&self, k: usize, src: &AtomicPtr<N>) -> *mut N {
+        let mut p = src.load(Ordering::Acquire);
+        loop {
+            self.slot.protect(k, p);
+            /* SeqCst for the same reason as in the stacks' pop() */
+            let newer = src.load(Ordering::SeqCst);
+            if newer == p {
+                return p;
+            }
+            p = newer;
+        }
+    }
+
+    /// Protects a pointer the caller already knows to be reachable, e.g.
+    /// the `next` of a protected node that is checked again afterwards
+    pub fn protect_raw<N>(&self, k: usize, p: *mut N) {
+        self.slot.protect(k, p);
+    }
+
+    /// Clears slot `k`, its pointer may be freed from now on
+    pub fn clear(&self, k: usize) {
+        self.slot.hazard::<u8>(k).store(ptr::null_mut(), Ordering::Release);
+    }
+
+    /// Hazard slots this guard has
+    pub fn hazards(&self) -> usize {
+        self.domain.hazards_per_handle()
+    }
+
+    /// Frees `ptr` once no hazard slot of the domain protects it anymore,
+    /// maybe on another thread
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `Box::into_raw`, be unreachable for threads
+    /// that didn't protect it yet, and be retired only once.
+    pub unsafe fn retire<N: Send>(&mut self, ptr: *mut N) {
+        self.retired.push(Retired::new(ptr));
+        if self.retired.len() >= self.domain.scan_threshold() {
+            self.scan();
+        }
+    }
+
+    /// Frees every node this guard retired that is no longer protected,
+    /// returns how many were freed
+    pub fn scan(&mut self) -> usize {
+        let hazards = self.domain.hazards();
+        let before = self.retired.len();
+        self.retired.retain(|r| {
+            if hazards.binary_search(&(r.ptr as *const u8)).is_ok() {
+                return true;
+            }
+            /* SAFETY: see retire(), the node is not protected anymore */
+            unsafe { (r.free)(r.ptr) };
+            return false;
+        });
+        let freed = before - self.retired.len();
+        self.domain.count_reclaimed(freed);
+        return freed;
+    }
+}
+
+impl Drop for HazardGuard<'_> {
+    fn drop(&mut self) {
+        self.slot.clear();
+        self.scan();
+        self.domain.hand_over(self.retired.drain(..));
+        self.slot.release();
+    }
+}
+
+impl std::fmt::Debug for HazardGuard<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HazardGuard")
+            .field("domain", &self.domain.id())
+            .field("index", &self.slot.index())
+            .field("retired", &self.retired.len())
+            .finish()
+    }
+}
+
+/// Background scanning of a [`Domain`], see [`Domain::spawn_reclaimer`].
+/// Dropping it stops and joins the thread.
+pub struct Reclaimer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Reclaimer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,216 @@
+/* Deduplicating store for large, immutable payloads.
+ *
+ * The table is a fixed array of raw `Arc` pointers with open addressing.
+ * Lookups never lock: a candidate is protected with a hazard pointer of the
+ * interner's own domain before its refcount is touched, so purge() can take
+ * entries out concurrently and retire the table's reference through the
+ * domain, just like the stacks retire their nodes.
+ *
+ * Removed entries leave a tombstone behind, so probe chains stay intact,
+ * and inserts reuse the first tombstone they pass. Deduplication is best
+ * effort: a value purged while someone is cloning it, or two racing
+ * inserts into different tombstones, may leave two copies alive. */
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::AtomicPtr;
+use std::sync::Arc;
+
+use crate::hazard::{Domain, Retired, Slot};
+use crate::ordering::Ordering;
+
+/// Hands out one shared `Arc<T>` per distinct value
+///
+/// ```
+/// let interner = stacc::interner::Interner::with_capacity(16);
+/// let a = interner.intern(String::from("large payload"));
+/// let b = interner.intern(String::from("large payload"));
+/// assert!(std::sync::Arc::ptr_eq(&a, &b));
+/// ```
+pub struct Interner<T, S = RandomState> {
+    /* Null is a never used entry, tombstone() a removed one,
+     * anything else came from Arc::into_raw and holds one reference */
+    table: Box<[AtomicPtr<T>]>,
+    hasher: S,
+    domain: Domain,
+}
+
+/* SAFETY: the table owns Arcs, which are shared and dropped on any thread */
+unsafe impl<T: Send + Sync, S: Send> Send for Interner<T, S> {}
+unsafe impl<T: Send + Sync, S: Sync> Sync for Interner<T, S> {}
+
+fn tombstone<T>() -> *mut T {
+    /* Never a heap address, so never the data pointer of an Arc */
+    NonNull::dangling().as_ptr()
+}
+
+fn is_entry<T>(p: *mut T) -> bool {
+    !p.is_null() && p != tombstone()
+}
+
+impl<T: Eq + Hash> Interner<T> {
+    /// Interner with room for at least `capacity` distinct values
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Interner<T, S> {
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        /* Keep probe chains short, at most half of the table is used */
+        let n = capacity.max(1).saturating_mul(2).next_power_of_two();
+        Self {
+            table: (0..n).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
+            hasher,
+            domain: Domain::new(),
+        }
+    }
+
+    /// Number of entries in the table, twice the requested capacity
+    pub fn capacity(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns the shared copy of `value`, storing it first if it isn't
+    /// there yet. If the table is full, `value` is returned in an `Arc` of
+    /// its own, without being stored.
+    pub fn intern(&self, value: T) -> Arc<T> {
+        let mask = self.table.len() - 1;
+        let start = self.hasher.hash_one(&value) as usize & mask;
+        let slot = self.domain.register();
+
+        let mut free = None;
+        let mut found = None;
+        for i in 0..self.table.len() {
+            let entry = &self.table[(start + i) & mask];
+            let mut p = entry.load(Ordering::Acquire);
+            if p.is_null() {
+                free = free.or(Some(entry));
+                break;
+            }
+            if p == tombstone() {
+                free = free.or(Some(entry));
+                continue;
+            }
+
+            p = protect_entry(&slot, entry, p);
+            if !is_entry(p) {
+                continue;
+            }
+            /* SAFETY: `p` is protected and was still in the table after
+             * protecting it, so the table's reference is alive */
+            if unsafe { *p == value } {
+                found = Some(unsafe { clone_raw(p) });
+                break;
+            }
+        }
+        slot.clear();
+        slot.release();
+
+        if let Some(arc) = found {
+            return arc;
+        }
+
+        let entry = match free {
+            Some(entry) => entry,
+            None => return Arc::new(value),
+        };
+
+        /* Our own reference keeps a concurrent purge() away from it */
+        let arc = Arc::new(value);
+        let raw = Arc::into_raw(Arc::clone(&arc)) as *mut T;
+        let current = entry.load(Ordering::Relaxed);
+        if !is_entry(current) && entry.compare_exchange(current, raw, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            return arc;
+        }
+
+        /* Lost the race for the free entry, maybe to the very same value */
+        /* SAFETY: never published, we still own this reference */
+        drop(unsafe { Arc::from_raw(raw) });
+        let value = match Arc::try_unwrap(arc) {
+            Ok(value) => value,
+            Err(_) => unreachable!("the Arc was never shared"),
+        };
+        return self.intern(value);
+    }
+
+    /// Takes out every value nobody but the interner refers to, returns
+    /// how many were removed. Their memory is freed once no concurrent
+    /// lookup looks at them anymore.
+    pub fn purge(&self) -> usize {
+        let slot = self.domain.register();
+        let mut retired = Vec::new();
+        for entry in self.table.iter() {
+            let mut p = entry.load(Ordering::Acquire);
+            if !is_entry(p) {
+                continue;
+            }
+            p = protect_entry(&slot, entry, p);
+            if !is_entry(p) {
+                continue;
+            }
+
+            /* SAFETY: protected and reachable, see intern() */
+            let unused = unsafe { strong_count(p) } == 1;
+            if unused && entry.compare_exchange(p, tombstone(), Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+                /* SAFETY: unlinked by us, so the reference is ours now */
+                retired.push(unsafe { Retired::arc(p as *const T) });
+            }
+        }
+        slot.clear();
+        slot.release();
+
+        let n = retired.len();
+        self.domain.hand_over(retired.into_iter());
+        self.domain.try_scan();
+        return n;
+    }
+}
+
+/* Protects `p`, loaded from `entry`, until it is still in `entry` after
+ * protecting it, like HazardGuard::protect. Whatever it returns is either
+ * not an entry or safe to dereference until the slot is cleared */
+fn protect_entry<T>(slot: &Slot, entry: &AtomicPtr<T>, mut p: *mut T) -> *mut T {
+    loop {
+        slot.protect(0, p);
+        /* SeqCst for the same reason as in the stacks' pop() */
+        let newer = entry.load(Ordering::SeqCst);
+        if newer == p {
+            return p;
+        }
+        p = newer;
+    }
+}
+
+/* Caller must make sure the table's reference behind `p` stays alive */
+unsafe fn clone_raw<T>(p: *const T) -> Arc<T> {
+    Arc::increment_strong_count(p);
+    return Arc::from_raw(p);
+}
+
+unsafe fn strong_count<T>(p: *const T) -> usize {
+    let arc = std::mem::ManuallyDrop::new(Arc::from_raw(p));
+    return Arc::strong_count(&arc);
+}
+
+impl<T, S> Drop for Interner<T, S> {
+    fn drop(&mut self) {
+        for entry in self.table.iter_mut() {
+            let p = *entry.get_mut();
+            if is_entry(p) {
+                /* SAFETY: the table's own reference, nobody else can see it */
+                drop(unsafe { Arc::from_raw(p as *const T) });
+            }
+        }
+        /* Dropping the domain drops what purge() retired */
+    }
+}
+
+impl<T, S> std::fmt::Debug for Interner<T, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interner")
+            .field("capacity", &self.table.len())
+            .finish_non_exhaustive()
+    }
+}
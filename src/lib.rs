@@ -1,4 +1,28 @@
-pub mod spsc_queue;
+/* Early returns are the house style here */
+#![allow(clippy::needless_return)]
+#![allow(clippy::new_without_default)]
+
+mod ordering;
+
+pub(crate) use stacc_core::next_id;
+
+/* `no_std` primitives, re-exported so paths stay the same */
+pub use stacc_core::{cache_padded, drop_counter, drop_policy, frozen, intercept, irq_spsc, progress, req_res, spsc_queue, stacc_lockfree_ebr, ticket_stack, weight, AllocError, Full, HandleLimitReached, MAX_THREADS};
+/* Tagged 64-bit heads, see index_stack */
+#[cfg(target_has_atomic = "64")]
+pub use stacc_core::{index_stack, slab_stacc};
+
+pub mod byte_pipe;
+pub mod cancel;
+pub mod hazard;
+pub mod interner;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod pipeline;
+pub mod select;
+pub mod semaphore;
+#[cfg(feature = "sim")]
+pub mod sim;
 pub mod stacc;
 pub mod stacc_lockfree_hp;
-pub mod stacc_lockfree_ebr;
+pub mod threads;
@@ -0,0 +1,114 @@
+/* Prometheus text exposition of the counters the structures keep anyway
+ * (lengths, reclamation stats) plus the ones `Stacc` collects with the
+ * `metrics` feature (misses, swaps).
+ *
+ *   let mut out = String::new();
+ *   stack.render_metrics(&mut out, &[("queue", "jobs")]);
+ *   domain.render_metrics(&mut out, &[]);
+ *
+ * HELP and TYPE lines are written once per metric name, so many structures
+ * can be rendered into the same buffer, told apart by their labels. Every
+ * sample also gets an `id` label with the structure's id(). */
+
+use std::fmt::Write;
+
+use crate::hazard::Domain;
+use crate::spsc_queue::{QueueConsumer, QueueProducer};
+#[cfg(not(feature = "no-stats"))]
+use crate::stacc_lockfree_hp::LockFreeStacc;
+
+/// Something that can describe its health in Prometheus text format
+pub trait Metrics {
+    /// Appends the metrics to `out`, every sample gets `labels`
+    fn render_metrics(&self, out: &mut String, labels: &[(&str, &str)]);
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Kind {
+    Counter,
+    Gauge,
+}
+
+/// `labels` plus the structure's id
+pub(crate) fn with_id<'a>(labels: &[(&'a str, &'a str)], id: &'a str) -> Vec<(&'a str, &'a str)> {
+    let mut v = labels.to_vec();
+    v.push(("id", id));
+    return v;
+}
+
+/// Writes a single sample, with its HELP and TYPE lines if they aren't in `out` yet
+pub(crate) fn write_sample(out: &mut String, name: &str, help: &str, kind: Kind, labels: &[(&str, &str)], value: usize) {
+    let kind = match kind {
+        Kind::Counter => "counter",
+        Kind::Gauge => "gauge",
+    };
+    let type_line = format!("# TYPE {} {}\n", name, kind);
+    if !out.contains(&type_line) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        out.push_str(&type_line);
+    }
+
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (key, value)) in labels.iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"", key);
+            for c in value.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '"' => out.push_str("\\\""),
+                    '\n' => out.push_str("\\n"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/* The length is its only metric */
+#[cfg(not(feature = "no-stats"))]
+impl<T> Metrics for LockFreeStacc<T> {
+    fn render_metrics(&self, out: &mut String, labels: &[(&str, &str)]) {
+        let id = self.id().to_string();
+        let labels = &with_id(labels, &id);
+        write_sample(out, "stacc_lockfree_len", "Elements in the stack", Kind::Gauge, labels, self.approx_len());
+    }
+}
+
+impl Metrics for Domain {
+    fn render_metrics(&self, out: &mut String, labels: &[(&str, &str)]) {
+        let id = self.id().to_string();
+        let labels = &with_id(labels, &id);
+        let stats = self.stats();
+        write_sample(out, "stacc_hazard_handles", "Handles registered in the domain", Kind::Gauge, labels, stats.handles);
+        write_sample(out, "stacc_hazard_scans_total", "Hazard scans", Kind::Counter, labels, stats.scans);
+        write_sample(out, "stacc_hazard_reclaimed_total", "Nodes reclaimed", Kind::Counter, labels, stats.reclaimed);
+        write_sample(out, "stacc_hazard_pending", "Nodes waiting for a domain scan", Kind::Gauge, labels, stats.pending);
+        write_sample(out, "stacc_hazard_cas_retries_total", "Failed CASes on the stacks' tops", Kind::Counter, labels, stats.cas_retries);
+    }
+}
+
+impl<T> Metrics for QueueProducer<T> {
+    fn render_metrics(&self, out: &mut String, labels: &[(&str, &str)]) {
+        let id = self.id().to_string();
+        let labels = &with_id(labels, &id);
+        write_sample(out, "stacc_queue_len", "Items in the SPSC queue", Kind::Gauge, labels, self.approx_len());
+        write_sample(out, "stacc_queue_capacity", "Capacity of the SPSC queue", Kind::Gauge, labels, self.capacity());
+        write_sample(out, "stacc_queue_weight", "Weight of the items in the SPSC queue", Kind::Gauge, labels, self.weight_used());
+    }
+}
+
+impl<T> Metrics for QueueConsumer<T> {
+    fn render_metrics(&self, out: &mut String, labels: &[(&str, &str)]) {
+        let id = self.id().to_string();
+        let labels = &with_id(labels, &id);
+        write_sample(out, "stacc_queue_len", "Items in the SPSC queue", Kind::Gauge, labels, self.approx_len());
+        write_sample(out, "stacc_queue_capacity", "Capacity of the SPSC queue", Kind::Gauge, labels, self.capacity());
+    }
+}
@@ -0,0 +1,3 @@
+/* The shim lives in stacc-core, see stacc-core/src/ordering.rs */
+pub(crate) use stacc_core::assert_ordering;
+pub(crate) use stacc_core::ordering::Ordering;
@@ -0,0 +1,134 @@
+/* Chains SPSC queues between stages, each stage running on its own thread.
+ *
+ *   input -> [queue] -> stage 1 -> [queue] -> stage 2 -> [queue] -> output
+ *
+ * Backpressure comes from the queues being bounded: a stage whose output
+ * queue is full just waits. Shutdown cascades: once a stage's input side
+ * is gone and its queue is drained, the stage exits and drops its own
+ * producer, which in turn stops the next stage.
+ */
+
+use std::thread::{self, JoinHandle};
+
+use crate::spsc_queue::{queue, QueueConsumer, QueueProducer};
+
+pub struct Pipeline<In, Out> {
+    input: QueueProducer<In>,
+    output: QueueConsumer<Out>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send> Pipeline<T, T> {
+    /// Empty pipeline, items come out the same way they went in
+    pub fn new() -> Self {
+        let (input, output) = queue();
+        Self {
+            input,
+            output,
+            threads: Vec::new(),
+        }
+    }
+}
+
+impl<In: Send, Out: Send + 'static> Pipeline<In, Out> {
+    /// Appends a stage running `f` on a dedicated thread
+    pub fn stage<U, F>(self, mut f: F) -> Pipeline<In, U>
+    where
+        U: Send + 'static,
+        F: FnMut(Out) -> U + Send + 'static,
+    {
+        let Self {
+            input,
+            output: mut rx,
+            mut threads,
+        } = self;
+        let (mut tx, output) = queue();
+
+        threads.push(thread::spawn(move || {
+            while let Some(x) = recv(&mut rx) {
+                if send(&mut tx, f(x)).is_err() {
+                    /* Nobody listens anymore */
+                    return;
+                }
+            }
+        }));
+
+        Pipeline {
+            input,
+            output,
+            threads,
+        }
+    }
+
+    /// Non-blocking push into the first stage, gives the item back if full
+    #[must_use = "the item is handed back if the first stage is full"]
+    pub fn push(&mut self, x: In) -> Option<In> {
+        self.input.push(x)
+    }
+
+    /// Pushes into the first stage, waiting while it is full.
+    /// Nothing drains the output meanwhile, so if the whole pipeline
+    /// is full this waits forever, use [`push`](Self::push) and
+    /// [`pop`](Self::pop) when feeding and draining from one thread.
+    /// Gives the item back if the first stage is gone, e.g. after a panic.
+    #[must_use = "the item is handed back if the first stage is gone"]
+    pub fn send(&mut self, x: In) -> Result<(), In> {
+        send(&mut self.input, x)
+    }
+
+    /// Non-blocking pop from the last stage
+    pub fn pop(&mut self) -> Option<Out> {
+        self.output.pop()
+    }
+
+    /// Closes the input, waits for every stage to finish and returns
+    /// whatever came out of the last one. Panics from stages are propagated.
+    pub fn finish(self) -> Vec<Out> {
+        let Self {
+            input,
+            mut output,
+            threads,
+        } = self;
+        drop(input);
+
+        let mut v = Vec::new();
+        while let Some(x) = recv(&mut output) {
+            v.push(x);
+        }
+
+        for t in threads {
+            if let Err(e) = t.join() {
+                std::panic::resume_unwind(e);
+            }
+        }
+        return v;
+    }
+}
+
+/// Waits for an item, returns None once the producer is gone and the queue is drained
+fn recv<T>(rx: &mut QueueConsumer<T>) -> Option<T> {
+    loop {
+        if let Some(x) = rx.pop() {
+            return Some(x);
+        }
+        if !rx.other_side_alive() {
+            /* The producer might have pushed right before leaving */
+            return rx.pop();
+        }
+        thread::yield_now();
+    }
+}
+
+/// Waits for a free slot, gives `x` back if the consumer is gone
+fn send<T>(tx: &mut QueueProducer<T>, mut x: T) -> Result<(), T> {
+    loop {
+        x = match tx.push(x) {
+            None => return Ok(()),
+            Some(x) => x,
+        };
+        if !tx.other_side_alive() {
+            return Err(x);
+        }
+        thread::yield_now();
+    }
+}
@@ -0,0 +1,161 @@
+/* Waiting on several structures at once.
+ *
+ * None of the structures can tell a sleeping consumer that something
+ * arrived (the SPSC queue is `no_std` and never blocks), so producers say
+ * it themselves: after pushing, they notify the Select every consumer
+ * waits on. That is an event count, so a consumer never misses a push,
+ * and producers skip the lock while nobody waits.
+ *
+ * The consumer goes to sleep only if it saw no push *and* no notify since
+ * it started waiting:
+ *
+ *   producer: push (release), epoch += 1 (SeqCst), load waiters (SeqCst)
+ *   consumer: waiters += 1 (SeqCst), key = epoch (SeqCst), check sources
+ *
+ * If the consumer read the new epoch, the push is visible to its check.
+ * If it read the old one, its waiters increment came first in the SeqCst
+ * order, so the producer sees it and takes the lock to wake it up. */
+
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::ordering::Ordering;
+use crate::spsc_queue::QueueConsumer;
+use crate::stacc::Stacc;
+use crate::stacc_lockfree_hp::{HpStack, LockFreeStacc};
+
+/// Something a consumer can wait for, see [`Select`]
+pub trait Ready {
+    /// Whether a pop would (likely) find something right now
+    fn is_ready(&self) -> bool;
+}
+
+impl<T> Ready for QueueConsumer<T> {
+    fn is_ready(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<T> Ready for LockFreeStacc<T> {
+    fn is_ready(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<T> Ready for HpStack<T> {
+    fn is_ready(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<T> Ready for Stacc<T> {
+    fn is_ready(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+/// Lets a consumer sleep until any of its sources has items. Producers of
+/// every source have to call [`notify`](Self::notify) after pushing.
+///
+/// ```
+/// use stacc::select::Select;
+///
+/// let select = Select::new();
+/// let (mut tx, mut commands) = stacc::spsc_queue::queue::<u32>();
+/// let mut work = stacc::stacc_lockfree_hp::HpStack::<u32>::new().handle();
+///
+/// assert_eq!(tx.push(1), None);
+/// select.notify();
+///
+/// match select.wait(&[&commands, &work]) {
+///     0 => assert_eq!(commands.pop(), Some(1)),
+///     _ => assert!(work.pop().is_some()),
+/// }
+/// ```
+pub struct Select {
+    epoch: AtomicUsize,
+    waiters: AtomicUsize,
+    lock: Mutex<()>,
+    wakeup: Condvar,
+}
+
+impl Select {
+    pub fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            waiters: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            wakeup: Condvar::new(),
+        }
+    }
+
+    /// Wakes every waiting consumer, cheap when there are none
+    pub fn notify(&self) {
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) != 0 {
+            /* Taking the lock makes sure the sleeper is already waiting */
+            drop(self.lock.lock());
+            self.wakeup.notify_all();
+        }
+    }
+
+    /// Index of the first source that is ready, never waits
+    pub fn ready(&self, sources: &[&dyn Ready]) -> Option<usize> {
+        sources.iter().position(|source| source.is_ready())
+    }
+
+    /// Waits until one of the `sources` is ready, returns its index
+    pub fn wait(&self, sources: &[&dyn Ready]) -> usize {
+        loop {
+            if let Some(i) = self.wait_until(sources, None) {
+                return i;
+            }
+        }
+    }
+
+    /// Like [`wait`](Self::wait), but gives up after `timeout`
+    pub fn wait_timeout(&self, sources: &[&dyn Ready], timeout: Duration) -> Option<usize> {
+        self.wait_until(sources, Some(Instant::now() + timeout))
+    }
+
+    /* Sleeps at most once, so a notify for some other consumer's source
+     * can't keep us here past the deadline */
+    fn wait_until(&self, sources: &[&dyn Ready], deadline: Option<Instant>) -> Option<usize> {
+        if let Some(i) = self.ready(sources) {
+            return Some(i);
+        }
+
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let key = self.epoch.load(Ordering::SeqCst);
+        let mut ready = self.ready(sources);
+
+        if ready.is_none() {
+            let mut guard = self.lock.lock();
+            while self.epoch.load(Ordering::SeqCst) == key {
+                match deadline {
+                    None => self.wakeup.wait(&mut guard),
+                    Some(deadline) => {
+                        if self.wakeup.wait_until(&mut guard, deadline).timed_out() {
+                            break;
+                        }
+                    }
+                }
+            }
+            drop(guard);
+            ready = self.ready(sources);
+        }
+
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        return ready;
+    }
+}
+
+impl std::fmt::Debug for Select {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Select")
+            .field("waiters", &self.waiters.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
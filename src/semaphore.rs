@@ -0,0 +1,158 @@
+/* Counting semaphore, for bounding concurrency around the unbounded
+ * lock-free stacks: take a permit before pushing, give it back after the
+ * matching pop, and the number of in-flight items never exceeds the count.
+ *
+ * Waiters park on a condvar (parking_lot, like the blocking Stacc), and a
+ * CancellationToken can get them out, same as the blocking pops. */
+
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::cancel::{CancellationToken, Cancelled};
+
+struct Inner {
+    permits: Mutex<usize>,
+    available: Condvar,
+}
+
+impl Wake for Inner {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        /* Taking the lock makes sure the sleeper is already waiting */
+        drop(self.permits.lock());
+        self.available.notify_all();
+    }
+}
+
+/// Counting semaphore, permits are given back when the [`Permit`] drops
+///
+/// ```
+/// let sem = stacc::semaphore::Semaphore::new(1);
+/// let permit = sem.acquire();
+/// assert!(sem.try_acquire().is_none());
+/// drop(permit);
+/// assert!(sem.try_acquire().is_some());
+/// ```
+pub struct Semaphore {
+    inner: Arc<Inner>,
+}
+
+/// One permit of a [`Semaphore`], released on drop
+#[must_use = "the permit is released right away if it is not kept"]
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        let inner = Inner {
+            permits: Mutex::new(permits),
+            available: Condvar::new(),
+        };
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Permits that can be taken right now
+    pub fn available_permits(&self) -> usize {
+        *self.inner.permits.lock()
+    }
+
+    /// Takes a permit, waiting for one if there is none
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut permits = self.inner.permits.lock();
+        while *permits == 0 {
+            self.inner.available.wait(&mut permits);
+        }
+        *permits -= 1;
+        return Permit { semaphore: self };
+    }
+
+    /// Takes a permit if there is one, never waits
+    pub fn try_acquire(&self) -> Option<Permit<'_>> {
+        let mut permits = self.inner.permits.lock();
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        return Some(Permit { semaphore: self });
+    }
+
+    /// Like [`acquire`](Self::acquire), but gives up after `timeout`
+    pub fn acquire_timeout(&self, timeout: Duration) -> Option<Permit<'_>> {
+        let deadline = Instant::now() + timeout;
+        let mut permits = self.inner.permits.lock();
+        while *permits == 0 {
+            if self.inner.available.wait_until(&mut permits, deadline).timed_out() && *permits == 0 {
+                return None;
+            }
+        }
+        *permits -= 1;
+        return Some(Permit { semaphore: self });
+    }
+
+    /// Like [`acquire`](Self::acquire), but gives up once `token` is cancelled
+    pub fn acquire_cancellable(&self, token: &CancellationToken) -> Result<Permit<'_>, Cancelled> {
+        /* A cancelled token has to get us out of Condvar::wait */
+        let waker = Waker::from(Arc::clone(&self.inner));
+        let id = token.register(&waker).ok_or(Cancelled)?;
+
+        let mut permits = self.inner.permits.lock();
+        while *permits == 0 && !token.is_cancelled() {
+            self.inner.available.wait(&mut permits);
+        }
+        let acquired = *permits != 0;
+        if acquired {
+            *permits -= 1;
+        }
+        drop(permits);
+
+        token.deregister(id);
+        if !acquired {
+            return Err(Cancelled);
+        }
+        return Ok(Permit { semaphore: self });
+    }
+
+    /// Adds `n` permits, e.g. to raise the limit at runtime, or to give
+    /// back permits that were [`forget`](Permit::forget)-ed
+    pub fn release(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        *self.inner.permits.lock() += n;
+        if n == 1 {
+            self.inner.available.notify_one();
+        } else {
+            self.inner.available.notify_all();
+        }
+    }
+}
+
+impl Permit<'_> {
+    /// Keeps the permit taken after the guard is gone, it has to be given
+    /// back with [`Semaphore::release`]. Useful when the item the permit
+    /// stands for outlives the borrow, e.g. it sits in a stack.
+    pub fn forget(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.release(1);
+    }
+}
+
+impl std::fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("available", &self.available_permits())
+            .finish()
+    }
+}
@@ -0,0 +1,192 @@
+/* Deterministic simulation of handle interleavings, for reproducing races
+ * in tests and bug reports.
+ *
+ * Every virtual handle runs a script (a closure). Scripts are backed by OS
+ * threads, but those only serve as coroutines: exactly one of them runs at
+ * any time, and control changes hands only at the yield points compiled
+ * into the lock-free stacks (see stacc_core::sim_hook). Which handle runs
+ * next is picked by the schedule, so a run is fully determined by it.
+ *
+ *   let trace = Sim::new().handle(|| ...).handle(|| ...).run(Schedule::Random(7));
+ *   // same interleaving again
+ *   Sim::new().handle(|| ...).handle(|| ...).run(Schedule::replay(&trace));
+ *
+ * Scripts must not block on each other (blocking pops, locks held across
+ * a yield point...), the run would deadlock. compare_exchange_weak may
+ * still fail spuriously on LL/SC hardware, which adds retries. */
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use stacc_core::sim_hook;
+
+/// How the next handle is picked at every yield point
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    /// Pseudo-random, from the seed
+    Random(u64),
+    /// The given handles in order. A finished handle, or the end of the
+    /// list, falls back to the lowest unfinished one.
+    Fixed(Vec<usize>),
+}
+
+impl Schedule {
+    /// The schedule that reproduces `trace`
+    pub fn replay(trace: &[Step]) -> Self {
+        Schedule::Fixed(trace.iter().map(|step| step.handle).collect())
+    }
+}
+
+/// One step of a run: `handle` ran until it reached `point`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub handle: usize,
+    /// Name of the yield point, `"done"` when the script returned
+    pub point: &'static str,
+}
+
+const NOBODY: usize = usize::MAX;
+
+struct State {
+    current: usize,
+    finished: Vec<bool>,
+    trace: Vec<Step>,
+    schedule: Schedule,
+    decisions: usize,
+}
+
+impl State {
+    fn pick(&mut self) -> usize {
+        let unfinished: Vec<usize> = (0..self.finished.len()).filter(|&i| !self.finished[i]).collect();
+        if unfinished.is_empty() {
+            return NOBODY;
+        }
+
+        let n = self.decisions;
+        self.decisions += 1;
+        return match &mut self.schedule {
+            Schedule::Random(seed) => {
+                /* xorshift, the seed doubles as the state */
+                *seed ^= *seed << 13;
+                *seed ^= *seed >> 7;
+                *seed ^= *seed << 17;
+                unfinished[(*seed % unfinished.len() as u64) as usize]
+            }
+            Schedule::Fixed(order) => match order.get(n) {
+                Some(&i) if unfinished.contains(&i) => i,
+                _ => unfinished[0],
+            },
+        };
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    turn: Condvar,
+}
+
+impl Shared {
+    fn wait_turn(&self, me: usize) {
+        let state = self.state.lock().unwrap();
+        drop(self.turn.wait_while(state, |s| s.current != me).unwrap());
+    }
+
+    fn switch(&self, me: usize, point: &'static str) {
+        let mut state = self.state.lock().unwrap();
+        state.trace.push(Step { handle: me, point });
+        state.current = state.pick();
+        self.turn.notify_all();
+        drop(self.turn.wait_while(state, |s| s.current != me).unwrap());
+    }
+
+    fn finish(&self, me: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.trace.push(Step { handle: me, point: "done" });
+        state.finished[me] = true;
+        state.current = state.pick();
+        self.turn.notify_all();
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<(Arc<Shared>, usize)>> = const { RefCell::new(None) };
+}
+
+fn on_yield(point: &'static str) {
+    /* Threads outside of a simulation run freely */
+    let current = CURRENT.with(|c| c.borrow().clone());
+    if let Some((shared, me)) = current {
+        shared.switch(me, point);
+    }
+}
+
+/// A set of virtual handles and their scripts
+pub struct Sim<'a> {
+    scripts: Vec<Box<dyn FnOnce() + Send + 'a>>,
+}
+
+impl<'a> Sim<'a> {
+    pub fn new() -> Self {
+        Self { scripts: Vec::new() }
+    }
+
+    /// Adds a virtual handle running `script`, its number is the number of
+    /// handles added before it
+    pub fn handle(mut self, script: impl FnOnce() + Send + 'a) -> Self {
+        self.scripts.push(Box::new(script));
+        return self;
+    }
+
+    /// Runs every script to completion, interleaved by `schedule`, and
+    /// returns the trace. A panic in a script is re-raised once the others
+    /// have finished.
+    pub fn run(self, schedule: Schedule) -> Vec<Step> {
+        sim_hook::set_hook(on_yield);
+
+        let n = self.scripts.len();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                current: NOBODY,
+                finished: vec![false; n],
+                trace: Vec::new(),
+                schedule,
+                decisions: 0,
+            }),
+            turn: Condvar::new(),
+        });
+
+        let panics = thread::scope(|scope| {
+            let threads: Vec<_> = self
+                .scripts
+                .into_iter()
+                .enumerate()
+                .map(|(i, script)| {
+                    let shared = Arc::clone(&shared);
+                    scope.spawn(move || {
+                        CURRENT.with(|c| *c.borrow_mut() = Some((Arc::clone(&shared), i)));
+                        shared.wait_turn(i);
+                        let result = panic::catch_unwind(AssertUnwindSafe(script));
+                        CURRENT.with(|c| *c.borrow_mut() = None);
+                        shared.finish(i);
+                        result.err()
+                    })
+                })
+                .collect();
+
+            {
+                let mut state = shared.state.lock().unwrap();
+                state.current = state.pick();
+                shared.turn.notify_all();
+            }
+            threads.into_iter().filter_map(|t| t.join().unwrap()).collect::<Vec<_>>()
+        });
+
+        if let Some(payload) = panics.into_iter().next() {
+            panic::resume_unwind(payload);
+        }
+        let state = shared.state.lock().unwrap();
+        return state.trace.clone();
+    }
+}
@@ -1,21 +1,29 @@
 use std::cell::UnsafeCell;
-use std::mem::MaybeUninit;
+use std::mem::{ManuallyDrop, MaybeUninit};
 use std::ptr;
-use std::sync::{
-    atomic::{AtomicIsize, Ordering},
-    Arc,
-};
+use std::sync::atomic::{self, AtomicIsize, AtomicUsize};
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::thread;
 
 /* We need parking_lot's implementation of RwLock, because it guarantees some fairness */
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{Condvar, Mutex, RwLock, RwLockReadGuard};
+
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::drop_policy::{DropPolicy, PolicyCell};
+use crate::ordering::Ordering;
+use crate::next_id;
+use crate::weight::{Weigher, WeightBudget};
 
 pub(crate) struct AtomicPop<T> {
     slice: Box<[MaybeUninit<UnsafeCell<T>>]>,
     len: AtomicIsize,
 }
 
-unsafe impl<T> Send for AtomicPop<T> {}
-unsafe impl<T> Sync for AtomicPop<T> {}
+/* SAFETY: items are only ever moved in and out, never shared by reference,
+ * so T: Send is enough for both */
+unsafe impl<T: Send> Send for AtomicPop<T> {}
+unsafe impl<T: Send> Sync for AtomicPop<T> {}
 
 impl<T> AtomicPop<T> {
     pub(crate) fn new(n: usize) -> Self {
@@ -26,7 +34,8 @@ impl<T> AtomicPop<T> {
         Self { slice, len }
     }
 
-    pub(crate) fn pop(&self) -> Option<T> {
+    /// Claims the index of the top element
+    fn claim(&self) -> Option<usize> {
         let len = self.len.fetch_sub(1, Ordering::Acquire);
         if len == 0 {
             self.len.fetch_max(0, Ordering::Release);
@@ -35,7 +44,11 @@ impl<T> AtomicPop<T> {
             return None;
         }
 
-        let n = len as usize - 1;
+        return Some(len as usize - 1);
+    }
+
+    pub(crate) fn pop(&self) -> Option<T> {
+        let n = self.claim()?;
         /* Now only we have access to element at n */
         let item = unsafe {
             let cellref = &*self.slice[n].as_ptr();
@@ -44,15 +57,39 @@ impl<T> AtomicPop<T> {
 
         return Some(item);
     }
+
+    /// Gives `f` back if the stack is empty
+    pub(crate) fn pop_with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Result<R, F> {
+        let n = match self.claim() {
+            Some(n) => n,
+            None => return Err(f),
+        };
+
+        /* Now only we have access to element at n. The guard drops it
+         * even if `f` panics */
+        let guard = DropInPlace(unsafe { (*self.slice[n].as_ptr()).get() });
+        return Ok(f(unsafe { &mut *guard.0 }));
+    }
+}
+
+struct DropInPlace<T>(*mut T);
+
+impl<T> Drop for DropInPlace<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.0) };
+    }
 }
 
 pub(crate) struct AtomicPush<T> {
     slice: Box<[MaybeUninit<UnsafeCell<T>>]>,
     len: AtomicIsize,
+    /* Slots given up by abandoned reservations, filled before the next swap */
+    holes: Mutex<Vec<usize>>,
 }
 
-unsafe impl<T> Send for AtomicPush<T> {}
-unsafe impl<T> Sync for AtomicPush<T> {}
+/* SAFETY: see AtomicPop */
+unsafe impl<T: Send> Send for AtomicPush<T> {}
+unsafe impl<T: Send> Sync for AtomicPush<T> {}
 
 impl<T> AtomicPush<T> {
     pub(crate) fn new(n: usize) -> Self {
@@ -60,10 +97,12 @@ impl<T> AtomicPush<T> {
         unsafe { v.set_len(n) };
         let slice = v.into_boxed_slice();
         let len = AtomicIsize::new(0);
-        Self { slice, len }
+        let holes = Mutex::new(Vec::new());
+        Self { slice, len, holes }
     }
 
-    pub(crate) fn push(&self, x: T) -> Option<T> {
+    /// Claims the index of the next free slot
+    fn claim(&self) -> Option<usize> {
         /* Allocation can't be larger than isize::MAX anyway */
         let maxlen = self.slice.len() as isize;
         let oldlen = self.len.fetch_add(1, Ordering::Acquire);
@@ -73,10 +112,18 @@ impl<T> AtomicPush<T> {
         }
 
         if oldlen >= maxlen {
-            return Some(x);
+            return None;
         }
 
-        let n = oldlen as usize;
+        return Some(oldlen as usize);
+    }
+
+    pub(crate) fn push(&self, x: T) -> Option<T> {
+        let n = match self.claim() {
+            Some(n) => n,
+            None => return Some(x),
+        };
+
         /* Now we are the only one having access to self.slice[n] */
         unsafe {
             let cellref = &*self.slice[n].as_ptr();
@@ -85,23 +132,220 @@ impl<T> AtomicPush<T> {
 
         return None;
     }
+
+    /// Gives back a claimed slot that was never written
+    fn unclaim(&self, n: usize) {
+        let top = n as isize + 1;
+        if self.len.compare_exchange(top, top - 1, Ordering::Release, Ordering::Relaxed).is_err() {
+            /* Somebody claimed a slot above ours, leave a hole */
+            self.holes.lock().push(n);
+        }
+    }
+
+    /// Moves elements from the top into the holes, needs exclusive access
+    fn fill_holes(&mut self) {
+        let holes = self.holes.get_mut();
+        if holes.is_empty() {
+            return;
+        }
+
+        /* Going from the highest hole down, the top element is never a hole */
+        holes.sort_unstable_by(|a, b| b.cmp(a));
+        let len = self.len.get_mut();
+        for &hole in holes.iter() {
+            *len -= 1;
+            let top = *len as usize;
+            if hole != top {
+                unsafe {
+                    let src = (*self.slice[top].as_ptr()).get();
+                    let dst = (*self.slice[hole].as_ptr()).get();
+                    ptr::copy_nonoverlapping(src, dst, 1);
+                }
+            }
+        }
+        holes.clear();
+    }
+}
+
+/// What [`Stacc::push_blocking`] and [`Stacc::pop_blocking`] do between
+/// failed attempts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// Busy-wait with a spin loop hint, lowest latency, burns a core
+    Spin,
+    /// Give the rest of the time slice to other threads
+    Yield,
+    /// Sleep until another thread pushes or pops. Every successful push
+    /// and pop then pays an extra fence to check for sleepers.
+    Park,
 }
 
 struct StaccInner<T> {
+    /* See Stacc::id() */
+    id: u64,
     poppers: RwLock<AtomicPop<T>>,
     pushers: RwLock<AtomicPush<T>>,
     swap_lock: Mutex<()>,
+
+    backoff: Backoff,
+    /* Only used with Backoff::Park. `wakeups` counts the notifies that
+     * saw a sleeper, see wait_for() */
+    sleepers: AtomicUsize,
+    wakeups: AtomicUsize,
+    parking: Arc<Parking>,
+
+    /* See Stacc::with_weight_limit */
+    weight: Option<(WeightBudget, Weigher<T>)>,
+
+    /* What happens to the elements left when the stack goes away */
+    drop_policy: PolicyCell<T>,
+
+    #[cfg(feature = "metrics")]
+    counters: Counters,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Counters {
+    push_misses: AtomicUsize,
+    pop_misses: AtomicUsize,
+    swaps: AtomicUsize,
+}
+
+/* Separate from StaccInner, so a cancellation token can wake sleepers
+ * without knowing about T */
+struct Parking {
+    lock: Mutex<()>,
+    parked: Condvar,
+}
+
+impl Wake for Parking {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        /* Taking the lock makes sure the sleeper is already waiting */
+        drop(self.lock.lock());
+        self.parked.notify_all();
+    }
 }
 
 impl<T> StaccInner<T> {
-    fn new(n: usize) -> Self {
+    fn new(n: usize, backoff: Backoff, weight: Option<(WeightBudget, Weigher<T>)>) -> Self {
         Self {
+            id: next_id(),
             poppers: RwLock::new(AtomicPop::new(n)),
             pushers: RwLock::new(AtomicPush::new(n)),
             swap_lock: Mutex::new(()),
+            backoff,
+            sleepers: AtomicUsize::new(0),
+            wakeups: AtomicUsize::new(0),
+            parking: Arc::new(Parking {
+                lock: Mutex::new(()),
+                parked: Condvar::new(),
+            }),
+            weight,
+            drop_policy: PolicyCell::new(),
+            #[cfg(feature = "metrics")]
+            counters: Counters::default(),
+        }
+    }
+
+    /// Takes the weight of `x` out of the budget, false if it doesn't fit
+    fn acquire_weight(&self, x: &T) -> bool {
+        match &self.weight {
+            Some((budget, weigh)) => budget.try_acquire(weigh(x)),
+            None => true,
+        }
+    }
+
+    /// Like acquire_weight(), but may go over the limit
+    fn charge_weight(&self, x: &T) {
+        if let Some((budget, weigh)) = &self.weight {
+            budget.charge(weigh(x));
+        }
+    }
+
+    /// Called for every element leaving the stack
+    fn release_weight(&self, x: &T) {
+        if let Some((budget, weigh)) = &self.weight {
+            budget.release(weigh(x));
         }
     }
 
+    /// Called after anything that might unblock a waiting push or pop
+    fn notify(&self) {
+        if self.backoff != Backoff::Park {
+            return;
+        }
+
+        /* Store-buffering against wait_for(): either we see the sleeper,
+         * or its retry sees our push/pop */
+        atomic::fence(Ordering::SeqCst);
+        if self.sleepers.load(Ordering::SeqCst) != 0 {
+            self.wakeups.fetch_add(1, Ordering::SeqCst);
+            self.parking.wake_by_ref();
+        }
+    }
+
+    /// Retries `attempt` until it succeeds, backing off in between.
+    /// Returns `None` only if `token` got cancelled.
+    fn wait_for<R>(&self, token: Option<&CancellationToken>, mut attempt: impl FnMut() -> Option<R>) -> Option<R> {
+        /* A cancelled token has to get us out of Condvar::wait */
+        let registered = match (token, self.backoff) {
+            (Some(token), Backoff::Park) => {
+                let waker = Waker::from(Arc::clone(&self.parking));
+                Some(token.register(&waker)?)
+            }
+            _ => None,
+        };
+        let cancelled = || token.is_some_and(|t| t.is_cancelled());
+
+        let r = loop {
+            if let Some(r) = attempt() {
+                break Some(r);
+            }
+            if cancelled() {
+                break None;
+            }
+
+            match self.backoff {
+                Backoff::Spin => std::hint::spin_loop(),
+                Backoff::Yield => thread::yield_now(),
+                Backoff::Park => {
+                    /* The retry runs outside the lock: if it succeeds, it
+                     * notifies, and with us counted as a sleeper that takes
+                     * the lock. Instead, `wakeups` tells under the lock
+                     * whether anything happened since we registered, a
+                     * notify that missed the check finds us waiting */
+                    self.sleepers.fetch_add(1, Ordering::SeqCst);
+                    /* Pairs with the fence in notify(), the retry's loads
+                     * aren't SeqCst themselves */
+                    atomic::fence(Ordering::SeqCst);
+                    let key = self.wakeups.load(Ordering::SeqCst);
+                    let r = attempt();
+                    if r.is_none() {
+                        let mut lock = self.parking.lock.lock();
+                        if self.wakeups.load(Ordering::SeqCst) == key && !cancelled() {
+                            self.parking.parked.wait(&mut lock);
+                        }
+                    }
+                    self.sleepers.fetch_sub(1, Ordering::Relaxed);
+
+                    if r.is_some() {
+                        break r;
+                    }
+                }
+            }
+        };
+
+        if let (Some(token), Some(id)) = (token, registered) {
+            token.deregister(id);
+        }
+        return r;
+    }
+
     fn swap_stacks(&self) {
         let swap_lock = self.swap_lock.try_lock();
         if swap_lock.is_none() {
@@ -112,17 +356,19 @@ impl<T> StaccInner<T> {
         let mut poppers = self.poppers.write();
         let mut pushers = self.pushers.write();
 
+        pushers.fill_holes();
         std::mem::swap(&mut poppers.slice, &mut pushers.slice);
         std::mem::swap(&mut poppers.len, &mut pushers.len);
         drop(swap_lock);
+
+        #[cfg(feature = "metrics")]
+        self.counters.swaps.fetch_add(1, Ordering::Relaxed);
     }
 
     fn push(&self, x: T) -> Option<T> {
         let lock = self.pushers.read();
-        let x = match lock.push(x) {
-            None => return None,
-            Some(x) => x,
-        };
+        /* `None` means the item landed in the buffer */
+        let x = lock.push(x)?;
         drop(lock);
 
         let poppers = self.poppers.read();
@@ -143,6 +389,36 @@ impl<T> StaccInner<T> {
         return Some(x);
     }
 
+    fn reserve_push(&self) -> Option<SlotGuard<'_, T>> {
+        let lock = self.pushers.read();
+        if let Some(n) = lock.claim() {
+            return Some(SlotGuard {
+                stacc: self,
+                lock: ManuallyDrop::new(lock),
+                n,
+                committed: false,
+            });
+        }
+        drop(lock);
+
+        let poppers = self.poppers.read();
+        let poppers_len = poppers.len.load(Ordering::Relaxed);
+        let poppers_len = if poppers_len < 0 {
+            0usize
+        } else {
+            poppers_len as usize
+        };
+        let poppers_maxlen = poppers.slice.len();
+        drop(poppers);
+
+        if poppers_len != poppers_maxlen {
+            self.swap_stacks();
+            return self.reserve_push();
+        }
+
+        return None;
+    }
+
     fn pop(&self) -> Option<T> {
         let lock = self.poppers.read();
         if let Some(x) = lock.pop() {
@@ -167,7 +443,86 @@ impl<T> StaccInner<T> {
         return None;
     }
 
-    fn len(&self) -> usize {
+    /* Caller guarantees an element no concurrent pop can take */
+    unsafe fn pop_unchecked(&self) -> T {
+        let lock = self.poppers.read();
+        let len = lock.len.fetch_sub(1, Ordering::Acquire);
+        if len > 0 {
+            /* Now only we have access to element at len - 1 */
+            let cellref = &*lock.slice[len as usize - 1].as_ptr();
+            return ptr::read(cellref.get());
+        }
+        return self.pop_unchecked_slow(lock, len);
+    }
+
+    /* The element is still on the push side */
+    #[cold]
+    unsafe fn pop_unchecked_slow(&self, lock: RwLockReadGuard<'_, AtomicPop<T>>, len: isize) -> T {
+        /* Undo the claim, same as AtomicPop::claim() */
+        if len == 0 {
+            lock.len.fetch_max(0, Ordering::Release);
+        }
+        drop(lock);
+        return self.pop().unwrap_unchecked();
+    }
+
+    fn pop_with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Option<R> {
+        let lock = self.poppers.read();
+        let f = match lock.pop_with(f) {
+            Ok(r) => return Some(r),
+            Err(f) => f,
+        };
+        drop(lock);
+
+        let pushers = self.pushers.read();
+        let pushers_len = pushers.len.load(Ordering::Relaxed);
+        drop(pushers);
+
+        if pushers_len > 0 {
+            self.swap_stacks();
+            return self.pop_with(f);
+        }
+
+        return None;
+    }
+
+    fn map_drain<U>(&self, mut f: impl FnMut(T) -> U) -> StaccInner<U> {
+        /* Same order as swap_stacks, so we can't deadlock with it */
+        let mut poppers = self.poppers.write();
+        let mut pushers = self.pushers.write();
+        pushers.fill_holes();
+
+        let new = StaccInner::new(poppers.slice.len(), self.backoff, None);
+        {
+            let mut new_poppers = new.poppers.write();
+            let mut new_pushers = new.pushers.write();
+
+            /* Elements keep their positions, so they pop in the same order.
+             * Lengths are zeroed first: if `f` panics, the rest just leaks */
+            let (pop, push) = (&mut *poppers, &mut *pushers);
+            let (new_pop, new_push) = (&mut *new_poppers, &mut *new_pushers);
+            let pairs = [
+                (&pop.slice, pop.len.get_mut(), &mut new_pop.slice, new_pop.len.get_mut()),
+                (&push.slice, push.len.get_mut(), &mut new_push.slice, new_push.len.get_mut()),
+            ];
+            for (src, len, dst, new_len) in pairs {
+                let n = std::mem::replace(len, 0).max(0) as usize;
+                for i in 0..n {
+                    let x = unsafe { ptr::read(src[i].as_ptr()).into_inner() };
+                    self.release_weight(&x);
+                    dst[i] = MaybeUninit::new(UnsafeCell::new(f(x)));
+                    *new_len += 1;
+                }
+            }
+        }
+
+        drop(pushers);
+        drop(poppers);
+        self.notify();
+        return new;
+    }
+
+    fn approx_len(&self) -> usize {
         let len1 = self.pushers.read().len.load(Ordering::Relaxed);
         let len2 = self.poppers.read().len.load(Ordering::Relaxed);
 
@@ -176,25 +531,323 @@ impl<T> StaccInner<T> {
 
         len1 + len2
     }
+
+    fn len_exact(&self) -> usize {
+        /* Same order as swap_stacks, so we can't deadlock with it.
+         * Holding both write locks means nobody is in the middle of
+         * a fetch_add/fetch_sub, so the counters are settled */
+        let poppers = self.poppers.write();
+        let pushers = self.pushers.write();
+
+        let len1 = pushers.len.load(Ordering::Relaxed);
+        let len2 = poppers.len.load(Ordering::Relaxed);
+
+        let len1 = if len1 < 0 { 0usize } else { len1 as usize };
+        let len2 = if len2 < 0 { 0usize } else { len2 as usize };
+
+        let holes = pushers.holes.lock().len();
+        len1 - holes + len2
+    }
 }
 
+impl<T> Drop for StaccInner<T> {
+    fn drop(&mut self) {
+        let poppers = self.poppers.get_mut();
+        let pushers = self.pushers.get_mut();
+        /* No guard can be alive anymore, abandoned slots are holes */
+        pushers.fill_holes();
+
+        let mut leaked = 0;
+        for (slice, len) in [(&poppers.slice, poppers.len.get_mut()), (&pushers.slice, pushers.len.get_mut())] {
+            let n = std::mem::replace(len, 0).max(0) as usize;
+            for cell in slice[..n].iter().rev() {
+                /* SAFETY: elements below len are initialized */
+                let x = unsafe { ptr::read(cell.as_ptr()).into_inner() };
+                leaked += self.drop_policy.dispose(x) as usize;
+            }
+        }
+        self.drop_policy.report_leaks(std::any::type_name::<Stacc<T>>(), leaked);
+    }
+}
+
+/// A claimed, not yet published slot, see [`Stacc::reserve_push`].
+/// Dropping it without committing gives the slot back, but leaks an
+/// element already written through [`slot`](Self::slot), see
+/// [`discard`](Self::discard).
+#[must_use = "dropping the guard gives the slot back without pushing anything"]
+pub struct SlotGuard<'a, T> {
+    stacc: &'a StaccInner<T>,
+    /* Released in drop(), before notifying sleepers */
+    lock: ManuallyDrop<RwLockReadGuard<'a, AtomicPush<T>>>,
+    n: usize,
+    committed: bool,
+}
+
+impl<T> SlotGuard<'_, T> {
+    /// The reserved slot, write the element here. Dropping the guard
+    /// without committing doesn't drop what was written, it is leaked,
+    /// use [`discard`](Self::discard) for that.
+    pub fn slot(&mut self) -> &mut MaybeUninit<T> {
+        /* UnsafeCell<T> has the same layout as T */
+        let cell = self.lock.slice[self.n].as_ptr();
+        return unsafe { &mut *(UnsafeCell::raw_get(cell) as *mut MaybeUninit<T>) };
+    }
+
+    /// Publishes the element
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been initialized through [`slot`](Self::slot)
+    pub unsafe fn commit(mut self) {
+        self.stacc.charge_weight(self.slot().assume_init_ref());
+        self.committed = true;
+    }
+
+    /// Writes `x` into the slot and publishes it
+    pub fn write(mut self, x: T) {
+        self.stacc.charge_weight(&x);
+        self.slot().write(x);
+        self.committed = true;
+    }
+
+    /// Drops the element written through [`slot`](Self::slot) and gives
+    /// the slot back, without publishing anything
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been initialized through [`slot`](Self::slot)
+    pub unsafe fn discard(mut self) {
+        /* The slot is given back first, a panicking drop must not leave
+         * it claimed */
+        let x = self.slot().assume_init_read();
+        drop(self);
+        drop(x);
+    }
+}
+
+impl<T> Drop for SlotGuard<'_, T> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.lock.unclaim(self.n);
+        }
+        /* The slot is settled once the lock is gone, either way */
+        unsafe { ManuallyDrop::drop(&mut self.lock) };
+        self.stacc.notify();
+    }
+}
+
+/// Bounded stack shared between threads by cloning.
+///
+/// `Stacc<T>` is `Send` and `Sync` only when `T: Send`:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<stacc::stacc::Stacc<std::rc::Rc<()>>>();
+/// ```
+///
+/// It takes locks, so it can't stand in where lock-free pops are required:
+///
+/// ```compile_fail
+/// fn assert_lock_free_pop<S: stacc::progress::LockFreePop>() {}
+/// assert_lock_free_pop::<stacc::stacc::Stacc<u32>>();
+/// ```
 pub struct Stacc<T> {
     inner: Arc<StaccInner<T>>,
 }
 
 impl<T> Stacc<T> {
     pub fn new(n: usize) -> Self {
-        let inner = Arc::new(StaccInner::new(n));
+        Self::with_backoff(n, Backoff::Yield)
+    }
+    /// Like [`new`](Self::new), with the way blocking operations wait
+    pub fn with_backoff(n: usize, backoff: Backoff) -> Self {
+        let inner = Arc::new(StaccInner::new(n, backoff, None));
+        Self { inner }
+    }
+    /// Like [`new`](Self::new), but elements also cost `weigh(&x)` out of
+    /// a budget of `limit`, e.g. their size in bytes. Pushes fail once
+    /// either the slots or the budget run out. Elements written through
+    /// [`reserve_push`](Self::reserve_push) are only weighed on commit,
+    /// so they may go over the limit.
+    ///
+    /// ```
+    /// let v = stacc::stacc::Stacc::with_weight_limit(16, 8, |s: &String| s.len());
+    /// assert_eq!(v.push("hello".to_string()), None);
+    /// assert!(v.push("world".to_string()).is_some());
+    /// assert_eq!(v.weight_used(), 5);
+    /// ```
+    pub fn with_weight_limit(n: usize, limit: usize, weigh: Weigher<T>) -> Self {
+        let weight = Some((WeightBudget::new(limit), weigh));
+        let inner = Arc::new(StaccInner::new(n, Backoff::Yield, weight));
         Self { inner }
     }
+    /// Weight of the elements in the stack, always 0 unless it was
+    /// created with [`with_weight_limit`](Self::with_weight_limit)
+    pub fn weight_used(&self) -> usize {
+        self.inner.weight.as_ref().map_or(0, |(budget, _)| budget.used())
+    }
+    #[must_use = "the item is handed back if there is no room, use push_or_drop() to discard it"]
     pub fn push(&self, x: T) -> Option<T> {
-        self.inner.push(x)
+        if !self.inner.acquire_weight(&x) {
+            return Some(x);
+        }
+        let rejected = self.inner.push(x);
+        match &rejected {
+            None => self.inner.notify(),
+            Some(x) => {
+                self.inner.release_weight(x);
+                #[cfg(feature = "metrics")]
+                self.inner.counters.push_misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        return rejected;
+    }
+    /// Pushes `x`, or drops it if the stack is full, for call sites where
+    /// losing the item is fine. Returns whether it was pushed.
+    pub fn push_or_drop(&self, x: T) -> bool {
+        self.push(x).is_none()
+    }
+    /// Pushes, waiting for free space according to the [`Backoff`]
+    pub fn push_blocking(&self, x: T) {
+        let pushed = self.push_waiting(x, None);
+        debug_assert!(pushed.is_ok());
+    }
+    /// Like [`push_blocking`](Self::push_blocking), but gives up and returns
+    /// the element once `token` is cancelled
+    pub fn push_blocking_cancellable(&self, x: T, token: &CancellationToken) -> Result<(), T> {
+        self.push_waiting(x, Some(token))
+    }
+    fn push_waiting(&self, x: T, token: Option<&CancellationToken>) -> Result<(), T> {
+        let mut x = Some(x);
+        let pushed = self.inner.wait_for(token, || match self.push(x.take()?) {
+            None => Some(()),
+            Some(back) => {
+                x = Some(back);
+                None
+            }
+        });
+
+        return match pushed {
+            Some(()) => Ok(()),
+            None => Err(x.take().unwrap()),
+        };
+    }
+    /// Claims a slot for an element to be built in place, `None` if full.
+    /// The element becomes visible on [`commit`](SlotGuard::commit),
+    /// dropping the guard gives the slot back.
+    /// Buffers can't be swapped while a guard is alive, so don't push or pop
+    /// on the same thread before committing, that may deadlock.
+    ///
+    /// ```
+    /// let v = stacc::stacc::Stacc::new(1);
+    /// let mut slot = v.reserve_push().unwrap();
+    /// slot.slot().write([0u64; 64]);
+    /// unsafe { slot.commit() };
+    /// assert_eq!(v.pop(), Some([0u64; 64]));
+    /// ```
+    #[must_use = "the slot is given back right away if the guard is dropped"]
+    pub fn reserve_push(&self) -> Option<SlotGuard<'_, T>> {
+        self.inner.reserve_push()
     }
     pub fn pop(&self) -> Option<T> {
-        self.inner.pop()
+        let x = self.inner.pop();
+        #[cfg(feature = "metrics")]
+        if x.is_none() {
+            self.inner.counters.pop_misses.fetch_add(1, Ordering::Relaxed);
+        }
+        let x = x?;
+        self.inner.release_weight(&x);
+        self.inner.notify();
+        return Some(x);
+    }
+    /// Pops without checking for an empty stack, for hot loops that know
+    /// how many elements there are. The fast path is a single atomic
+    /// subtraction, without the miss handling of [`pop`](Self::pop).
+    ///
+    /// # Safety
+    ///
+    /// The stack must hold an element that no concurrent pop can take,
+    /// e.g. this is the only thread popping and it pushed more than it
+    /// popped so far.
+    ///
+    /// ```
+    /// let v = stacc::stacc::Stacc::new(4);
+    /// assert_eq!(v.push(1), None);
+    /// assert_eq!(unsafe { v.pop_unchecked() }, 1);
+    /// ```
+    pub unsafe fn pop_unchecked(&self) -> T {
+        let x = self.inner.pop_unchecked();
+        self.inner.release_weight(&x);
+        self.inner.notify();
+        return x;
+    }
+    /// Pops, waiting for an element according to the [`Backoff`]
+    pub fn pop_blocking(&self) -> T {
+        self.inner.wait_for(None, || self.pop()).unwrap()
+    }
+    /// Like [`pop_blocking`](Self::pop_blocking), but gives up once
+    /// `token` is cancelled
+    pub fn pop_blocking_cancellable(&self, token: &CancellationToken) -> Result<T, Cancelled> {
+        self.inner.wait_for(Some(token), || self.pop()).ok_or(Cancelled)
+    }
+    /// Pops an element and hands it to `f` by reference, right where it
+    /// lies in the buffer, then drops it. Avoids moving large `T`s around.
+    /// Swapping the buffers waits until `f` returns, so keep it short.
+    pub fn pop_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let r = self.inner.pop_with(|x: &mut T| {
+            /* Before `f` gets a chance to change the weight */
+            self.inner.release_weight(x);
+            f(x)
+        })?;
+        self.inner.notify();
+        return Some(r);
+    }
+    /// Moves every element through `f` into a new stack with the same
+    /// capacity and [`Backoff`], leaving this one empty. The new stack
+    /// has no weight limit, `weigh` can't apply to `U`. Pushes and pops
+    /// on this stack wait until it's done, so the migration is atomic.
+    /// If `f` panics, the elements not mapped yet are leaked.
+    pub fn map_drain<U>(&self, f: impl FnMut(T) -> U) -> Stacc<U> {
+        let inner = Arc::new(self.inner.map_drain(f));
+        Stacc { inner }
+    }
+    /// What happens to the elements still in the stack when the last
+    /// handle goes away
+    pub fn set_drop_policy(&self, policy: DropPolicy<T>) {
+        self.inner.drop_policy.set(policy);
     }
+    /// Process-unique id of the stack, the same for all its clones
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+    /// Same as [`approx_len`](Self::approx_len)
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.inner.approx_len()
+    }
+    /// Whether [`approx_len`](Self::approx_len) is 0, just as racy
+    pub fn is_empty(&self) -> bool {
+        self.inner.approx_len() == 0
+    }
+    /// Cheap, racy length. Counters are read without stopping other threads,
+    /// so the result may be off while pushes, pops or a swap are in flight.
+    pub fn approx_len(&self) -> usize {
+        self.inner.approx_len()
+    }
+    /// Exact length at some point in time. Briefly blocks all pushes and pops.
+    pub fn len_exact(&self) -> usize {
+        self.inner.len_exact()
+    }
+}
+
+/* Pushes and pops take the buffers' read locks, a swap takes the write locks */
+impl<T> crate::progress::Blocking for Stacc<T> {}
+
+impl<T> std::fmt::Debug for Stacc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stacc")
+            .field("id", &self.id())
+            .field("len", &self.approx_len())
+            .finish_non_exhaustive()
     }
 }
 
@@ -205,3 +858,26 @@ impl<T> Clone for Stacc<T> {
         }
     }
 }
+
+#[cfg(feature = "metrics")]
+impl<T> crate::metrics::Metrics for Stacc<T> {
+    fn render_metrics(&self, out: &mut String, labels: &[(&str, &str)]) {
+        use crate::metrics::{with_id, write_sample, Kind};
+
+        let id = self.id().to_string();
+        let labels = &with_id(labels, &id);
+
+        let inner = &self.inner;
+        let counters = &inner.counters;
+        let capacity = 2 * inner.poppers.read().slice.len();
+        write_sample(out, "stacc_len", "Elements in the stack", Kind::Gauge, labels, inner.approx_len());
+        write_sample(out, "stacc_capacity", "Capacity of the stack", Kind::Gauge, labels, capacity);
+        write_sample(out, "stacc_weight", "Weight of the elements in the stack", Kind::Gauge, labels, self.weight_used());
+        let push_misses = counters.push_misses.load(Ordering::Relaxed);
+        write_sample(out, "stacc_push_misses_total", "Pushes that found the stack full", Kind::Counter, labels, push_misses);
+        let pop_misses = counters.pop_misses.load(Ordering::Relaxed);
+        write_sample(out, "stacc_pop_misses_total", "Pops that found the stack empty", Kind::Counter, labels, pop_misses);
+        let swaps = counters.swaps.load(Ordering::Relaxed);
+        write_sample(out, "stacc_swaps_total", "Buffer swaps", Kind::Counter, labels, swaps);
+    }
+}
@@ -2,187 +2,864 @@
  * https://cs.nyu.edu/courses/fall16/CSCI-GA.3033-017/readings/hazard_pointers.pdf
  */
 
-use std::marker::PhantomData;
+use std::alloc::Layout;
+use std::future::Future;
 use std::mem::MaybeUninit;
-use std::ptr;
+use std::pin::Pin;
+use std::ptr::{self, NonNull};
 use std::sync::{atomic::*, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 
-/* 32, because arrays implement Default only up to 32 elements :( */
-const MAX_THREADS: usize = 32;
-const R: usize = 42;
+use crate::drop_policy::{DropPolicy, PolicyCell};
+use crate::frozen::FrozenStack;
+use crate::intercept::{InterceptCell, Op};
+use crate::hazard::{Domain, DomainStats, Retired, Slot};
+use crate::ordering::{assert_ordering, Ordering};
+use crate::{next_id, AllocError, Full};
+use stacc_core::limit::NodeLimit;
+use stacc_core::load::LenShard;
+#[cfg(not(feature = "no-stats"))]
+use stacc_core::load::{LoadEstimate, ShardedLen};
+use stacc_core::prefetch::{prefetch_read, spans_lines};
+use stacc_core::sim_hook::yield_point;
 
+/* Retired nodes a handle may keep after a scan (as a multiple of the
+ * retire threshold), the rest is spilled to the domain */
+const SPILL_FACTOR: usize = 4;
+/* Cached nodes freed between deadline checks in maintain() */
+const MAINTAIN_CHUNK: usize = 64;
+/* Nodes allocated at once when a handle's cache runs dry, see get_node().
+ * A chunk is freed only with its last node, so one long-lived node keeps
+ * the whole chunk around */
+const NODE_CHUNK: usize = 64;
+/* Nodes a consumer handle keeps cached, see hint_consumer() */
+const CONSUMER_CACHE: usize = 16;
+
+/* When a handle scans its retired list */
+#[derive(Clone, Copy)]
+enum Threshold {
+    /// Set with with_retire_threshold()
+    Fixed(usize),
+    /// Domain::scan_threshold() times this many quarters, see the role hints
+    Adaptive(usize),
+}
+
+/// A stack node, opaque outside the crate. Like the handles, it is `Send`
+/// only when the payload is:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<stacc::stacc_lockfree_hp::Node<std::rc::Rc<()>>>();
+/// ```
 pub struct Node<T> {
     data: MaybeUninit<T>,
     next: *const Node<T>,
+    /* Null for boxed nodes */
+    chunk: *mut Chunk<T>,
 }
 
 /* Well, if you happen to own a Node, it means it is outside of stack.
- * That means you can do whatever you want with it */
+ * That means you can do whatever you want with it, as long as the payload
+ * itself may cross threads. Node is deliberately opaque outside the crate. */
 unsafe impl<T: Send> Send for Node<T> {}
 
+/* With the `poison` feature, reclaimed nodes are filled with garbage
+ * and every dereference checks for it, so reclamation bugs show up
+ * as a clear panic instead of a random crash much later */
+#[cfg(feature = "poison")]
+const POISON_BYTE: u8 = 0xa5;
+#[cfg(feature = "poison")]
+const POISON_NEXT: usize = 0xdead_beef;
+
 impl<T> Node<T> {
-    pub fn uninit() -> Self {
-        Self {
+    /// A node without an element, to push one with
+    /// [`LockFreeStacc::push_node`]
+    pub fn empty() -> Box<Self> {
+        Box::new(Node {
             data: MaybeUninit::uninit(),
-            next: 0 as *const Self,
+            next: ptr::null(),
+            chunk: ptr::null_mut(),
+        })
+    }
+
+    #[cfg(feature = "poison")]
+    fn poison(&mut self) {
+        /* SAFETY: data was already moved out, the bytes are garbage anyway */
+        unsafe {
+            ptr::write_bytes(self.data.as_mut_ptr() as *mut u8, POISON_BYTE, std::mem::size_of::<T>());
         }
+        self.next = ptr::without_provenance(POISON_NEXT);
+    }
+
+    #[cfg(not(feature = "poison"))]
+    fn poison(&mut self) {}
+
+    /// Panics if `node` was reclaimed (only with the `poison` feature)
+    #[cfg(feature = "poison")]
+    unsafe fn check_poison(node: *const Self) {
+        assert!(
+            (*node).next.addr() != POISON_NEXT,
+            "hazard pointer violation: dereferenced reclaimed node {:p}",
+            node
+        );
+    }
+
+    #[cfg(not(feature = "poison"))]
+    unsafe fn check_poison(_node: *const Self) {}
+}
+
+/* NODE_CHUNK nodes in one allocation, for locality and fewer trips to
+ * the allocator. The nodes are handed out separately and may end up in
+ * any handle (or the domain), so each one is freed on its own, see
+ * free_node() */
+struct Chunk<T> {
+    /* Nodes not freed yet */
+    live: AtomicUsize,
+    nodes: [Node<T>; NODE_CHUNK],
+}
+
+/// Frees a node whose data was moved out already
+///
+/// # Safety
+///
+/// `node` must be owned by the caller, it is gone afterwards
+unsafe fn free_node<T>(node: *mut Node<T>) {
+    let chunk = (*node).chunk;
+    if chunk.is_null() {
+        drop(Box::from_raw(node));
+        return;
+    }
+    /* Release/Acquire as in Arc: every use of the other nodes happens
+     * before the chunk is deallocated */
+    if (*chunk).live.fetch_sub(1, Ordering::Release) == 1 {
+        fence(Ordering::Acquire);
+        std::alloc::dealloc(chunk as *mut u8, Layout::new::<Chunk<T>>());
+    }
+}
+
+/* For nodes handed over to the domain */
+unsafe fn free_retired<T>(node: *mut u8) {
+    free_node(node as *mut Node<T>);
+}
+
+/* A node in a handle's cache, its data was moved out. It may live in a
+ * chunk, so it can't be a Box */
+struct CachedNode<T>(NonNull<Node<T>>);
+
+impl<T> CachedNode<T> {
+    /// # Safety
+    ///
+    /// `node` must be owned by the caller, with its data moved out
+    unsafe fn new(node: *mut Node<T>) -> Self {
+        Self(NonNull::new_unchecked(node))
+    }
+
+    fn into_raw(self) -> *mut Node<T> {
+        let node = self.0.as_ptr();
+        std::mem::forget(self);
+        return node;
+    }
+}
+
+impl<T> Drop for CachedNode<T> {
+    fn drop(&mut self) {
+        /* SAFETY: the cache owns the node */
+        unsafe { free_node(self.0.as_ptr()) };
     }
 }
 
 struct Shared<T> {
     top: AtomicPtr<Node<T>>,
-    hazard_pointers: [AtomicPtr<Node<T>>; MAX_THREADS],
-    _marker: PhantomData<Box<T>>,
+    /* See LockFreeStacc::id() */
+    id: u64,
+
+    /* Hazard slots and thread numbers. If a LockFreeStacc is being dropped,
+     * or its retired list grows too long, the pointers that are still
+     * marked as hazard are handed over to the domain too */
+    domain: Arc<Domain>,
 
-    /* If a LockFreeStacc is being dropped, but some pointers are still marked as
-     * hazard, they end up here */
-    boxes_that_are_still_hazard: Mutex<Vec<*const Node<T>>>,
-    /* Used to give unique ID for each thread */
-    counter: AtomicUsize,
+    /* (Optional) Purely for statistics, sharded by handle number so
+     * handles don't contend on it */
+    #[cfg(not(feature = "no-stats"))]
+    len: ShardedLen,
+    /* Average of `len`, sampled by add_len() and sub_len() */
+    #[cfg(not(feature = "no-stats"))]
+    load: LoadEstimate,
 
-    /* (Optional) Purely for statistics, is updated using relaxed ordering */
-    len: AtomicUsize,
+    /* One waker slot per handle, indexed by thread number, used by poll_pop.
+     * `waiters` counts the occupied slots, so push can skip the lock */
+    wakers: Mutex<Vec<Option<Waker>>>,
+    waiters: AtomicUsize,
+
+    /* See with_max_nodes() */
+    limit: Option<NodeLimit>,
+    /* What happens to the elements left when the stack goes away */
+    drop_policy: PolicyCell<T>,
+    /* See set_interceptor() */
+    intercept: InterceptCell<T>,
 }
 
 impl<T> Shared<T> {
-    fn new() -> Self {
+    fn new(domain: Arc<Domain>) -> Self {
         Self {
             top: AtomicPtr::new(ptr::null_mut()),
-            hazard_pointers: Default::default(),
-            boxes_that_are_still_hazard: Mutex::new(Vec::new()),
-            counter: AtomicUsize::new(0),
-            len: AtomicUsize::new(0),
-            _marker: PhantomData,
+            id: next_id(),
+            domain,
+            #[cfg(not(feature = "no-stats"))]
+            len: ShardedLen::new(),
+            #[cfg(not(feature = "no-stats"))]
+            load: LoadEstimate::new(),
+            wakers: Mutex::new(Vec::new()),
+            waiters: AtomicUsize::new(0),
+            limit: None,
+            drop_policy: PolicyCell::new(),
+            intercept: InterceptCell::new(),
+        }
+    }
+
+    /// Called after something was linked onto `top`
+    fn notify_pushed(&self) {
+        /* Store-buffering against poll_pop(): the push CAS isn't SeqCst,
+         * so this fence pairs with the one after register_waker. Either we
+         * see the waiter, or the waiter's retry sees our push */
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) != 0 {
+            self.wake_one();
         }
     }
+
+    fn wake_one(&self) {
+        let mut wakers = self.wakers.lock().unwrap();
+        let waker = wakers.iter_mut().find_map(|slot| slot.take());
+        drop(wakers);
+
+        if let Some(waker) = waker {
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+            waker.wake();
+        }
+    }
+
+    fn register_waker(&self, thread_number: usize, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        if wakers.len() <= thread_number {
+            wakers.resize(thread_number + 1, None);
+        }
+
+        let slot = &mut wakers[thread_number];
+        match slot {
+            Some(old) if old.will_wake(waker) => {}
+            Some(old) => *old = waker.clone(),
+            None => {
+                *slot = Some(waker.clone());
+                self.waiters.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Returns true if the slot was still registered, false if a push
+    /// has already taken the waker out of it
+    fn deregister_waker(&self, thread_number: usize) -> bool {
+        let mut wakers = self.wakers.lock().unwrap();
+        let taken = wakers.get_mut(thread_number).and_then(|slot| slot.take());
+        drop(wakers);
+
+        if taken.is_some() {
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+        return taken.is_some();
+    }
 }
 
-impl<T> Drop for Shared<T> {
-    fn drop(&mut self) {
-        let v: &mut Vec<_> = self.boxes_that_are_still_hazard.get_mut().unwrap();
+impl<T> Shared<T> {
+    /* The len counter is compiled out with `no-stats`, the node count is
+     * only kept with a limit, so by default the ops skip both */
+    #[inline]
+    fn add_len(&self, _shard: &mut LenShard, n: usize) {
+        if let Some(limit) = &self.limit {
+            limit.add(n);
+        }
+        #[cfg(not(feature = "no-stats"))]
+        if self.len.add(_shard, n) {
+            self.load.update(self.len.get());
+        }
+    }
 
-        for ptr in v.iter().copied() {
-            /* SAFETY: pointer is from Box::into_raw and we are the only ones having it */
-            debug_assert!(!ptr.is_null());
-            let boxed = unsafe { Box::from_raw(ptr as *mut Node<T>) };
-            drop(boxed);
+    #[inline]
+    fn sub_len(&self, _shard: &mut LenShard, n: usize) {
+        if let Some(limit) = &self.limit {
+            limit.sub(n);
+        }
+        #[cfg(not(feature = "no-stats"))]
+        if self.len.sub(_shard, n) {
+            self.load.update(self.len.get());
         }
+    }
 
-        let mut top = *self.top.get_mut();
+    fn count_nodes(&mut self) -> usize {
+        let mut n = 0;
+        let mut top = *self.top.get_mut() as *const Node<T>;
         while !top.is_null() {
-            /* SAFETY: the pointer is non-null, so it must come from Box::into_raw */
-            let mut boxed = unsafe { Box::from_raw(top) };
-            /* SAFETY: boxed.data must be initialized, because its on stack */
-            unsafe { ptr::drop_in_place(boxed.data.as_mut_ptr()); }
+            /* SAFETY: we have exclusive access, so every node on the list is alive */
+            top = unsafe { (*top).next };
+            n += 1;
+        }
+        return n;
+    }
 
-            let next = boxed.next;
-            drop(boxed);
-            top = next as *mut _;
+    /// Drops and deallocates up to `budget` nodes from the top of the stack,
+    /// returns how many were freed
+    fn free_nodes(&mut self, budget: usize) -> usize {
+        let top = self.top.get_mut();
+        let mut freed = 0;
+        let mut leaked = 0;
+        while freed < budget && !top.is_null() {
+            let node = *top;
+            /* SAFETY: the node is on the stack, so its data is initialized
+             * and we have exclusive access to it */
+            let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+            leaked += self.drop_policy.dispose(data) as usize;
+
+            *top = next as *mut _;
+            /* SAFETY: unlinked above, data was moved out */
+            unsafe { free_node(node) };
+            freed += 1;
         }
+        self.drop_policy.report_leaks(std::any::type_name::<Self>(), leaked);
+        return freed;
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        self.free_nodes(usize::MAX);
     }
 }
 
+/// Per-thread handle to a hazard-pointer protected stack.
+///
+/// Handles can be sent to other threads only when `T: Send`:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<stacc::stacc_lockfree_hp::LockFreeStacc<std::rc::Rc<()>>>();
+/// ```
+///
+/// and are never `Sync`:
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<stacc::stacc_lockfree_hp::LockFreeStacc<u32>>();
+/// ```
 pub struct LockFreeStacc<T> {
     shared: Arc<Shared<T>>,
     retired_pointers: Vec<*const Node<T>>,
-    thread_number: usize,
+    /* Retired nodes that trigger a scan */
+    retire_threshold: Threshold,
+    /* Our hazard pointers, the index doubles as the thread number */
+    slot: Slot,
 
     /* (Optional) reduces calls to alloc() and dealloc() */
-    pub cached_allocations: Vec<Box<Node<T>>>,
+    cached_allocations: Vec<CachedNode<T>>,
+    /* Reclaimed nodes beyond this are freed instead of cached */
+    cache_limit: usize,
+
+    /* (Optional) FIFO-ish mode, see set_fairness_window() */
+    fairness_window: Option<usize>,
+    pops_since_reverse: usize,
+
+    /* Whether our slot in Shared::wakers may be occupied */
+    waker_registered: bool,
+
+    /* See stats(), plain counters since the handle is never shared */
+    counters: HandleStats,
+    /* CAS retries not yet added to the domain's count */
+    unreported_retries: usize,
+    /* Which part of the len counter this handle updates */
+    len_shard: LenShard,
 }
 
-/* SAFETY: This structure is prepared to be used on multiple threads */
+/// Counters of one handle, see [`LockFreeStacc::stats`]. The domain wide
+/// ones are in [`DomainStats`](crate::hazard::DomainStats).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandleStats {
+    /// Nodes this handle retired
+    pub retired: usize,
+    /// Retired nodes still waiting in this handle's list
+    pub pending: usize,
+    /// Scans of the retired list
+    pub scans: usize,
+    /// Nodes reclaimed by those scans, including help with the domain's list
+    pub reclaimed: usize,
+    /// Failed CASes on the top of the stack, a measure of contention
+    pub cas_retries: usize,
+    /// Nodes cached for the next pushes, reclaimed ones and the spares of
+    /// the last chunk allocated
+    pub cached: usize,
+}
+
+/* SAFETY: This structure is prepared to be used on multiple threads.
+ * Handles are moved, never shared, so there is no Sync impl. */
 unsafe impl<T: Send> Send for LockFreeStacc<T> {}
 
 impl<T> LockFreeStacc<T> {
     pub fn new() -> Self {
-        let shared = Shared::new();
+        Self::with_domain(&Arc::new(Domain::new()))
+    }
+
+    /// New stack whose handles use the hazard slots of `domain`, together
+    /// with every other stack created in it.
+    pub fn with_domain(domain: &Arc<Domain>) -> Self {
+        Self::from_shared(Arc::new(Shared::new(Arc::clone(domain))))
+    }
+
+    /// New handle to an existing stack, with default settings
+    fn from_shared(shared: Arc<Shared<T>>) -> Self {
+        let slot = shared.domain.register();
         Self {
-            thread_number: shared.counter.fetch_add(1, Ordering::Relaxed),
-            shared: Arc::new(shared),
+            len_shard: LenShard::new(slot.index()),
+            slot,
+            shared,
             retired_pointers: Vec::new(),
+            retire_threshold: Threshold::Adaptive(4),
             cached_allocations: Vec::new(),
+            cache_limit: usize::MAX,
+            fairness_window: None,
+            pops_since_reverse: 0,
+            waker_registered: false,
+            counters: HandleStats::default(),
+            unreported_retries: 0,
         }
     }
 
-    fn get_node(&mut self, node: Node<T>) -> Box<Node<T>> {
-        match self.cached_allocations.pop() {
-            None => Box::new(node),
-            Some(b) => b,
+    fn hazard(&self, k: usize) -> &AtomicPtr<Node<T>> {
+        self.slot.hazard(k)
+    }
+
+    /// Publishes `p` in our hazard slot `k`, it is still up to the caller
+    /// to check that `p` was reachable when protected
+    fn protect(&self, k: usize, p: *mut Node<T>) {
+        self.slot.protect(k, p);
+    }
+
+    /// Fills an empty cache with a fresh chunk of nodes. Handles whose
+    /// cache limit is below a chunk allocate nodes one by one instead, so
+    /// the limit keeps bounding what they hold. Does nothing if the
+    /// allocation fails, the caller falls back to a single node.
+    fn refill_cache(&mut self) {
+        if !self.cached_allocations.is_empty() || self.cache_limit < NODE_CHUNK {
+            return;
+        }
+
+        /* SAFETY: the layout has a non-zero size, there is a counter in it */
+        let chunk = unsafe { std::alloc::alloc(Layout::new::<Chunk<T>>()) } as *mut Chunk<T>;
+        if chunk.is_null() {
+            return;
+        }
+        self.cached_allocations.reserve(NODE_CHUNK);
+        /* SAFETY: the chunk is ours, every field is written before use.
+         * Pushed in reverse, so the nodes are handed out in address order */
+        unsafe {
+            ptr::addr_of_mut!((*chunk).live).write(AtomicUsize::new(NODE_CHUNK));
+            let nodes = ptr::addr_of_mut!((*chunk).nodes) as *mut Node<T>;
+            for i in (0..NODE_CHUNK).rev() {
+                let node = nodes.add(i);
+                node.write(Node {
+                    data: MaybeUninit::uninit(),
+                    next: ptr::null(),
+                    chunk,
+                });
+                self.cached_allocations.push(CachedNode::new(node));
+            }
         }
     }
-    fn prepare_for_reuse(&mut self, boxed: Box<Node<T>>) {
-        self.cached_allocations.push(boxed);
+
+    fn get_node(&mut self, data: T, next: *const Node<T>) -> *mut Node<T> {
+        self.refill_cache();
+        let node = match self.cached_allocations.pop() {
+            Some(node) => node.into_raw(),
+            None => Box::into_raw(Node::empty()),
+        };
+
+        /* Cached nodes hold stale (already moved-out) data, overwrite it.
+         * `chunk` stays, the node still lives where it was allocated.
+         * Node has no drop glue, so this can't run user code and panic */
+        /* SAFETY: the node is ours until it is published */
+        unsafe {
+            (*node).data = MaybeUninit::new(data);
+            (*node).next = next;
+        }
+        return node;
     }
 
-    fn scan(&mut self) {
-        /* It shouldn't be needed, but its just nice to have fresher data */
-        fence(Ordering::Acquire);
+    fn try_get_node(&mut self, data: T) -> Result<*mut Node<T>, T> {
+        self.refill_cache();
+        if self.cached_allocations.is_empty() {
+            let node = Node {
+                data: MaybeUninit::new(data),
+                next: ptr::null(),
+                chunk: ptr::null_mut(),
+            };
+            return match stacc_core::try_alloc_box(node) {
+                Ok(node) => Ok(Box::into_raw(node)),
+                /* SAFETY: data was just initialized above */
+                Err(node) => Err(unsafe { node.data.assume_init() }),
+            };
+        }
+        return Ok(self.get_node(data, ptr::null()));
+    }
+
+    /// Caches `node` for the next pushes, or frees it if the cache is full
+    ///
+    /// # Safety
+    ///
+    /// `node` must be owned by the caller, with its data moved out
+    unsafe fn prepare_for_reuse(&mut self, node: *mut Node<T>) {
+        if self.cached_allocations.len() >= self.cache_limit {
+            free_node(node);
+            return;
+        }
+        (*node).poison();
+        self.cached_allocations.push(CachedNode::new(node));
+    }
 
-        let mut v: Vec<*const Node<T>> = self
-            .shared
-            .hazard_pointers
-            .iter()
-            .map(|x| x.load(Ordering::Relaxed) as *const Node<T>)
-            .filter(|p| !p.is_null())
-            .collect();
+    /// Hands every retired node over to the domain
+    fn spill(&mut self) {
+        /* SAFETY: retired nodes are ours, data was moved out */
+        let nodes = self.retired_pointers.drain(..).map(|p| unsafe { Retired::with_free(p as *mut Node<T>, free_retired::<T>) });
+        self.shared.domain.hand_over(nodes);
+    }
+
+    /// Like spill(), but keeps the nodes if another thread holds the
+    /// domain's list, so push and pop never wait for a lock
+    fn try_spill(&mut self) {
+        let retired = &mut self.retired_pointers;
+        self.shared.domain.try_hand_over(|list| {
+            /* SAFETY: see spill() */
+            list.extend(retired.drain(..).map(|p| unsafe { Retired::with_free(p as *mut Node<T>, free_retired::<T>) }));
+        });
+    }
 
-        v.sort_unstable();
-        let mut rlist = std::mem::replace(&mut self.retired_pointers, Vec::new());
+    /// Returns how many nodes were reclaimed, by us or on behalf of the domain
+    fn scan(&mut self) -> usize {
+        self.report_retries();
+        /* Reads hazards with the SeqCst fence the AcqRel CAS in pop() relies on */
+        let v = self.shared.domain.hazards();
+        let is_hazard = |p: &*const Node<T>| v.binary_search(&(*p as *const u8)).is_ok();
+        let mut rlist = std::mem::take(&mut self.retired_pointers);
 
-        for ptr in rlist.iter().filter(|x| v.binary_search(x).is_err()).copied() {
-            /* SAFETY: pointer is from Box::into_raw and we are the only ones having it */
+        let mut reclaimed = 0;
+        for ptr in rlist.iter().filter(|x| !is_hazard(x)).copied() {
+            /* SAFETY: nobody protects the node, we are the only ones having it */
             debug_assert!(!ptr.is_null());
-            let boxed = unsafe { Box::from_raw(ptr as *mut Node<T>) };
-            self.prepare_for_reuse(boxed);
+            unsafe { self.prepare_for_reuse(ptr as *mut Node<T>) };
+            reclaimed += 1;
         }
-        rlist.retain(|x| v.binary_search(x).is_ok());
+        rlist.retain(is_hazard);
+        self.shared.domain.count_reclaimed(reclaimed);
 
         self.retired_pointers = rlist;
+        if self.retired_pointers.len() >= SPILL_FACTOR * self.retire_threshold() {
+            self.try_spill();
+        }
+
+        /* Help with what other handles (of any stack in the domain) left */
+        if self.shared.domain.pending() != 0 {
+            reclaimed += self.shared.domain.try_scan();
+        }
+        self.counters.scans += 1;
+        self.counters.reclaimed += reclaimed;
+        return reclaimed;
+    }
+
+    #[inline]
+    fn cas_failed(&mut self) {
+        self.counters.cas_retries += 1;
+        self.unreported_retries += 1;
+    }
+
+    fn report_retries(&mut self) {
+        self.shared.domain.count_cas_retries(std::mem::take(&mut self.unreported_retries));
+    }
+
+    fn retire_threshold(&self) -> usize {
+        match self.retire_threshold {
+            Threshold::Fixed(n) => n,
+            Threshold::Adaptive(quarters) => {
+                (self.shared.domain.scan_threshold() * quarters / 4).max(1)
+            }
+        }
     }
 
     fn retire_node(&mut self, node: *const Node<T>) {
+        self.counters.retired += 1;
         self.retired_pointers.push(node);
-        if self.retired_pointers.len() >= R {
-            self.scan();
+        if self.retired_pointers.len() >= self.retire_threshold() {
+            if self.shared.domain.offloading() {
+                self.try_spill();
+            } else {
+                self.scan();
+            }
+        }
+    }
+
+    /// Links `head..=tail` (already chained through `next`) on top of the stack
+    fn splice_chain(&mut self, head: *mut Node<T>, tail: *mut Node<T>) {
+        let mut top = self.shared.top.load(Ordering::Acquire);
+        loop {
+            /* SAFETY: the chain is still private to us */
+            unsafe {
+                (*tail).next = top;
+            }
+
+            match self
+                .shared
+                .top
+                .compare_exchange_weak(top, head, Ordering::SeqCst, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(newtop) => {
+                    self.cas_failed();
+                    top = newtop;
+                }
+            }
         }
+
+        self.shared.notify_pushed();
+    }
+
+    /// Detaches the whole stack and splices it back in reverse order,
+    /// so the oldest elements end up on top
+    fn reverse_stack(&mut self) {
+        let mut node = self.shared.top.swap(ptr::null_mut(), Ordering::AcqRel) as *const Node<T>;
+
+        /* Nodes must not be relinked in place, because other threads may
+         * still hold them as hazard and read `next`. Move the data out,
+         * retire the old nodes and build a fresh chain instead */
+        let mut head: *mut Node<T> = ptr::null_mut();
+        let mut tail: *mut Node<T> = ptr::null_mut();
+        while !node.is_null() {
+            /* SAFETY: the chain is detached, so only we can read the data,
+             * `next` is never modified while a node is reachable */
+            let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+            self.retire_node(node);
+
+            head = self.get_node(data, head);
+            if tail.is_null() {
+                tail = head;
+            }
+            node = next;
+        }
+
+        if !head.is_null() {
+            self.splice_chain(head, tail);
+        }
+    }
+
+    /// Scans for reclaimable nodes once `n` of them were retired by this
+    /// handle. Lower keeps less memory around, higher scans less often.
+    /// Clones of the handle inherit it. By default the threshold adapts to
+    /// the domain: twice its hazard slots (two per handle unless
+    /// [`Domain::with_hazards`] says otherwise), but at least 42.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new().with_retire_threshold(8);
+    /// s.push(1);
+    /// assert_eq!(s.pop(), Some(1));
+    /// ```
+    pub fn with_retire_threshold(mut self, n: usize) -> Self {
+        assert_ne!(n, 0, "retire threshold must be non-zero");
+        self.retire_threshold = Threshold::Fixed(n);
+        return self;
+    }
+
+    /// Caps how many reclaimed nodes this handle keeps for its next pushes,
+    /// the rest goes back to the allocator. Unlimited by default, which is
+    /// best for handles that push about as much as they pop. Clones inherit
+    /// the cap, the role hints below replace it.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new().with_cache_limit(0);
+    /// s.push(1);
+    /// assert_eq!(s.pop(), Some(1));
+    /// ```
+    pub fn with_cache_limit(mut self, n: usize) -> Self {
+        self.cache_limit = n;
+        self.cached_allocations.truncate(n);
+        return self;
+    }
+
+    /// Caps the stack at about `n` elements for
+    /// [`push_checked`](Self::push_checked), so a runaway producer can't
+    /// eat all memory. Racing pushes may overshoot by up to the number of
+    /// handles pushing at once. The other pushes ignore the cap, but still
+    /// count towards it. Panics if the stack already has other handles.
+    ///
+    /// ```
+    /// use stacc::stacc_lockfree_hp::LockFreeStacc;
+    ///
+    /// let mut s = LockFreeStacc::new().with_max_nodes(1);
+    /// assert!(s.push_checked(1).is_ok());
+    /// assert_eq!(s.push_checked(2).unwrap_err().0, 2);
+    /// ```
+    pub fn with_max_nodes(mut self, n: usize) -> Self {
+        let shared = Arc::get_mut(&mut self.shared).expect("with_max_nodes(): the stack already has other handles");
+        let limit = NodeLimit::new(n);
+        limit.add(shared.count_nodes());
+        shared.limit = Some(limit);
+        return self;
+    }
+
+    /// Tunes this handle for mostly pushing: it scans rarely (a producer
+    /// retires few nodes) and keeps every node it reclaims for later pushes.
+    /// Only a hint, the handle still works for both. Clones inherit it.
+    pub fn hint_producer(&mut self) {
+        self.retire_threshold = Threshold::Adaptive(8);
+        self.cache_limit = usize::MAX;
+    }
+
+    /// Tunes this handle for mostly popping: it scans four times as often,
+    /// so retired nodes go back to the allocator (and to the producers)
+    /// sooner, and caches only a few of them.
+    /// Only a hint, the handle still works for both. Clones inherit it.
+    pub fn hint_consumer(&mut self) {
+        self.retire_threshold = Threshold::Adaptive(1);
+        self.cache_limit = CONSUMER_CACHE;
+        self.cached_allocations.truncate(CONSUMER_CACHE);
+    }
+
+    /// Approximate FIFO fairness: every `window` pops, this handle reverses
+    /// the stack so that the oldest elements are served next. Useful when
+    /// strict LIFO starves old items. `None` (the default) disables it.
+    ///
+    /// There are no per-push sequence numbers, so this is no FIFO
+    /// guarantee: between two reversals pops are plain LIFO, and elements
+    /// pushed while a reversal runs land under the reversed ones. An
+    /// element may still wait for more than one window.
+    ///
+    /// Reversing is O(n) in the current length of the stack. The whole
+    /// stack is detached while it happens, so pops on other handles get
+    /// `None` (and async pops wait) until it is spliced back, even though
+    /// the stack isn't empty.
+    pub fn set_fairness_window(&mut self, window: Option<usize>) {
+        assert_ne!(window, Some(0), "fairness window must be non-zero");
+        self.fairness_window = window;
+        self.pops_since_reverse = 0;
     }
 
     pub fn push(&mut self, data: T) {
+        self.shared.intercept.call(&data, Op::Push);
+        /* The CAS below stays SeqCst, notify_pushed() relies on it */
         let mut top = self.shared.top.load(Ordering::Acquire);
-        let node = Node {
-            next: top as *const _,
-            data: MaybeUninit::new(data),
-        };
-        let node = self.get_node(node);
-        let node = Box::into_raw(node);
+        let node = self.get_node(data, top);
 
+        yield_point("hp::push::cas");
         while let Err(newtop) =
             self.shared
                 .top
-                .compare_exchange_weak(top, node, Ordering::AcqRel, Ordering::Acquire)
+                .compare_exchange_weak(top, node, Ordering::SeqCst, Ordering::Acquire)
         {
-            /* SAFETY: This pointer must be valid, because it comes from Box::into_raw above */
+            /* SAFETY: This pointer must be valid, because it comes from get_node() above */
             unsafe {
                 (*node).next = newtop;
             }
+            self.cas_failed();
             top = newtop;
         }
 
-        self.shared.len.fetch_add(1, Ordering::Relaxed);
+        self.shared.add_len(&mut self.len_shard, 1);
+        self.shared.notify_pushed();
+    }
+
+    /// Publishes every element of `chain` with a single CAS. The stack
+    /// ends up as if they were pushed one by one, in the chain's order.
+    ///
+    /// ```
+    /// use stacc::stacc_lockfree_hp::{Chain, LockFreeStacc};
+    /// let mut s = LockFreeStacc::new();
+    /// let mut chain = Chain::new();
+    /// chain.push(1);
+    /// chain.push(2);
+    /// s.push_chain(chain);
+    /// assert_eq!(s.pop(), Some(2));
+    /// assert_eq!(s.pop(), Some(1));
+    /// ```
+    pub fn push_chain(&mut self, chain: Chain<T>) {
+        if chain.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "intercept")]
+        {
+            /* Oldest first, as if pushed one by one */
+            let mut nodes = Vec::with_capacity(chain.len);
+            let mut node = chain.head as *const Node<T>;
+            while !node.is_null() {
+                nodes.push(node);
+                /* SAFETY: the chain is still private to us */
+                node = unsafe { (*node).next };
+            }
+            for &node in nodes.iter().rev() {
+                /* SAFETY: every node of a chain holds an element */
+                self.shared.intercept.call(unsafe { &*(*node).data.as_ptr() }, Op::Push);
+            }
+        }
+        /* Only now, if an interceptor panicked the chain dropped its nodes */
+        let chain = std::mem::ManuallyDrop::new(chain);
+        self.splice_chain(chain.head, chain.tail);
+        self.shared.add_len(&mut self.len_shard, chain.len);
+        /* splice_chain() woke one waiter, there is something for more */
+        for _ in 1..chain.len {
+            self.shared.notify_pushed();
+        }
+    }
+
+    /// Like [`push`](Self::push), but hands the element back if the stack
+    /// is at its [`with_max_nodes`](Self::with_max_nodes) cap
+    pub fn push_checked(&mut self, data: T) -> Result<(), Full<T>> {
+        if let Some(limit) = &self.shared.limit {
+            if limit.is_reached() {
+                return Err(Full(data));
+            }
+        }
+        self.push(data);
+        return Ok(());
+    }
+
+    /// Like [`push`](Self::push), but returns the element instead of
+    /// aborting if a node can't be allocated
+    pub fn try_push(&mut self, data: T) -> Result<(), AllocError<T>> {
+        /* Reported before the node exists, so a panic leaves nothing behind */
+        self.shared.intercept.call(&data, Op::Push);
+        let node = match self.try_get_node(data) {
+            Ok(node) => node,
+            Err(data) => return Err(AllocError(data)),
+        };
+
+        self.splice_chain(node, node);
+        self.shared.add_len(&mut self.len_shard, 1);
+        return Ok(());
     }
 
     pub fn pop(&mut self) -> Option<T> {
+        if let Some(window) = self.fairness_window {
+            self.pops_since_reverse += 1;
+            if self.pops_since_reverse >= window {
+                self.pops_since_reverse = 0;
+                self.reverse_stack();
+            }
+        }
+
         let mut top = self.shared.top.load(Ordering::Acquire);
 
         let oldtop = loop {
             /* SeqCst is _very_ important here and at the load, because without them
              * the algorithm would be incorrect. Thanks Acrimon for pointing it out! */
-            self.shared.hazard_pointers[self.thread_number].store(top, Ordering::SeqCst);
+            self.protect(0, top);
             if top.is_null() {
                 return None;
             }
 
+            yield_point("hp::pop::protected");
             let newertop = self.shared.top.load(Ordering::SeqCst); // see comment before store()
             if newertop != top {
                 top = newertop;
@@ -194,56 +871,1116 @@ impl<T> LockFreeStacc<T> {
              * Hardware can pre-fetch the result (because of speculative execution), but it
              * shouldn't change correctness of this code, because top.next is a constant.
              * Also, it shouldn't cause segfault, unlike software instruction reordering. */
-            let next = unsafe { (*top).next };
+            assert_ordering!(
+                self.hazard(0).load(Ordering::SeqCst) == top,
+                "dereferencing a node that is not published as hazard"
+            );
+            let next = unsafe {
+                Node::check_poison(top);
+                (*top).next
+            };
+            if spans_lines::<Node<T>>() {
+                prefetch_read(unsafe { (*top).data.as_ptr() });
+            }
 
+            yield_point("hp::pop::cas");
+            /* AcqRel is enough: the SeqCst fence in scan() orders the
+             * unlink before the hazard reads. On failure nothing is
+             * dereferenced before protecting the new top again */
             let cas = self.shared.top.compare_exchange_weak(
                 top,
                 next as *mut _,
-                Ordering::SeqCst,
-                Ordering::Acquire,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
             );
 
             match cas {
-                Ok(oldtop) => break oldtop,
-                Err(newertop) => top = newertop,
+                Ok(oldtop) => {
+                    /* Most likely the next pop's top */
+                    prefetch_read(next);
+                    break oldtop;
+                }
+                Err(newertop) => {
+                    self.cas_failed();
+                    top = newertop;
+                }
             }
         };
 
         /* Ordering is relaxed, because this thread now is responsible for the allocated memory */
-        self.shared.hazard_pointers[self.thread_number].store(ptr::null_mut(), Ordering::Relaxed);
-        self.shared.len.fetch_sub(1, Ordering::Relaxed);
+        self.hazard(0).store(ptr::null_mut(), Ordering::Relaxed);
+        self.shared.sub_len(&mut self.len_shard, 1);
 
         /* SAFETY: only one thread can succeed at CAS, so we are the only
          * ones reading oldtop.data */
         let data = unsafe { ptr::read((*oldtop).data.as_ptr()) };
+        self.retire_node(oldtop);
+
+        /* After the bookkeeping, so a panic only drops our copy */
+        self.shared.intercept.call(&data, Op::Pop);
+        return Some(data);
+    }
 
+    /// Like [`pop`](Self::pop), but also hands out a spare node, so the
+    /// caller can manage node lifetimes and push with
+    /// [`push_node`](Self::push_node) without allocating. The popped node
+    /// itself can't be handed out, other threads may still be reading it,
+    /// the spare one comes from this handle's cache of reclaimed nodes.
+    /// Only nodes allocated on their own qualify, e.g. those that went
+    /// through `push_node` before, nodes of the cache's chunks don't. A
+    /// node is only allocated if the cache has none of them, so a
+    /// `pop_node`/`push_node` loop stops allocating once its nodes are
+    /// reclaimed.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new();
+    /// s.push(1);
+    /// let (x, node) = s.pop_node().unwrap();
+    /// s.push_node(node, x + 1);
+    /// assert_eq!(s.pop(), Some(2));
+    /// ```
+    pub fn pop_node(&mut self) -> Option<(T, Box<Node<T>>)> {
+        let data = self.pop()?;
+        /* Only a boxed node can be handed out, chunk nodes stay here.
+         * Reclaimed nodes are pushed last, so the search is usually short */
+        /* SAFETY: the cache owns its nodes */
+        let boxed = self.cached_allocations.iter().rposition(|node| unsafe { (*node.0.as_ptr()).chunk.is_null() });
+        let node = match boxed {
+            Some(i) => {
+                let node = self.cached_allocations.swap_remove(i).into_raw();
+                /* SAFETY: a boxed node, see above */
+                unsafe { Box::from_raw(node) }
+            }
+            None => Node::empty(),
+        };
+        return Some((data, node));
+    }
+
+    /// Pushes `data` in `node` instead of a node from the cache, never
+    /// allocates. See [`pop_node`](Self::pop_node) and [`Node::empty`].
+    pub fn push_node(&mut self, mut node: Box<Node<T>>, data: T) {
+        self.shared.intercept.call(&data, Op::Push);
+        *node = Node {
+            next: ptr::null(),
+            data: MaybeUninit::new(data),
+            chunk: ptr::null_mut(),
+        };
+        let node = Box::into_raw(node);
+        self.splice_chain(node, node);
+        self.shared.add_len(&mut self.len_shard, 1);
+    }
+
+    /// Pushes `data` unless `pred` returns true for the current top element,
+    /// e.g. `push_unless(x, |top| *top == x)` skips consecutive duplicates.
+    /// If the top changes before the push lands, `pred` runs again on the
+    /// new top. Returns `data` back if it was rejected, an empty stack
+    /// always accepts. `T: Copy` for the same reason as in
+    /// [`peek_many`](Self::peek_many), `pred` gets a copy of the top.
+    #[must_use = "the item is handed back if it was rejected"]
+    pub fn push_unless(&mut self, data: T, mut pred: impl FnMut(&T) -> bool) -> Option<T>
+    where
+        T: Copy,
+    {
+        let node = self.get_node(data, ptr::null());
+        let mut top = self.shared.top.load(Ordering::Acquire);
+
+        loop {
+            /* Same protection dance as in pop() */
+            self.protect(0, top);
+            let newertop = self.shared.top.load(Ordering::SeqCst);
+            if newertop != top {
+                top = newertop;
+                continue;
+            }
+
+            if !top.is_null() {
+                assert_ordering!(
+                    self.hazard(0).load(Ordering::SeqCst) == top,
+                    "dereferencing a node that is not published as hazard"
+                );
+                /* SAFETY: `top` is protected, see pop() */
+                let current = unsafe {
+                    Node::check_poison(top);
+                    *(*top).data.as_ptr()
+                };
+                /* If `pred` panics, the node was never published */
+                let pending = PendingNode {
+                    node,
+                    hazard: self.hazard(0),
+                };
+                let rejected = pred(&current);
+                std::mem::forget(pending);
+                if rejected {
+                    self.hazard(0).store(ptr::null_mut(), Ordering::Relaxed);
+                    /* SAFETY: the node never left our hands */
+                    let data = unsafe { ptr::read((*node).data.as_ptr()) };
+                    unsafe { self.prepare_for_reuse(node) };
+                    return Some(data);
+                }
+            }
+
+            /* SAFETY: the new node is still private to us */
+            unsafe {
+                (*node).next = top;
+            }
+            let cas = self.shared.top.compare_exchange_weak(top, node, Ordering::SeqCst, Ordering::Acquire);
+            match cas {
+                Ok(_) => break,
+                Err(newertop) => {
+                    self.cas_failed();
+                    top = newertop;
+                }
+            }
+        }
+
+        self.hazard(0).store(ptr::null_mut(), Ordering::Relaxed);
+        /* Already visible, but `data` is a copy nobody else can touch */
+        self.shared.intercept.call(&data, Op::Push);
+        self.shared.add_len(&mut self.len_shard, 1);
+        self.shared.notify_pushed();
+        return None;
+    }
+
+    /// Coalescing push, e.g. for repeated "redraw" events: skips `data`
+    /// if it equals the current top. Returns whether it was pushed.
+    pub fn push_dedup(&mut self, data: T) -> bool
+    where
+        T: Copy + PartialEq,
+    {
+        self.push_unless(data, |top| *top == data).is_none()
+    }
+
+    /// Atomically replaces the top element with `data` and returns the old
+    /// one, on an empty stack `data` is just pushed. Unlike a pop followed
+    /// by a push, other handles never see the stack one element shorter.
+    pub fn swap_top(&mut self, data: T) -> Option<T> {
+        self.shared.intercept.call(&data, Op::Push);
+        let node = self.get_node(data, ptr::null());
+        let mut top = self.shared.top.load(Ordering::Acquire);
+
+        let oldtop = loop {
+            /* Same protection dance as in pop() */
+            self.protect(0, top);
+            let newertop = self.shared.top.load(Ordering::SeqCst);
+            if newertop != top {
+                top = newertop;
+                continue;
+            }
+
+            let next = if top.is_null() {
+                ptr::null()
+            } else {
+                assert_ordering!(
+                    self.hazard(0).load(Ordering::SeqCst) == top,
+                    "dereferencing a node that is not published as hazard"
+                );
+                /* SAFETY: `top` is protected, see pop() */
+                unsafe {
+                    Node::check_poison(top);
+                    (*top).next
+                }
+            };
+            /* SAFETY: the new node is still private to us */
+            unsafe {
+                (*node).next = next;
+            }
+
+            let cas = self.shared.top.compare_exchange_weak(top, node, Ordering::SeqCst, Ordering::Acquire);
+            match cas {
+                Ok(oldtop) => break oldtop,
+                Err(newertop) => {
+                    self.cas_failed();
+                    top = newertop;
+                }
+            }
+        };
+
+        self.hazard(0).store(ptr::null_mut(), Ordering::Relaxed);
+        if oldtop.is_null() {
+            self.shared.add_len(&mut self.len_shard, 1);
+            self.shared.notify_pushed();
+            return None;
+        }
+
+        /* SAFETY: only one thread can succeed at CAS, so we are the only
+         * ones reading oldtop.data */
+        let data = unsafe { ptr::read((*oldtop).data.as_ptr()) };
         self.retire_node(oldtop);
+        /* See pop() */
+        self.shared.intercept.call(&data, Op::Pop);
         return Some(data);
     }
 
+    /// Pops an element, or registers `cx`'s waker to be woken by a later push.
+    ///
+    /// Each handle owns one waker slot, so only the latest waker is kept.
+    /// If you stop polling before getting `Ready`, call
+    /// [`cancel_poll_pop`](Self::cancel_poll_pop), so a wakeup meant for
+    /// this handle is passed on to another waiting one.
+    pub fn poll_pop(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        if let Some(x) = self.pop() {
+            self.cancel_poll_pop();
+            return Poll::Ready(x);
+        }
+
+        self.shared.register_waker(self.slot.index(), cx.waker());
+        self.waker_registered = true;
+
+        /* A push could have happened before our registration was visible.
+         * pop() only loads `top` with Acquire, the fence orders that load
+         * after the registration, see notify_pushed() */
+        fence(Ordering::SeqCst);
+        if let Some(x) = self.pop() {
+            self.cancel_poll_pop();
+            return Poll::Ready(x);
+        }
+
+        return Poll::Pending;
+    }
+
+    /// Removes this handle's waker. If a push has already consumed it,
+    /// the wakeup is forwarded to another waiting handle.
+    pub fn cancel_poll_pop(&mut self) {
+        if !self.waker_registered {
+            return;
+        }
+
+        self.waker_registered = false;
+        if !self.shared.deregister_waker(self.slot.index()) {
+            self.shared.wake_one();
+        }
+    }
+
+    /// Future version of [`pop`](Self::pop). Dropping the future before it
+    /// completes takes nothing from the stack and releases its waker slot.
+    pub fn pop_async(&mut self) -> PopFuture<'_, T> {
+        PopFuture { handle: self }
+    }
+
+    /// Copies up to `n` elements starting from the top, without removing them.
+    ///
+    /// This is a best-effort snapshot: if the top changes during the walk,
+    /// only the elements read so far are returned. `T: Copy` is required,
+    /// because a concurrent pop may move the element out (and start mutating
+    /// or dropping it) while we are still reading the node.
+    pub fn peek_many(&mut self, n: usize) -> Vec<T>
+    where
+        T: Copy,
+    {
+        let mut v = Vec::new();
+        if n == 0 {
+            return v;
+        }
+
+        let mut anchor = self.shared.top.load(Ordering::Acquire);
+        loop {
+            /* SeqCst for the same reason as in pop() */
+            self.protect(0, anchor);
+            let newertop = self.shared.top.load(Ordering::SeqCst);
+            if newertop == anchor {
+                break;
+            }
+            anchor = newertop;
+        }
+
+        /* While `top` still equals the protected anchor, the anchor was never
+         * popped, so none of the nodes below it were popped either */
+        let mut node = anchor as *const Node<T>;
+        while !node.is_null() && v.len() < n {
+            assert_ordering!(
+                self.hazard(0).load(Ordering::SeqCst) == anchor,
+                "walking the stack without the anchor published as hazard"
+            );
+            /* SAFETY: `node` is protected and was reachable after protecting it */
+            let (data, next) = unsafe {
+                Node::check_poison(node);
+                (*(*node).data.as_ptr(), (*node).next)
+            };
+            v.push(data);
+
+            self.protect(1, next as *mut _);
+            if self.shared.top.load(Ordering::SeqCst) != anchor {
+                break;
+            }
+            node = next;
+        }
+
+        self.hazard(0).store(ptr::null_mut(), Ordering::Release);
+        self.hazard(1).store(ptr::null_mut(), Ordering::Release);
+        return v;
+    }
+
+    /// Runs `f` on the top element without removing it, `None` if the
+    /// stack is empty. The top node is protected by a hazard pointer while
+    /// `f` runs, so it can't be freed or reused under it.
+    ///
+    /// `T: Copy` for the same reason as [`peek_many`](Self::peek_many): the
+    /// element may be popped concurrently, and the node only keeps a stale
+    /// copy of it, which is only harmless to look at if `T` owns nothing.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new();
+    /// s.push(7);
+    /// assert_eq!(s.peek_with(|x| x * 2), Some(14));
+    /// assert_eq!(s.pop(), Some(7));
+    /// ```
+    pub fn peek_with<R>(&mut self, f: impl FnOnce(&T) -> R) -> Option<R>
+    where
+        T: Copy,
+    {
+        let mut top = self.shared.top.load(Ordering::Acquire);
+        loop {
+            if top.is_null() {
+                self.hazard(0).store(ptr::null_mut(), Ordering::Release);
+                return None;
+            }
+            /* SeqCst for the same reason as in pop() */
+            self.protect(0, top);
+            let newertop = self.shared.top.load(Ordering::SeqCst);
+            if newertop == top {
+                break;
+            }
+            top = newertop;
+        }
+
+        /* SAFETY: `top` is protected and was reachable after protecting it,
+         * and T: Copy, so even a stale copy is a valid T */
+        let r = unsafe {
+            Node::check_poison(top);
+            f(&*(*top).data.as_ptr())
+        };
+        self.hazard(0).store(ptr::null_mut(), Ordering::Release);
+        return Some(r);
+    }
+
+    /// Detaches everything currently on the stack into a shared, immutable
+    /// snapshot, the stack itself continues empty. Pushes racing with this
+    /// land either in the snapshot or on the fresh stack, never both.
+    pub fn freeze(&mut self) -> FrozenStack<T> {
+        return FrozenStack::from(self.take_all());
+    }
+
+    /// Takes every element off the stack with a single exchange of the top,
+    /// instead of popping them one by one. They are returned top first.
+    /// Pushes racing with this either land in the result or stay on the
+    /// stack, never both.
+    pub fn take_all(&mut self) -> Vec<T> {
+        let mut node = self.shared.top.swap(ptr::null_mut(), Ordering::AcqRel) as *const Node<T>;
+
+        let mut v = Vec::new();
+        while !node.is_null() {
+            /* SAFETY: the chain is detached, see reverse_stack() */
+            let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+            self.retire_node(node);
+            v.push(data);
+            node = next;
+        }
+
+        self.shared.sub_len(&mut self.len_shard, v.len());
+        /* See pop(), a panic here drops what was taken with `v` */
+        for data in &v {
+            self.shared.intercept.call(data, Op::Pop);
+        }
+        return v;
+    }
+
+    /// Pops up to `n` elements, top first. The chain is walked under one
+    /// pair of hazards and cut off with a single CAS, instead of paying the
+    /// hazard publication and the CAS once per element.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new();
+    /// for i in 0..5 {
+    ///     s.push(i);
+    /// }
+    /// assert_eq!(s.pop_batch(3), vec![4, 3, 2]);
+    /// assert_eq!(s.pop_batch(3), vec![1, 0]);
+    /// ```
+    pub fn pop_batch(&mut self, n: usize) -> Vec<T> {
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let (mut node, count) = self.detach_chain(n);
+        let mut v = Vec::with_capacity(count);
+        for _ in 0..count {
+            /* SAFETY: the chain is detached, see reverse_stack() */
+            let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+            self.retire_node(node);
+            v.push(data);
+            node = next;
+        }
+
+        self.shared.sub_len(&mut self.len_shard, count);
+        /* Same as in take_all() */
+        for data in &v {
+            self.shared.intercept.call(data, Op::Pop);
+        }
+        return v;
+    }
+
+    /// Moves up to `n` elements from the top of `other` onto this stack,
+    /// keeping their order, returns how many were moved. The elements are
+    /// cut off `other` with one CAS and spliced here with another one, but
+    /// they are not moved as a chain: each one is copied into a fresh
+    /// (usually cached) node of this handle.
+    ///
+    /// The nodes themselves can't change stacks: threads of `other` may
+    /// still hold them as hazard, and only `other`'s scan knows about that.
+    /// So the old nodes are retired through `other`.
+    pub fn steal_from(&mut self, other: &mut LockFreeStacc<T>, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+
+        let (chain, count) = other.detach_chain(n);
+        if chain.is_null() {
+            return 0;
+        }
+
+        let mut node = chain;
+        let mut head: *mut Node<T> = ptr::null_mut();
+        let mut tail: *mut Node<T> = ptr::null_mut();
+        for _ in 0..count {
+            /* SAFETY: the chain is detached, see reverse_stack() */
+            let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+            other.retire_node(node);
+
+            let fresh = self.get_node(data, ptr::null());
+            if tail.is_null() {
+                head = fresh;
+            } else {
+                /* SAFETY: the new chain is still private to us */
+                unsafe {
+                    (*tail).next = fresh;
+                }
+            }
+            tail = fresh;
+            node = next;
+        }
+
+        other.shared.sub_len(&mut other.len_shard, count);
+        self.splice_chain(head, tail);
+        self.shared.add_len(&mut self.len_shard, count);
+        return count;
+    }
+
+    /// Cuts up to `n` nodes off the top with a single CAS, returns the
+    /// first one and how many there are
+    fn detach_chain(&mut self, n: usize) -> (*const Node<T>, usize) {
+        if n == usize::MAX {
+            let chain = self.shared.top.swap(ptr::null_mut(), Ordering::AcqRel) as *const Node<T>;
+            let mut count = 0;
+            let mut node = chain;
+            while !node.is_null() {
+                /* SAFETY: the chain is detached, `next` never changes */
+                node = unsafe { (*node).next };
+                count += 1;
+            }
+            return (chain, count);
+        }
+
+        let mut anchor = self.shared.top.load(Ordering::Acquire);
+        'retry: loop {
+            /* Same protection dance as in pop() */
+            self.protect(0, anchor);
+            let newertop = self.shared.top.load(Ordering::SeqCst);
+            if newertop != anchor {
+                anchor = newertop;
+                continue;
+            }
+            if anchor.is_null() {
+                self.hazard(1).store(ptr::null_mut(), Ordering::Release);
+                return (ptr::null(), 0);
+            }
+
+            /* Walk like peek_many(): while top is still the anchor,
+             * nothing below it was popped */
+            let mut last = anchor as *const Node<T>;
+            let mut count = 1;
+            let next = loop {
+                assert_ordering!(
+                    self.hazard(0).load(Ordering::SeqCst) == anchor,
+                    "walking the stack without the anchor published as hazard"
+                );
+                /* SAFETY: `last` is protected and was reachable after protecting it */
+                let next = unsafe {
+                    Node::check_poison(last);
+                    (*last).next
+                };
+                if count == n || next.is_null() {
+                    break next;
+                }
+
+                self.protect(1, next as *mut _);
+                let newertop = self.shared.top.load(Ordering::SeqCst);
+                if newertop != anchor {
+                    anchor = newertop;
+                    continue 'retry;
+                }
+                last = next;
+                count += 1;
+            };
+
+            let cas = self.shared.top.compare_exchange(anchor, next as *mut _, Ordering::AcqRel, Ordering::Relaxed);
+            match cas {
+                Ok(_) => {
+                    self.hazard(0).store(ptr::null_mut(), Ordering::Release);
+                    self.hazard(1).store(ptr::null_mut(), Ordering::Release);
+                    return (anchor, count);
+                }
+                Err(newertop) => {
+                    self.cas_failed();
+                    anchor = newertop;
+                }
+            }
+        }
+    }
+
+    /// Releases excess capacity of this handle's bookkeeping vectors,
+    /// which otherwise stay at their peak size after a burst
+    pub fn shrink_to_fit(&mut self) {
+        self.retired_pointers.shrink_to_fit();
+        self.cached_allocations.shrink_to_fit();
+    }
+
+    /// Makes sure at least `n` nodes are cached, so the next `n` pushes of
+    /// this handle don't allocate, e.g. before a latency-sensitive section.
+    /// The cache limit only applies to reclaimed nodes, not to these.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new();
+    /// s.reserve_nodes(16);
+    /// for i in 0..16 {
+    ///     s.push(i);
+    /// }
+    /// ```
+    pub fn reserve_nodes(&mut self, n: usize) {
+        let missing = n.saturating_sub(self.cached_allocations.len());
+        self.cached_allocations.reserve(missing);
+        for _ in 0..missing {
+            /* SAFETY: a fresh box, nothing in it */
+            self.cached_allocations.push(unsafe { CachedNode::new(Box::into_raw(Node::empty())) });
+        }
+    }
+
+    /// Frees every cached node, e.g. after a burst of pops on a handle that
+    /// won't push again soon. Returns how many were freed.
+    pub fn shrink_caches(&mut self) -> usize {
+        let n = self.cached_allocations.len();
+        self.cached_allocations = Vec::new();
+        return n;
+    }
+
+    /// Scans for reclaimable nodes right away instead of waiting for the
+    /// retire threshold, e.g. before going idle. Returns how many retired
+    /// nodes were reclaimed, ours and ones other handles left to the domain.
+    /// Reclaimed nodes of this handle go to its cache, see
+    /// [`shrink_caches`](Self::shrink_caches).
+    pub fn force_reclaim(&mut self) -> usize {
+        self.scan()
+    }
+
+    /// Housekeeping for idle time: reclaims retired nodes (including ones
+    /// spilled by other handles), then frees cached nodes and shrinks the
+    /// bookkeeping vectors. `out_of_time` is checked between steps, same
+    /// as in the EBR stack's `maintain`, so pass e.g.
+    /// `|| Instant::now() >= deadline`.
+    /// Returns true if everything got done in time.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::<u32>::new();
+    /// let deadline = Instant::now() + Duration::from_millis(1);
+    /// s.maintain(|| Instant::now() >= deadline);
+    /// ```
+    pub fn maintain(&mut self, mut out_of_time: impl FnMut() -> bool) -> bool {
+        if !self.retired_pointers.is_empty() || self.shared.domain.pending() != 0 {
+            /* The scan can't be cut short, so don't start it too late */
+            if out_of_time() {
+                return false;
+            }
+            self.scan();
+        }
+
+        while !self.cached_allocations.is_empty() {
+            if out_of_time() {
+                return false;
+            }
+            let keep = self.cached_allocations.len().saturating_sub(MAINTAIN_CHUNK);
+            self.cached_allocations.truncate(keep);
+        }
+
+        self.shrink_to_fit();
+        return true;
+    }
+
+    /// Same as [`approx_len`](Self::approx_len)
+    #[cfg(not(feature = "no-stats"))]
     pub fn len(&self) -> usize {
-        self.shared.len.load(Ordering::Relaxed)
+        self.approx_len()
+    }
+
+    /// Process-unique id of the stack, the same for all its handles
+    pub fn id(&self) -> u64 {
+        self.shared.id
+    }
+
+    /// Reclamation and contention counters of this handle, e.g. for tuning
+    /// [`with_retire_threshold`](Self::with_retire_threshold)
+    pub fn stats(&self) -> HandleStats {
+        HandleStats {
+            pending: self.retired_pointers.len(),
+            cached: self.cached_allocations.len(),
+            ..self.counters
+        }
+    }
+
+    /// Counters of the domain this handle's stack lives in
+    pub fn domain_stats(&self) -> DomainStats {
+        self.shared.domain.stats()
+    }
+
+    /// The stack this handle belongs to, to create more handles from
+    pub fn stack(&self) -> HpStack<T> {
+        HpStack {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Cheap, racy length: the sum of relaxed per-handle counters, may lag
+    /// behind concurrent pushes and pops. Not there with the `no-stats` feature.
+    #[cfg(not(feature = "no-stats"))]
+    pub fn approx_len(&self) -> usize {
+        self.shared.len.get()
+    }
+
+    /// Whether the stack looked empty at the time of the call
+    pub fn is_empty(&self) -> bool {
+        self.shared.top.load(Ordering::Acquire).is_null()
+    }
+
+    /// Moving average of the length, sampled every 8th push or pop of a
+    /// handle and covering roughly its last 128, smoother than [`approx_len`](Self::approx_len) for deciding
+    /// e.g. how many workers to run. Not there with the `no-stats` feature.
+    #[cfg(not(feature = "no-stats"))]
+    pub fn load_estimate(&self) -> f64 {
+        self.shared.load.get()
+    }
+
+    /// Exact length, computed by walking the list. Only possible when this
+    /// is the only handle to the stack, returns `None` otherwise.
+    pub fn len_exact(&mut self) -> Option<usize> {
+        let shared = Arc::get_mut(&mut self.shared)?;
+        return Some(shared.count_nodes());
+    }
+
+    /// What happens to the elements still on the stack when the last
+    /// handle goes away, or on [`dispose`](Self::dispose)
+    pub fn set_drop_policy(&self, policy: DropPolicy<T>) {
+        self.shared.drop_policy.set(policy);
+    }
+
+    /// Debugging aid: calls `f` with every element pushed to or popped
+    /// from the stack, through any handle, `None` removes it. Pushes are
+    /// reported before the element becomes visible, pops after it was
+    /// taken out. Only with the `intercept` feature.
+    /// [`steal_from`](Self::steal_from) and the fairness window move
+    /// elements without reporting them.
+    #[cfg(feature = "intercept")]
+    pub fn set_interceptor(&self, f: Option<fn(&T, Op)>) {
+        self.shared.intercept.set(f);
+    }
+
+    /// Incremental teardown: frees at most `budget` elements from the top,
+    /// so dropping a huge stack can be spread over time instead of stalling
+    /// in `drop`. Returns the number of freed elements, less than `budget`
+    /// means the stack is now empty. Only possible when this is the only
+    /// handle to the stack, returns `None` otherwise.
+    pub fn dispose(&mut self, budget: usize) -> Option<usize> {
+        let shared = Arc::get_mut(&mut self.shared)?;
+        let freed = shared.free_nodes(budget);
+        shared.sub_len(&mut LenShard::new(0), freed);
+        return Some(freed);
+    }
+
+    /// Pops everything through an iterator that needs neither atomics nor
+    /// hazards. Only possible when this is the only handle to the stack,
+    /// returns `None` otherwise. Elements the iterator doesn't get to are
+    /// dropped with it.
+    ///
+    /// ```
+    /// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new();
+    /// s.push(1);
+    /// s.push(2);
+    /// assert_eq!(s.try_drain().unwrap().collect::<Vec<_>>(), [2, 1]);
+    ///
+    /// let _other = s.clone();
+    /// assert!(s.try_drain().is_none());
+    /// ```
+    pub fn try_drain(&mut self) -> Option<Drain<'_, T>> {
+        let shared = Arc::get_mut(&mut self.shared)?;
+        return Some(Drain {
+            shared,
+            len_shard: &mut self.len_shard,
+            taken: 0,
+        });
+    }
+}
+
+/// Iterator returned by [`LockFreeStacc::try_drain`], top first
+pub struct Drain<'a, T> {
+    shared: &'a mut Shared<T>,
+    len_shard: &'a mut LenShard,
+    /* Settled with the len counter at the end */
+    taken: usize,
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let top = self.shared.top.get_mut();
+        if top.is_null() {
+            return None;
+        }
+        let node = *top;
+        /* SAFETY: we have exclusive access, see count_nodes() */
+        let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+        *top = next as *mut _;
+        /* SAFETY: no other handle, so nobody protects the node either */
+        unsafe { free_node(node) };
+        self.taken += 1;
+
+        self.shared.intercept.call(&data, Op::Pop);
+        return Some(data);
+    }
+}
+
+impl<T> Drop for Drain<'_, T> {
+    fn drop(&mut self) {
+        /* A panicking drop leaves the rest on the stack, and the (only
+         * approximate) len counter too high */
+        self.for_each(drop);
+        self.shared.sub_len(self.len_shard, self.taken);
+    }
+}
+
+/// Pushes every element in the iterator's order, so the last one ends up on
+/// top. They are linked up in a [`Chain`] first and published with a
+/// single CAS, see [`push_chain`](LockFreeStacc::push_chain).
+///
+/// ```
+/// let mut s: stacc::stacc_lockfree_hp::LockFreeStacc<_> = (0..3).collect();
+/// s.extend([3, 4]);
+/// assert_eq!(s.pop(), Some(4));
+/// assert_eq!(s.pop(), Some(3));
+/// ```
+impl<T> Extend<T> for LockFreeStacc<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut chain = Chain::new();
+        for data in iter {
+            chain.push(data);
+        }
+        self.push_chain(chain);
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for LockFreeStacc<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut s = Self::new();
+        s.extend(iter);
+        return s;
+    }
+}
+
+/// Pops everything, top first. Without other handles it doesn't touch any
+/// atomics or hazards, like [`try_drain`](LockFreeStacc::try_drain),
+/// otherwise the whole stack is taken with one exchange, like
+/// [`take_all`](LockFreeStacc::take_all).
+///
+/// ```
+/// let mut s = stacc::stacc_lockfree_hp::LockFreeStacc::new();
+/// s.push(1);
+/// s.push(2);
+/// assert_eq!(s.into_iter().collect::<Vec<_>>(), [2, 1]);
+/// ```
+impl<T> IntoIterator for LockFreeStacc<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> IntoIter<T> {
+        let (chain, count, exclusive) = match Arc::get_mut(&mut self.shared) {
+            Some(shared) => {
+                let count = shared.count_nodes();
+                let chain = std::mem::replace(shared.top.get_mut(), ptr::null_mut());
+                (chain as *const Node<T>, count, true)
+            }
+            None => {
+                let (chain, count) = self.detach_chain(usize::MAX);
+                (chain, count, false)
+            }
+        };
+        self.shared.sub_len(&mut self.len_shard, count);
+        return IntoIter {
+            handle: self,
+            chain,
+            exclusive,
+        };
+    }
+}
+
+/// Iterator returned by [`LockFreeStacc::into_iter`]
+pub struct IntoIter<T> {
+    handle: LockFreeStacc<T>,
+    /* Detached, but other handles may still protect its nodes, unless
+     * `exclusive` */
+    chain: *const Node<T>,
+    exclusive: bool,
+}
+
+/* SAFETY: the chain belongs to us, like the elements of a handle */
+unsafe impl<T: Send> Send for IntoIter<T> {}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node = self.chain;
+        if node.is_null() {
+            return None;
+        }
+        /* SAFETY: the chain is detached, see reverse_stack() */
+        let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+        self.chain = next;
+        if self.exclusive {
+            /* SAFETY: no other handle, so nobody protects the node */
+            unsafe { free_node(node as *mut Node<T>) };
+        } else {
+            self.handle.retire_node(node);
+        }
+
+        self.handle.shared.intercept.call(&data, Op::Pop);
+        return Some(data);
+    }
+}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        /* A panicking drop leaks the rest of the chain */
+        self.for_each(drop);
     }
 }
 
 impl<T> Drop for LockFreeStacc<T> {
     fn drop(&mut self) {
-        self.shared.hazard_pointers[self.thread_number].store(ptr::null_mut(), Ordering::Release);
+        self.cancel_poll_pop();
+        self.slot.clear();
+        /* Also reports the CAS retries */
         self.scan();
-        let mut lock = self.shared.boxes_that_are_still_hazard.lock().unwrap();
-        lock.append(&mut self.retired_pointers);
+        self.spill();
+        self.slot.release();
     }
 }
 
+impl<T> std::fmt::Debug for LockFreeStacc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut d = f.debug_struct("LockFreeStacc");
+        d.field("id", &self.id())
+            .field("domain", &self.shared.domain.id())
+            .field("thread_number", &self.slot.index());
+        #[cfg(not(feature = "no-stats"))]
+        d.field("len", &self.approx_len());
+        d.finish_non_exhaustive()
+    }
+}
+
+/* Reclamation only ever try_locks the domain's list, see try_spill() */
+impl<T> crate::progress::LockFreePush for LockFreeStacc<T> {}
+impl<T> crate::progress::LockFreePop for LockFreeStacc<T> {}
+
 impl<T> Clone for LockFreeStacc<T> {
     fn clone(&self) -> Self {
-        let shared = Arc::clone(&self.shared);
-        let thread_number = shared.counter.fetch_add(1, Ordering::AcqRel);
+        let mut handle = Self::from_shared(Arc::clone(&self.shared));
+        handle.retire_threshold = self.retire_threshold;
+        handle.cache_limit = self.cache_limit;
+        return handle;
+    }
+}
+
+/// Per-thread handle of an [`HpStack`], all the operations live here
+pub type HpHandle<T> = LockFreeStacc<T>;
+
+/// The shared part of a hazard-pointer protected stack, without a hazard
+/// slot of its own. Keep it wherever the stack is owned and hand every
+/// thread its own [`handle`](Self::handle):
+///
+/// ```
+/// let stack = stacc::stacc_lockfree_hp::HpStack::new();
+/// let mut producer = stack.handle();
+/// let mut consumer = stack.handle();
+/// producer.push(1);
+/// assert_eq!(consumer.pop(), Some(1));
+/// ```
+///
+/// The elements go away together with the last of the stack and its handles.
+pub struct HpStack<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/* SAFETY: the stack itself only creates handles, and those move elements
+ * across threads, hence T: Send for both */
+unsafe impl<T: Send> Send for HpStack<T> {}
+unsafe impl<T: Send> Sync for HpStack<T> {}
+
+impl<T> HpStack<T> {
+    pub fn new() -> Self {
+        Self::with_domain(&Arc::new(Domain::new()))
+    }
+
+    /// New stack whose handles take their hazard slots from `domain`
+    pub fn with_domain(domain: &Arc<Domain>) -> Self {
         Self {
-            shared,
-            thread_number,
-            retired_pointers: Vec::new(),
-            cached_allocations: Vec::new(),
+            shared: Arc::new(Shared::new(Arc::clone(domain))),
         }
     }
+
+    /// Registers a new handle, taking one hazard slot of the domain until
+    /// it is dropped
+    pub fn handle(&self) -> HpHandle<T> {
+        LockFreeStacc::from_shared(Arc::clone(&self.shared))
+    }
+
+    /// Same as [`LockFreeStacc::id`]
+    pub fn id(&self) -> u64 {
+        self.shared.id
+    }
+
+    /// Same as [`LockFreeStacc::is_empty`]
+    pub fn is_empty(&self) -> bool {
+        self.shared.top.load(Ordering::Acquire).is_null()
+    }
+
+    /// Same as [`LockFreeStacc::approx_len`]
+    #[cfg(not(feature = "no-stats"))]
+    pub fn approx_len(&self) -> usize {
+        self.shared.len.get()
+    }
+
+    /// Same as [`LockFreeStacc::load_estimate`]
+    #[cfg(not(feature = "no-stats"))]
+    pub fn load_estimate(&self) -> f64 {
+        self.shared.load.get()
+    }
+}
+
+impl<T> Clone for HpStack<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for HpStack<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut d = f.debug_struct("HpStack");
+        d.field("id", &self.id()).field("domain", &self.shared.domain.id());
+        #[cfg(not(feature = "no-stats"))]
+        d.field("len", &self.approx_len());
+        d.finish_non_exhaustive()
+    }
+}
+
+/* Frees a node of push_unless() and clears the hazard on the top, when
+ * the predicate panics */
+struct PendingNode<'a, T> {
+    node: *mut Node<T>,
+    hazard: &'a AtomicPtr<Node<T>>,
+}
+
+impl<T> Drop for PendingNode<'_, T> {
+    fn drop(&mut self) {
+        self.hazard.store(ptr::null_mut(), Ordering::Release);
+        /* SAFETY: the node never left our hands, and holds an element */
+        unsafe {
+            ptr::drop_in_place((*self.node).data.as_mut_ptr());
+            free_node(self.node);
+        }
+    }
+}
+
+/// Elements linked up locally, without touching any stack, to be published
+/// all at once with [`LockFreeStacc::push_chain`]
+pub struct Chain<T> {
+    /* Last pushed element, becomes the top */
+    head: *mut Node<T>,
+    tail: *mut Node<T>,
+    len: usize,
+}
+
+/* SAFETY: the chain owns its nodes, nobody else can see them */
+unsafe impl<T: Send> Send for Chain<T> {}
+
+impl<T> Chain<T> {
+    pub fn new() -> Self {
+        Self {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, data: T) {
+        let node = Box::into_raw(Box::new(Node {
+            data: MaybeUninit::new(data),
+            next: self.head,
+            chunk: ptr::null_mut(),
+        }));
+        if self.head.is_null() {
+            self.tail = node;
+        }
+        self.head = node;
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Drop for Chain<T> {
+    fn drop(&mut self) {
+        let mut node = self.head;
+        while !node.is_null() {
+            /* SAFETY: every node came from Box::into_raw with initialized data */
+            let mut boxed = unsafe { Box::from_raw(node) };
+            unsafe { ptr::drop_in_place(boxed.data.as_mut_ptr()) };
+            node = boxed.next as *mut _;
+        }
+    }
+}
+
+/// Future returned by [`LockFreeStacc::pop_async`]
+pub struct PopFuture<'a, T> {
+    handle: &'a mut LockFreeStacc<T>,
+}
+
+impl<T> Future for PopFuture<'_, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.get_mut().handle.poll_pop(cx)
+    }
+}
+
+impl<T> Drop for PopFuture<'_, T> {
+    fn drop(&mut self) {
+        self.handle.cancel_poll_pop();
+    }
 }
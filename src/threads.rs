@@ -0,0 +1,48 @@
+/* Fan-out helper for tests and quick benchmarks.
+ *
+ * Every handle is cloned on the calling thread before anything is spawned,
+ * so each thread gets its own registration (hazard slots, EBR slot) and
+ * the slot numbering doesn't depend on scheduling. */
+
+use std::thread;
+
+/// Runs `f(i, handle)` on `n` scoped threads, each with its own clone of
+/// `handle`, waits for all of them and returns their results in order.
+/// A panic in any thread is propagated.
+///
+/// ```
+/// use stacc::stacc_lockfree_hp::LockFreeStacc;
+///
+/// let mut s = LockFreeStacc::new();
+/// stacc::threads::with_threads(4, &s, |i, mut s| {
+///     for j in 0..100 {
+///         s.push(i * 100 + j);
+///     }
+/// });
+/// assert_eq!(s.len_exact(), Some(400));
+/// ```
+pub fn with_threads<H, R, F>(n: usize, handle: &H, f: F) -> Vec<R>
+where
+    H: Clone + Send,
+    R: Send,
+    F: Fn(usize, H) -> R + Sync,
+{
+    let handles: Vec<H> = (0..n).map(|_| handle.clone()).collect();
+    let f = &f;
+
+    thread::scope(|scope| {
+        let threads: Vec<_> = handles
+            .into_iter()
+            .enumerate()
+            .map(|(i, h)| scope.spawn(move || f(i, h)))
+            .collect();
+
+        threads
+            .into_iter()
+            .map(|t| match t.join() {
+                Ok(r) => r,
+                Err(e) => std::panic::resume_unwind(e),
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,131 @@
+/* Test support: a payload that counts how many times it was constructed
+ * and dropped, to check that a structure (or a wrapper around one) neither
+ * leaks nor double-drops its elements.
+ *
+ *   let tracker = DropTracker::new();
+ *   stack.push(tracker.wrap(1));
+ *   drop(stack);
+ *   tracker.assert_balanced();
+ */
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::AtomicUsize;
+use alloc::sync::Arc;
+
+use crate::ordering::Ordering;
+
+/// Counts for every [`DropCounter`] created through it
+pub struct DropTracker {
+    created: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl DropTracker {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            created: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        })
+    }
+
+    /// Wraps `value`, counting one construction
+    pub fn wrap<T>(self: &Arc<Self>, value: T) -> DropCounter<T> {
+        self.created.fetch_add(1, Ordering::Relaxed);
+        DropCounter {
+            value,
+            tracker: Arc::clone(self),
+        }
+    }
+
+    pub fn created(&self) -> usize {
+        self.created.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Values created but not dropped yet
+    pub fn alive(&self) -> usize {
+        self.created() - self.dropped()
+    }
+
+    /// Panics unless exactly `n` values are alive
+    #[track_caller]
+    pub fn assert_alive(&self, n: usize) {
+        let (created, dropped) = (self.created(), self.dropped());
+        assert!(
+            created - dropped == n,
+            "expected {} alive values, but {} were created and {} dropped",
+            n,
+            created,
+            dropped
+        );
+    }
+
+    /// Panics unless every value was dropped exactly once
+    #[track_caller]
+    pub fn assert_balanced(&self) {
+        self.assert_alive(0);
+    }
+}
+
+/// A value whose constructions (including clones) and drops are counted
+/// by a [`DropTracker`]
+pub struct DropCounter<T> {
+    value: T,
+    tracker: Arc<DropTracker>,
+}
+
+impl<T> DropCounter<T> {
+    /// Takes the value out, counting it as dropped
+    pub fn into_inner(self) -> T {
+        let this = core::mem::ManuallyDrop::new(self);
+        this.tracker.dropped.fetch_add(1, Ordering::Relaxed);
+        /* SAFETY: `this` is never used or dropped again */
+        unsafe {
+            drop(core::ptr::read(&this.tracker));
+            return core::ptr::read(&this.value);
+        }
+    }
+}
+
+impl<T> Drop for DropCounter<T> {
+    fn drop(&mut self) {
+        let old = self.tracker.dropped.fetch_add(1, Ordering::Relaxed);
+        debug_assert!(old < self.tracker.created(), "dropped more values than were created");
+    }
+}
+
+impl<T: Clone> Clone for DropCounter<T> {
+    fn clone(&self) -> Self {
+        self.tracker.wrap(self.value.clone())
+    }
+}
+
+impl<T> Deref for DropCounter<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for DropCounter<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for DropCounter<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for DropCounter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
@@ -0,0 +1,103 @@
+/* What happens to the items still inside a structure when it is torn down.
+ *
+ * Every structure drops them by default. Payloads that represent
+ * acknowledged work can be handed to a callback instead, or leaked on
+ * purpose with a warning through the leak hook (we are no_std here, so
+ * printing is up to whoever installs the hook).
+ *
+ * The policy is set through a shared reference, but only read at teardown,
+ * when the structure is already exclusively owned. */
+
+use core::ptr;
+use core::sync::atomic::AtomicPtr;
+use alloc::boxed::Box;
+
+use crate::ordering::Ordering;
+
+/// Per-structure teardown behaviour for unconsumed items
+pub enum DropPolicy<T> {
+    /// Drop them, the default
+    DropInPlace,
+    /// Leak them, and report how many through the hook set with
+    /// [`set_leak_hook`]
+    LeakAndWarn,
+    /// Hand every item to the callback
+    Callback(Box<dyn FnMut(T) + Send + Sync>),
+}
+
+static LEAK_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Installs the function [`DropPolicy::LeakAndWarn`] reports to, it gets
+/// the name of the structure and the number of leaked items
+///
+/// ```
+/// stacc_core::drop_policy::set_leak_hook(|owner, n| eprintln!("{} leaked {} items", owner, n));
+/// ```
+pub fn set_leak_hook(hook: fn(&'static str, usize)) {
+    LEAK_HOOK.store(hook as *mut (), Ordering::Release);
+}
+
+/// Holds the policy of one structure, empty means [`DropPolicy::DropInPlace`]
+#[doc(hidden)]
+pub struct PolicyCell<T> {
+    policy: AtomicPtr<DropPolicy<T>>,
+}
+
+impl<T> PolicyCell<T> {
+    pub const fn new() -> Self {
+        Self {
+            policy: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    pub fn set(&self, policy: DropPolicy<T>) {
+        let new = Box::into_raw(Box::new(policy));
+        let old = self.policy.swap(new, Ordering::AcqRel);
+        if !old.is_null() {
+            /* SAFETY: came from Box::into_raw, and the policy is only ever
+             * dereferenced with exclusive access */
+            drop(unsafe { Box::from_raw(old) });
+        }
+    }
+
+    /// Disposes of one unconsumed item, returns true if it was leaked
+    pub fn dispose(&mut self, x: T) -> bool {
+        let policy = *self.policy.get_mut();
+        if policy.is_null() {
+            drop(x);
+            return false;
+        }
+
+        /* SAFETY: see set(), we have exclusive access */
+        match unsafe { &mut *policy } {
+            DropPolicy::DropInPlace => drop(x),
+            DropPolicy::LeakAndWarn => {
+                core::mem::forget(x);
+                return true;
+            }
+            DropPolicy::Callback(f) => f(x),
+        }
+        return false;
+    }
+
+    /// Reports leaked items to the leak hook, if any
+    pub fn report_leaks(&self, owner: &'static str, leaked: usize) {
+        let hook = LEAK_HOOK.load(Ordering::Acquire);
+        if leaked == 0 || hook.is_null() {
+            return;
+        }
+        /* SAFETY: only set_leak_hook() stores there, from a fn pointer */
+        let hook: fn(&'static str, usize) = unsafe { core::mem::transmute(hook) };
+        hook(owner, leaked);
+    }
+}
+
+impl<T> Drop for PolicyCell<T> {
+    fn drop(&mut self) {
+        let policy = *self.policy.get_mut();
+        if !policy.is_null() {
+            /* SAFETY: see set() */
+            drop(unsafe { Box::from_raw(policy) });
+        }
+    }
+}
@@ -0,0 +1,64 @@
+/* Immutable snapshot of a lock-free stack, produced by `freeze()`.
+ *
+ * The elements are moved out of the nodes into one allocation: the nodes
+ * themselves may still be protected by other threads (hazard pointers or
+ * epochs), so they have to go through regular reclamation anyway. */
+
+use core::fmt;
+use core::ops::Deref;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Elements detached from a stack, top first. Cheap to clone and share,
+/// derefs to a slice for iteration and indexing.
+///
+/// ```
+/// let mut s = stacc_core::stacc_lockfree_ebr::Local::new();
+/// s.push(1);
+/// s.push(2);
+/// let frozen = s.freeze();
+/// assert_eq!(frozen[0], 2);
+/// assert_eq!(frozen.iter().sum::<i32>(), 3);
+/// assert_eq!(s.pop(), None);
+/// ```
+pub struct FrozenStack<T> {
+    items: Arc<[T]>,
+}
+
+impl<T> Clone for FrozenStack<T> {
+    fn clone(&self) -> Self {
+        Self {
+            items: Arc::clone(&self.items),
+        }
+    }
+}
+
+impl<T> Deref for FrozenStack<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T> From<Vec<T>> for FrozenStack<T> {
+    /// `v[0]` is the top
+    fn from(v: Vec<T>) -> Self {
+        Self { items: v.into() }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a FrozenStack<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FrozenStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.items.iter()).finish()
+    }
+}
@@ -0,0 +1,112 @@
+/* Treiber stack of small integers, the classic allocator free list.
+ *
+ * "Nodes" are just the slots of a fixed slab: `next[i]` is the index below
+ * `i`. Nothing is ever allocated or freed after construction, so there is
+ * nothing to reclaim, and ABA is prevented by a tag packed next to the top
+ * index in a single 64-bit word, bumped by every successful pop.
+ *
+ *   head: | tag: 32 | top index: 32 |
+ *
+ * Reading `next[top]` may race with another thread that popped and pushed
+ * the same index meanwhile, but it is an atomic, and the tag makes the
+ * following CAS fail, so the stale value is never used. */
+
+use core::sync::atomic::{AtomicU32, AtomicU64};
+use alloc::boxed::Box;
+
+use crate::ordering::Ordering;
+
+const NIL: u32 = u32::MAX;
+
+fn pack(tag: u32, index: u32) -> u64 {
+    (tag as u64) << 32 | index as u64
+}
+
+fn unpack(head: u64) -> (u32, u32) {
+    ((head >> 32) as u32, head as u32)
+}
+
+/// Lock-free stack of the indices `0..capacity`, e.g. free slots of a pool.
+/// Each index should be in the stack at most once, pushing an index that
+/// is already there breaks the list (but is not undefined behaviour).
+///
+/// ```
+/// use stacc_core::index_stack::IndexStack;
+///
+/// let free = IndexStack::full(4);
+/// let slot = free.pop().unwrap();
+/// assert_eq!(slot, 0);
+/// free.push(slot);
+/// ```
+pub struct IndexStack {
+    head: AtomicU64,
+    next: Box<[AtomicU32]>,
+}
+
+impl IndexStack {
+    /// Empty stack for indices below `capacity`
+    pub fn new(capacity: u32) -> Self {
+        assert!(capacity != NIL, "capacity must be below u32::MAX");
+        Self {
+            head: AtomicU64::new(pack(0, NIL)),
+            next: (0..capacity).map(|_| AtomicU32::new(NIL)).collect(),
+        }
+    }
+
+    /// Stack holding every index below `capacity`, the lowest on top
+    pub fn full(capacity: u32) -> Self {
+        let stack = Self::new(capacity);
+        for i in 0..capacity {
+            let next = if i + 1 < capacity { i + 1 } else { NIL };
+            stack.next[i as usize].store(next, Ordering::Relaxed);
+        }
+        let top = if capacity > 0 { 0 } else { NIL };
+        stack.head.store(pack(0, top), Ordering::Relaxed);
+        return stack;
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.next.len() as u32
+    }
+
+    /// Panics if `index` is not below the capacity
+    pub fn push(&self, index: u32) {
+        let slot = &self.next[index as usize];
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let (tag, top) = unpack(head);
+            slot.store(top, Ordering::Relaxed);
+            /* Release publishes the store to `next` to the popper */
+            let cas = self.head.compare_exchange_weak(head, pack(tag, index), Ordering::Release, Ordering::Relaxed);
+            match cas {
+                Ok(_) => return,
+                Err(newhead) => head = newhead,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<u32> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let (tag, top) = unpack(head);
+            if top == NIL {
+                return None;
+            }
+            let next = self.next[top as usize].load(Ordering::Relaxed);
+            let new = pack(tag.wrapping_add(1), next);
+            let cas = self.head.compare_exchange_weak(head, new, Ordering::Acquire, Ordering::Acquire);
+            match cas {
+                Ok(_) => return Some(top),
+                Err(newhead) => head = newhead,
+            }
+        }
+    }
+
+    /// Racy, only a snapshot of the top
+    pub fn is_empty(&self) -> bool {
+        unpack(self.head.load(Ordering::Relaxed)).1 == NIL
+    }
+}
+
+impl crate::progress::LockFreePush for IndexStack {}
+impl crate::progress::LockFreePop for IndexStack {}
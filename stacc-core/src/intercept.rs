@@ -0,0 +1,64 @@
+/* Debug interceptors, behind the `intercept` feature.
+ *
+ * An interceptor is a plain function installed on one structure and called
+ * with every element that goes in or out of it, to check invariants in
+ * integration tests (unique task ids, no element popped twice...) without
+ * touching the call sites. Push interceptors run before the element becomes
+ * visible to other threads, pop interceptors once it was taken out, so both
+ * see an element nobody else can touch.
+ *
+ * Without the feature the cell is empty and every call compiles to nothing. */
+
+use core::marker::PhantomData;
+#[cfg(feature = "intercept")]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "intercept")]
+use crate::ordering::Ordering;
+
+/// What the element passed to an interceptor is going through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Push,
+    Pop,
+}
+
+/// Holds the interceptor of one structure
+#[doc(hidden)]
+pub struct InterceptCell<T> {
+    #[cfg(feature = "intercept")]
+    f: AtomicPtr<()>,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T> InterceptCell<T> {
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "intercept")]
+            f: AtomicPtr::new(core::ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "intercept")]
+    pub fn set(&self, f: Option<fn(&T, Op)>) {
+        let p = f.map_or(core::ptr::null_mut(), |f| f as *mut ());
+        self.f.store(p, Ordering::Release);
+    }
+
+    #[cfg(feature = "intercept")]
+    #[inline]
+    pub fn call(&self, x: &T, op: Op) {
+        let f = self.f.load(Ordering::Acquire);
+        if f.is_null() {
+            return;
+        }
+        /* SAFETY: only set() stores there, from a fn(&T, Op) */
+        let f: fn(&T, Op) = unsafe { core::mem::transmute(f) };
+        f(x, op);
+    }
+
+    #[cfg(not(feature = "intercept"))]
+    #[inline(always)]
+    pub fn call(&self, _x: &T, _op: Op) {}
+}
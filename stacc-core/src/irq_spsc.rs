@@ -0,0 +1,227 @@
+/* SPSC ring for the classic embedded setup: an interrupt handler produces,
+ * the main thread consumes (or the other way around), both on the same core.
+ *
+ * On a single core the only thing that can reorder our memory accesses is
+ * the compiler, hardware always observes its own stores in program order.
+ * An interrupt can't be "half-observed" either, it runs to completion
+ * between two instructions of the interrupted code. So a compiler fence
+ * plus a plain (relaxed) store of the index is enough to publish a slot.
+ *
+ * Only loads and stores are used on the indices, no read-modify-write,
+ * so this works on cores without CAS (e.g. Cortex-M0).
+ */
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, fence, AtomicBool, AtomicUsize};
+
+use crate::drop_policy::{DropPolicy, PolicyCell};
+use crate::ordering::Ordering;
+
+pub struct IrqSafeSpsc<T, const N: usize> {
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    single_core: bool,
+    /* Set once split_static() handed out the endpoints */
+    split: AtomicBool,
+
+    /* Size must be power of two */
+    data: [UnsafeCell<MaybeUninit<T>>; N],
+
+    /* What happens to the items left when the queue goes away */
+    drop_policy: PolicyCell<T>,
+}
+
+/* SAFETY: same reasoning as for the regular SPSC queue, each slot is accessed
+ * only by the side owning it according to head/tail */
+unsafe impl<T: Send, const N: usize> Send for IrqSafeSpsc<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for IrqSafeSpsc<T, N> {}
+
+impl<T, const N: usize> IrqSafeSpsc<T, N> {
+    /// Queue usable from any number of cores. Publishing uses real
+    /// acquire/release barriers. `N` must be a power of two, one slot is
+    /// kept empty, so `N - 1` items fit.
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two(), "N must be a power of two");
+        Self {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            single_core: false,
+            split: AtomicBool::new(false),
+            data: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            drop_policy: PolicyCell::new(),
+        }
+    }
+
+    /// Queue that publishes with compiler fences only.
+    ///
+    /// # Safety
+    ///
+    /// Both endpoints must only ever run on the same, single core
+    /// (typically an interrupt handler and the code it interrupts).
+    pub const unsafe fn new_single_core() -> Self {
+        let mut q = Self::new();
+        q.single_core = true;
+        q
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// What happens to the items never popped, when the queue is dropped
+    pub fn set_drop_policy(&self, policy: DropPolicy<T>) {
+        self.drop_policy.set(policy);
+    }
+
+    fn release(&self) {
+        if self.single_core {
+            compiler_fence(Ordering::Release);
+        } else {
+            fence(Ordering::Release);
+        }
+    }
+
+    fn acquire(&self) {
+        if self.single_core {
+            compiler_fence(Ordering::Acquire);
+        } else {
+            fence(Ordering::Acquire);
+        }
+    }
+
+    /// Splits the queue into its two endpoints. Taking `&mut self` makes
+    /// sure it can happen only once while the endpoints are alive.
+    pub fn split(&mut self) -> (IrqProducer<'_, T, N>, IrqConsumer<'_, T, N>) {
+        *self.split.get_mut() = true;
+        let this: &Self = self;
+        return this.endpoints();
+    }
+
+    /// Splits a queue living in a `static`. Returns `None` if the endpoints
+    /// were already handed out. The check runs inside a critical section,
+    /// so it is safe even on cores without compare-and-swap.
+    #[cfg(feature = "critical-section")]
+    pub fn split_static(&'static self) -> Option<(IrqProducer<'static, T, N>, IrqConsumer<'static, T, N>)> {
+        let taken = critical_section::with(|_| {
+            let taken = self.split.load(Ordering::Relaxed);
+            self.split.store(true, Ordering::Relaxed);
+            taken
+        });
+
+        if taken {
+            return None;
+        }
+        return Some(self.endpoints());
+    }
+
+    fn endpoints(&self) -> (IrqProducer<'_, T, N>, IrqConsumer<'_, T, N>) {
+        let producer = IrqProducer {
+            queue: self,
+            tail: self.tail.load(Ordering::Relaxed),
+            _not_sync: PhantomData,
+        };
+        let consumer = IrqConsumer {
+            queue: self,
+            head: self.head.load(Ordering::Relaxed),
+            _not_sync: PhantomData,
+        };
+        return (producer, consumer);
+    }
+}
+
+impl<T, const N: usize> Drop for IrqSafeSpsc<T, N> {
+    fn drop(&mut self) {
+        let mut head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+
+        /* Initialized elements live in [head, tail) */
+        let mut leaked = 0;
+        while head != tail {
+            let item = unsafe { ptr::read(self.data[head].get()).assume_init() };
+            leaked += self.drop_policy.dispose(item) as usize;
+            head = head.wrapping_add(1) & (N - 1);
+        }
+        self.drop_policy.report_leaks(core::any::type_name::<Self>(), leaked);
+    }
+}
+
+/// Producing side of an [`IrqSafeSpsc`]
+pub struct IrqProducer<'a, T, const N: usize> {
+    queue: &'a IrqSafeSpsc<T, N>,
+    /* Producer "owns" tail, so it keeps its own copy */
+    tail: usize,
+    _not_sync: PhantomData<core::cell::Cell<()>>,
+}
+
+impl<T, const N: usize> IrqProducer<'_, T, N> {
+    #[must_use = "the item is handed back if there is no room, use push_or_drop() to discard it"]
+    pub fn push(&mut self, x: T) -> Option<T> {
+        let tail = self.tail;
+        let newtail = tail.wrapping_add(1) & (N - 1);
+
+        if newtail == self.queue.head.load(Ordering::Relaxed) {
+            return Some(x);
+        }
+        /* Don't write the slot before seeing that the consumer left it */
+        self.queue.acquire();
+
+        unsafe {
+            ptr::write(self.queue.data[tail].get(), MaybeUninit::new(x));
+        }
+
+        self.queue.release();
+        self.queue.tail.store(newtail, Ordering::Relaxed);
+        self.tail = newtail;
+
+        return None;
+    }
+
+    /// Pushes `x`, or drops it if the queue is full, for call sites where
+    /// losing the item is fine (e.g. a sample in an interrupt handler).
+    /// Returns whether it was pushed.
+    pub fn push_or_drop(&mut self, x: T) -> bool {
+        self.push(x).is_none()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.tail.wrapping_add(1) & (N - 1) == self.queue.head.load(Ordering::Relaxed)
+    }
+}
+
+/// Consuming side of an [`IrqSafeSpsc`]
+pub struct IrqConsumer<'a, T, const N: usize> {
+    queue: &'a IrqSafeSpsc<T, N>,
+    /* Consumer "owns" head, so it keeps its own copy */
+    head: usize,
+    _not_sync: PhantomData<core::cell::Cell<()>>,
+}
+
+impl<T, const N: usize> IrqConsumer<'_, T, N> {
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.head;
+        if head == self.queue.tail.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.queue.acquire();
+
+        let item = unsafe { ptr::read(self.queue.data[head].get()).assume_init() };
+
+        let newhead = head.wrapping_add(1) & (N - 1);
+        self.queue.release();
+        self.queue.head.store(newhead, Ordering::Relaxed);
+        self.head = newhead;
+
+        return Some(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.tail.load(Ordering::Relaxed).wrapping_sub(self.head) & (N - 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,124 @@
+/* The lock-free algorithms only need an allocator, std conveniences
+ * (locks, blocking, async) live in the `stacc` crate */
+#![no_std]
+/* Early returns are the house style here */
+#![allow(clippy::needless_return)]
+#![allow(clippy::new_without_default)]
+
+extern crate alloc;
+
+#[doc(hidden)]
+pub mod limit;
+#[doc(hidden)]
+pub mod load;
+#[doc(hidden)]
+pub mod ordering;
+#[doc(hidden)]
+pub mod prefetch;
+#[doc(hidden)]
+pub mod sim_hook;
+
+/// Number of handle slots in the EBR stack (the hazard-pointer stack
+/// allocates its slots as needed). Picked with the `threads-*` cargo features,
+/// the largest enabled one wins, 32 by default.
+#[cfg(feature = "threads-256")]
+pub const MAX_THREADS: usize = 256;
+#[cfg(all(feature = "threads-64", not(feature = "threads-256")))]
+pub const MAX_THREADS: usize = 64;
+#[cfg(all(feature = "threads-8", not(any(feature = "threads-64", feature = "threads-256"))))]
+pub const MAX_THREADS: usize = 8;
+#[cfg(not(any(feature = "threads-8", feature = "threads-64", feature = "threads-256")))]
+pub const MAX_THREADS: usize = 32;
+
+/// Allocating a node failed, the element is handed back
+pub struct AllocError<T>(pub T);
+
+impl<T> core::fmt::Debug for AllocError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("AllocError(..)")
+    }
+}
+
+impl<T> core::fmt::Display for AllocError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl<T> core::error::Error for AllocError<T> {}
+
+/// The structure holds as many elements as it was allowed to, the
+/// element is handed back
+pub struct Full<T>(pub T);
+
+impl<T> core::fmt::Debug for Full<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Full(..)")
+    }
+}
+
+impl<T> core::fmt::Display for Full<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("node limit reached")
+    }
+}
+
+impl<T> core::error::Error for Full<T> {}
+
+/// Every handle slot of the structure is taken, see [`MAX_THREADS`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HandleLimitReached;
+
+impl core::fmt::Display for HandleLimitReached {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("all handle slots are taken")
+    }
+}
+
+impl core::error::Error for HandleLimitReached {}
+
+static NEXT_ID: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(1);
+
+/// Process-unique id for a new structure, see the `id()` methods.
+/// The counter is a usize, so on 32-bit targets ids repeat after 2^32.
+#[doc(hidden)]
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, ordering::Ordering::Relaxed) as u64
+}
+
+/// `Box::new` that hands `x` back instead of aborting on OOM
+#[doc(hidden)]
+pub fn try_alloc_box<T>(x: T) -> Result<alloc::boxed::Box<T>, T> {
+    let layout = core::alloc::Layout::new::<T>();
+    if layout.size() == 0 {
+        return Ok(alloc::boxed::Box::new(x));
+    }
+
+    /* SAFETY: the layout has a non-zero size */
+    let p = unsafe { alloc::alloc::alloc(layout) as *mut T };
+    if p.is_null() {
+        return Err(x);
+    }
+    /* SAFETY: freshly allocated with T's layout, just like Box::new */
+    unsafe {
+        p.write(x);
+        return Ok(alloc::boxed::Box::from_raw(p));
+    }
+}
+
+pub mod cache_padded;
+pub mod drop_counter;
+pub mod drop_policy;
+pub mod frozen;
+#[cfg(target_has_atomic = "64")]
+pub mod index_stack;
+pub mod intercept;
+pub mod irq_spsc;
+pub mod progress;
+pub mod req_res;
+#[cfg(target_has_atomic = "64")]
+pub mod slab_stacc;
+pub mod spsc_queue;
+pub mod stacc_lockfree_ebr;
+pub mod ticket_stack;
+pub mod weight;
@@ -0,0 +1,47 @@
+/* Node-count ceiling for the unbounded stacks, see `with_max_nodes()`.
+ *
+ * The count is a relaxed counter next to the stack, not part of the CAS,
+ * so pushes racing on the check can all get in: the ceiling may be
+ * exceeded by up to the number of handles pushing at the same time. That
+ * slack is the price for not making every push a second CAS loop. */
+
+use core::sync::atomic::AtomicUsize;
+
+use crate::ordering::Ordering;
+
+pub struct NodeLimit {
+    max: usize,
+    /* Wrapping, a pop may be counted before the push it took from */
+    count: AtomicUsize,
+}
+
+impl NodeLimit {
+    pub const fn new(max: usize) -> Self {
+        Self {
+            max,
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn add(&self, n: usize) {
+        self.count.fetch_add(n, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn sub(&self, n: usize) {
+        self.count.fetch_sub(n, Ordering::Relaxed);
+    }
+
+    /// Whether a push now would go over the ceiling
+    #[inline]
+    pub fn is_reached(&self) -> bool {
+        let count = self.count.load(Ordering::Relaxed);
+        /* Below zero after wrapping counts as empty, `max` may not fit an
+         * isize, so compare as usize */
+        if (count as isize) < 0 {
+            return false;
+        }
+        return count >= self.max;
+    }
+}
@@ -0,0 +1,113 @@
+/* Occupancy statistics of the lock-free stacks.
+ *
+ * ShardedLen is the length counter: one counter per shard, each on its own
+ * cache line, handles only touch the shard picked by their number and
+ * len() sums them. Pushes and pops on different threads don't fight over
+ * one line anymore, in exchange the sum is not a snapshot.
+ *
+ * LoadEstimate is an exponential moving average of the occupancy, for
+ * autoscalers that want a smoothed "how busy is it" without wrapping every
+ * push and pop. Updates are a relaxed load and store, not a read-modify-
+ * write, so racing updates may overwrite each other. That only drops
+ * samples, and the average stays within the range of the samples it was
+ * fed. */
+
+use core::sync::atomic::AtomicUsize;
+
+use crate::cache_padded::CachePadded;
+use crate::ordering::Ordering;
+
+/* Enough to spread the usual thread counts, still cheap to sum */
+const SHARDS: usize = 16;
+/* Summing every shard on each op would bring the contention back, so a
+ * handle feeds the load estimate only every this many ops */
+const SAMPLE_EVERY: usize = 8;
+
+pub struct ShardedLen {
+    /* Wrapping, a shard goes "negative" when a handle pops what another
+     * one pushed, only the sum makes sense */
+    shards: [CachePadded<AtomicUsize>; SHARDS],
+}
+
+/// A handle's view of a [`ShardedLen`]: which shard it writes and how
+/// many ops it did since the last load sample
+pub struct LenShard {
+    index: usize,
+    ops: usize,
+}
+
+impl LenShard {
+    /// Shard of handle number `handle`
+    pub const fn new(handle: usize) -> Self {
+        Self {
+            index: handle % SHARDS,
+            ops: 0,
+        }
+    }
+
+    fn sample_due(&mut self) -> bool {
+        self.ops += 1;
+        if self.ops < SAMPLE_EVERY {
+            return false;
+        }
+        self.ops = 0;
+        return true;
+    }
+}
+
+impl ShardedLen {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: CachePadded<AtomicUsize> = CachePadded::new(AtomicUsize::new(0));
+        Self { shards: [ZERO; SHARDS] }
+    }
+
+    /// Adds `n` to the shard, returns whether it is time to feed the load
+    /// estimate
+    #[inline]
+    pub fn add(&self, shard: &mut LenShard, n: usize) -> bool {
+        self.shards[shard.index].fetch_add(n, Ordering::Relaxed);
+        return shard.sample_due();
+    }
+
+    /// Like [`add`](Self::add), but subtracts
+    #[inline]
+    pub fn sub(&self, shard: &mut LenShard, n: usize) -> bool {
+        self.shards[shard.index].fetch_sub(n, Ordering::Relaxed);
+        return shard.sample_due();
+    }
+
+    /// Sum of the shards. A pop may be counted before the push it took
+    /// from, so a negative sum is reported as 0.
+    pub fn get(&self) -> usize {
+        let sum = self.shards.iter().fold(0usize, |sum, s| sum.wrapping_add(s.load(Ordering::Relaxed)));
+        return if (sum as isize) < 0 { 0 } else { sum };
+    }
+}
+
+/* Fixed point, the average is kept in 1/256ths */
+const FRAC: u32 = 8;
+/* Every sample weighs 1/2^SHIFT, so about the last 16 operations count */
+const SHIFT: u32 = 4;
+
+pub struct LoadEstimate {
+    avg: AtomicUsize,
+}
+
+impl LoadEstimate {
+    pub const fn new() -> Self {
+        Self { avg: AtomicUsize::new(0) }
+    }
+
+    /// Folds `occupancy` into the average
+    #[inline]
+    pub fn update(&self, occupancy: usize) {
+        let old = self.avg.load(Ordering::Relaxed);
+        let sample = occupancy.saturating_mul(1 << FRAC);
+        self.avg.store(old - (old >> SHIFT) + (sample >> SHIFT), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        self.avg.load(Ordering::Relaxed) as f64 / (1 << FRAC) as f64
+    }
+}
@@ -0,0 +1,42 @@
+/* Every module imports `Ordering` from here instead of std.
+ *
+ * Building with `RUSTFLAGS="--cfg stacc_seqcst"` upgrades every ordering
+ * in the crate to SeqCst. If a failure goes away with it, it is most likely
+ * a memory ordering bug.
+ *
+ * Building with `--cfg stacc_assert_ordering` turns on `assert_ordering!`
+ * checks of documented invariants (e.g. hazard published before deref).
+ *
+ * What still needs SeqCst, everything else is acquire/release at most:
+ *  - HP protect: hazard store + re-load of top (store-buffering against
+ *    the unlink in pop() and the SeqCst fence in scan()). On aarch64 these
+ *    are plain stlr/ldar, no barrier instructions.
+ *  - HP push-like CASes vs. the waiter counter (notify_pushed), same pattern.
+ *  - EBR is_active store vs. the epoch scan.
+ * tests/litmus.rs checks the store-buffering shapes we rely on. */
+
+#[cfg(not(stacc_seqcst))]
+pub use core::sync::atomic::Ordering;
+
+#[cfg(stacc_seqcst)]
+pub struct Ordering;
+
+#[cfg(stacc_seqcst)]
+#[allow(non_upper_case_globals, dead_code)]
+impl Ordering {
+    pub const Relaxed: core::sync::atomic::Ordering = core::sync::atomic::Ordering::SeqCst;
+    pub const Acquire: core::sync::atomic::Ordering = core::sync::atomic::Ordering::SeqCst;
+    pub const Release: core::sync::atomic::Ordering = core::sync::atomic::Ordering::SeqCst;
+    pub const AcqRel: core::sync::atomic::Ordering = core::sync::atomic::Ordering::SeqCst;
+    pub const SeqCst: core::sync::atomic::Ordering = core::sync::atomic::Ordering::SeqCst;
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! assert_ordering {
+    ($($arg:tt)*) => {
+        if cfg!(stacc_assert_ordering) {
+            assert!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,35 @@
+/* Cache prefetch hints for the pop paths, behind the `prefetch` feature.
+ *
+ * Popping chases `top -> next`, and the next pop mostly misses the cache
+ * on exactly that load. Prefetching the new top right after a successful
+ * pop hides some of it. A prefetch never faults, so it is fine even if the
+ * node got freed by the time the hint reaches memory. */
+
+/// Hints that the cache line at `p` will be read soon
+#[inline(always)]
+pub fn prefetch_read<T>(p: *const T) {
+    #[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+    {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+        /* SAFETY: only a hint, the address is never dereferenced, and SSE
+         * is part of the x86_64 baseline */
+        unsafe { _mm_prefetch::<_MM_HINT_T0>(p as *const i8) };
+    }
+
+    #[cfg(all(feature = "prefetch", target_arch = "aarch64"))]
+    {
+        /* SAFETY: only a hint, prfm never faults */
+        unsafe { core::arch::asm!("prfm pldl1keep, [{0}]", in(reg) p, options(nostack, readonly, preserves_flags)) };
+    }
+
+    #[cfg(not(all(feature = "prefetch", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+    let _ = p;
+}
+
+/// Whether a node is bigger than a cache line, so its payload is worth a
+/// prefetch of its own
+#[inline(always)]
+pub const fn spans_lines<N>() -> bool {
+    core::mem::size_of::<N>() > 64
+}
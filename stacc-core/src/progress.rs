@@ -0,0 +1,51 @@
+/* Progress guarantees as marker traits, so generic code can ask for them
+ * in bounds instead of relying on prose:
+ *
+ *   fn drain_in_signal_handler<Q: WaitFreePop>(q: &mut Q) { ... }
+ *
+ * The guarantees are about the operations themselves, assuming (as
+ * everyone does) that the allocator is lock-free. Waking async waiters
+ * of the HP stack takes a lock, that part is not covered. */
+
+/// Some push always completes in a bounded number of steps, no matter
+/// what other threads do: a stalled thread can't stall the others
+pub trait LockFreePush {}
+
+/// Like [`LockFreePush`], for pops
+///
+/// ```
+/// use stacc_core::progress::LockFreePop;
+///
+/// fn assert_lock_free_pop<S: LockFreePop>() {}
+/// assert_lock_free_pop::<stacc_core::stacc_lockfree_ebr::Local<u32>>();
+/// assert_lock_free_pop::<stacc_core::spsc_queue::QueueConsumer<u32>>();
+/// ```
+pub trait LockFreePop {}
+
+/// Every push completes in a bounded number of its own steps
+pub trait WaitFreePush: LockFreePush {}
+
+/// Every pop completes in a bounded number of its own steps
+pub trait WaitFreePop: LockFreePop {}
+
+/// Operations may take a lock, a thread stalled while holding it stalls
+/// everyone else
+pub trait Blocking {}
+
+use crate::irq_spsc::{IrqConsumer, IrqProducer};
+use crate::spsc_queue::{QueueConsumer, QueueProducer};
+use crate::stacc_lockfree_ebr::Local;
+
+impl<T> LockFreePush for Local<T> {}
+impl<T> LockFreePop for Local<T> {}
+
+/* Each side only waits for nothing, it either finds room/items or fails */
+impl<T> LockFreePush for QueueProducer<T> {}
+impl<T> WaitFreePush for QueueProducer<T> {}
+impl<T> LockFreePop for QueueConsumer<T> {}
+impl<T> WaitFreePop for QueueConsumer<T> {}
+
+impl<T, const N: usize> LockFreePush for IrqProducer<'_, T, N> {}
+impl<T, const N: usize> WaitFreePush for IrqProducer<'_, T, N> {}
+impl<T, const N: usize> LockFreePop for IrqConsumer<'_, T, N> {}
+impl<T, const N: usize> WaitFreePop for IrqConsumer<'_, T, N> {}
@@ -0,0 +1,146 @@
+/* Request/response over two SPSC rings, one each way.
+ *
+ * Every request gets an id from a counter of the requester, the responder
+ * hands it back with the response, so the requester can match responses
+ * to requests even if they are answered out of order. Nothing is shared
+ * besides the two rings: each endpoint holds the producer of one ring and
+ * the consumer of the other, and both go away together, so one liveness
+ * check covers both directions. */
+
+use crate::spsc_queue::{queue, QueueConsumer, QueueProducer};
+
+/// Matches a response to its request, see [`req_res`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestId(u64);
+
+/// Creates a request/response channel: requests of type `T` go from the
+/// [`Requester`] to the [`Responder`], responses of type `U` come back.
+///
+/// ```
+/// use stacc_core::req_res::req_res;
+///
+/// let (mut client, mut server) = req_res::<u32, String>();
+/// let id = client.request(7).unwrap();
+///
+/// assert_eq!(server.serve(|x| x.to_string()), 1);
+/// assert_eq!(client.response(), Some((id, "7".to_string())));
+/// ```
+pub fn req_res<T: Send, U: Send>() -> (Requester<T, U>, Responder<T, U>) {
+    let (requests, incoming) = queue();
+    let (outgoing, responses) = queue();
+    let requester = Requester {
+        requests,
+        responses,
+        next_id: 0,
+        in_flight: 0,
+    };
+    let responder = Responder { incoming, outgoing };
+    return (requester, responder);
+}
+
+/// Sending side of the requests, see [`req_res`]
+pub struct Requester<T, U> {
+    requests: QueueProducer<(RequestId, T)>,
+    responses: QueueConsumer<(RequestId, U)>,
+    next_id: u64,
+    /* Requests sent, minus responses received */
+    in_flight: usize,
+}
+
+impl<T, U> Requester<T, U> {
+    /// Sends `x`, hands it back if the request ring is full
+    pub fn request(&mut self, x: T) -> Result<RequestId, T> {
+        let id = RequestId(self.next_id);
+        if let Some((_, x)) = self.requests.push((id, x)) {
+            return Err(x);
+        }
+        self.next_id += 1;
+        self.in_flight += 1;
+        return Ok(id);
+    }
+
+    /// Next response and the id of the request it answers, in the order
+    /// the responder sent them
+    pub fn response(&mut self) -> Option<(RequestId, U)> {
+        let response = self.responses.pop()?;
+        self.in_flight -= 1;
+        return Some(response);
+    }
+
+    /// Requests not answered yet, assuming the responder answers each
+    /// one exactly once
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Whether the responder still exists. Responses it sent before going
+    /// away can still be received.
+    pub fn other_side_alive(&self) -> bool {
+        self.requests.other_side_alive() && self.responses.other_side_alive()
+    }
+}
+
+impl<T, U> core::fmt::Debug for Requester<T, U> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Requester")
+            .field("requests", &self.requests)
+            .field("responses", &self.responses)
+            .field("in_flight", &self.in_flight)
+            .finish()
+    }
+}
+
+/// Answering side of the requests, see [`req_res`]
+pub struct Responder<T, U> {
+    incoming: QueueConsumer<(RequestId, T)>,
+    outgoing: QueueProducer<(RequestId, U)>,
+}
+
+impl<T, U> Responder<T, U> {
+    /// Next request and the id to answer it with
+    pub fn next_request(&mut self) -> Option<(RequestId, T)> {
+        self.incoming.pop()
+    }
+
+    /// Sends the answer to request `id`, hands it back if the response
+    /// ring is full
+    pub fn respond(&mut self, id: RequestId, x: U) -> Result<(), U> {
+        match self.outgoing.push((id, x)) {
+            Some((_, x)) => Err(x),
+            None => Ok(()),
+        }
+    }
+
+    /// Answers requests with `f` while there are requests and room for
+    /// the responses, returns how many were answered
+    pub fn serve(&mut self, mut f: impl FnMut(T) -> U) -> usize {
+        let mut served = 0;
+        /* Checked first, so a request is never taken without room for
+         * its response */
+        while !self.outgoing.is_full() {
+            let (id, x) = match self.incoming.pop() {
+                Some(request) => request,
+                None => break,
+            };
+            let rejected = self.outgoing.push((id, f(x)));
+            debug_assert!(rejected.is_none(), "only we push responses");
+            served += 1;
+        }
+        return served;
+    }
+
+    /// Whether the requester still exists. Requests it sent before going
+    /// away can still be received.
+    pub fn other_side_alive(&self) -> bool {
+        self.incoming.other_side_alive() && self.outgoing.other_side_alive()
+    }
+}
+
+impl<T, U> core::fmt::Debug for Responder<T, U> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Responder")
+            .field("incoming", &self.incoming)
+            .field("outgoing", &self.outgoing)
+            .finish()
+    }
+}
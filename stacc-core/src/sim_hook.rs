@@ -0,0 +1,38 @@
+/* Yield points for the deterministic simulator (`stacc::sim`).
+ *
+ * The lock-free algorithms call yield_point() between the steps another
+ * thread could interleave with (after reading top, before the CAS...).
+ * Without the `sim` feature it compiles to nothing. With it, the call is
+ * forwarded to the hook the simulator installed, which may switch to
+ * another virtual handle before returning. */
+
+#[cfg(feature = "sim")]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "sim")]
+use crate::ordering::Ordering;
+
+#[cfg(feature = "sim")]
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Installs the function every yield point calls with its name
+#[cfg(feature = "sim")]
+pub fn set_hook(hook: fn(&'static str)) {
+    HOOK.store(hook as *mut (), Ordering::Release);
+}
+
+#[cfg(feature = "sim")]
+#[inline]
+pub fn yield_point(name: &'static str) {
+    let hook = HOOK.load(Ordering::Acquire);
+    if hook.is_null() {
+        return;
+    }
+    /* SAFETY: only set_hook() stores there, from a fn pointer */
+    let hook: fn(&'static str) = unsafe { core::mem::transmute(hook) };
+    hook(name);
+}
+
+#[cfg(not(feature = "sim"))]
+#[inline(always)]
+pub fn yield_point(_name: &'static str) {}
@@ -0,0 +1,165 @@
+/* Fixed slab of slots with an IndexStack as the free list.
+ *
+ * insert() pops a free index and writes the value there, remove() moves
+ * the value out and pushes the index back. The free list hands each index
+ * to one inserter at a time, and a Key is the only way to name an occupied
+ * slot: it can't be copied, and remove() consumes it. So whoever holds the
+ * key owns the slot, without any locking. The release push and acquire
+ * pop of the free list order a remove() before the next insert() into the
+ * same slot.
+ *
+ * Keys carry the id of their slab, so a key of another slab is caught
+ * instead of naming a random slot here. */
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+use crate::index_stack::IndexStack;
+use crate::{next_id, Full};
+
+/// Names an occupied slot of a [`SlabStacc`], until it is passed to
+/// [`remove`](SlabStacc::remove). Deliberately not `Clone`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[must_use = "the value can't be removed without its key"]
+pub struct Key {
+    slab: u64,
+    index: u32,
+}
+
+impl Key {
+    /// Slot number, below the slab's capacity. Stable for as long as the
+    /// key lives, e.g. to index a side table.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+/// Lock-free slab with stable keys: [`insert`](Self::insert) finds a free
+/// slot, [`remove`](Self::remove) frees it again, both only through `&self`.
+///
+/// ```
+/// use stacc_core::slab_stacc::SlabStacc;
+///
+/// let slab = SlabStacc::new(2);
+/// let a = slab.insert("a").unwrap();
+/// let b = slab.insert("b").unwrap();
+/// assert!(slab.insert("c").is_err());
+/// assert_eq!(slab.get(&b), &"b");
+/// assert_eq!(slab.remove(a), "a");
+/// ```
+pub struct SlabStacc<T> {
+    id: u64,
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    free: IndexStack,
+}
+
+/* SAFETY: values move in and out through shared references, possibly on
+ * different threads, hence T: Send. Sharing them (get()) needs T: Sync on
+ * top of that, which get() asks for itself */
+unsafe impl<T: Send> Send for SlabStacc<T> {}
+unsafe impl<T: Send> Sync for SlabStacc<T> {}
+
+impl<T> SlabStacc<T> {
+    /// Empty slab with room for `capacity` values
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            id: next_id(),
+            slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+            free: IndexStack::full(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.free.capacity()
+    }
+
+    /// Stores `x` in a free slot, hands it back if there is none
+    pub fn insert(&self, x: T) -> Result<Key, Full<T>> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => return Err(Full(x)),
+        };
+        /* SAFETY: the free list gave the slot to us alone */
+        unsafe { (*self.slots[index as usize].get()).write(x) };
+        return Ok(Key { slab: self.id, index });
+    }
+
+    /// Takes the value out and frees its slot. Panics if `key` is from
+    /// another slab.
+    pub fn remove(&self, key: Key) -> T {
+        let slot = self.slot(&key);
+        /* SAFETY: the key was the only way to reach the slot, and it is
+         * gone now */
+        let x = unsafe { (*slot.get()).assume_init_read() };
+        self.free.push(key.index);
+        return x;
+    }
+
+    /// Panics if `key` is from another slab. The reference borrows the
+    /// key, so the value can't be removed under it:
+    ///
+    /// ```compile_fail
+    /// let slab = stacc_core::slab_stacc::SlabStacc::new(1);
+    /// let key = slab.insert(1).unwrap();
+    /// let x = slab.get(&key);
+    /// slab.remove(key);
+    /// assert_eq!(*x, 1);
+    /// ```
+    pub fn get<'a>(&'a self, key: &'a Key) -> &'a T
+    where
+        T: Sync,
+    {
+        let slot = self.slot(key);
+        /* SAFETY: the slot stays occupied while the key is borrowed,
+         * others may read it too, but only with T: Sync */
+        unsafe { (*slot.get()).assume_init_ref() }
+    }
+
+    /// Panics if `key` is from another slab
+    /* The exclusive borrow comes from the key, not from `self` */
+    #[allow(clippy::mut_from_ref)]
+    pub fn get_mut<'a>(&'a self, key: &'a mut Key) -> &'a mut T {
+        let slot = self.slot(key);
+        /* SAFETY: the slot stays occupied while the key is borrowed, and
+         * the key is borrowed mutably, so this is the only reference */
+        unsafe { (*slot.get()).assume_init_mut() }
+    }
+
+    /// Racy, only a snapshot
+    pub fn is_full(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    fn slot(&self, key: &Key) -> &UnsafeCell<MaybeUninit<T>> {
+        assert_eq!(key.slab, self.id, "key of another SlabStacc");
+        return &self.slots[key.index as usize];
+    }
+}
+
+impl<T> Drop for SlabStacc<T> {
+    fn drop(&mut self) {
+        /* Whatever is not on the free list is occupied */
+        let mut occupied = vec![true; self.slots.len()];
+        while let Some(index) = self.free.pop() {
+            occupied[index as usize] = false;
+        }
+        for (slot, _) in self.slots.iter_mut().zip(occupied).filter(|(_, occupied)| *occupied) {
+            /* SAFETY: occupied, and nobody can use the keys anymore */
+            unsafe { slot.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for SlabStacc<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SlabStacc")
+            .field("id", &self.id)
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> crate::progress::LockFreePush for SlabStacc<T> {}
+impl<T> crate::progress::LockFreePop for SlabStacc<T> {}
@@ -0,0 +1,764 @@
+use core::cell::{Cell, UnsafeCell};
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::ptr;
+use core::sync::atomic::{self, AtomicUsize};
+use alloc::sync::Arc;
+
+use crate::drop_policy::{DropPolicy, PolicyCell};
+use crate::intercept::{InterceptCell, Op};
+use crate::load::LoadEstimate;
+use crate::ordering::Ordering;
+use crate::next_id;
+use crate::weight::{Weigher, WeightBudget};
+
+struct QueueInner<T> {
+    /* Free-running indices, only masked when a slot is accessed, so
+     * tail - head is the length even across the wraparound of usize */
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    /* See QueueProducer::id() */
+    id: u64,
+
+    /* Size must be power of two */
+    data: [UnsafeCell<MaybeUninit<T>>; 256],
+
+    /* See weighted_queue() */
+    weight: Option<(WeightBudget, Weigher<T>)>,
+
+    /* Written by the producer only, see sample_occupancy() */
+    histogram: [AtomicUsize; HISTOGRAM_BUCKETS],
+    /* Occupancy seen by the producer's pushes, see load_estimate() */
+    load: LoadEstimate,
+
+    /* What happens to the items left when both endpoints are gone */
+    drop_policy: PolicyCell<T>,
+    /* See set_interceptor() */
+    intercept: InterceptCell<T>,
+}
+
+/* Occupancy is at most 255: bucket 0 for empty, then one per bit length */
+const HISTOGRAM_BUCKETS: usize = 9;
+
+/// Sampled occupancy of a queue, see [`QueueProducer::sample_occupancy`].
+/// `buckets[0]` counts samples of an empty queue, `buckets[i]` samples
+/// with occupancy in `2^(i-1) .. 2^i`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OccupancyHistogram {
+    pub buckets: [usize; HISTOGRAM_BUCKETS],
+}
+
+impl OccupancyHistogram {
+    pub fn samples(&self) -> usize {
+        self.buckets.iter().sum()
+    }
+}
+
+/* SAFETY: slots are only ever accessed by the side that owns them according
+ * to head/tail, and items only move between threads, they are never shared */
+unsafe impl<T: Send> Send for QueueInner<T> {}
+unsafe impl<T: Send> Sync for QueueInner<T> {}
+
+impl<T> QueueInner<T> {
+    fn new(start: usize, weight: Option<(WeightBudget, Weigher<T>)>) -> Self {
+        Self {
+            head: AtomicUsize::new(start),
+            tail: AtomicUsize::new(start),
+            id: next_id(),
+            data: core::array::from_fn(|_| UnsafeCell::new(MaybeUninit::uninit())),
+            weight,
+            histogram: Default::default(),
+            load: LoadEstimate::new(),
+            drop_policy: PolicyCell::new(),
+            intercept: InterceptCell::new(),
+        }
+    }
+
+    fn mask(&self) -> usize {
+        self.data.len() - 1
+    }
+
+    /// The slot behind a free-running index
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.data[index & self.mask()].get()
+    }
+
+    /* One slot less than there is, so occupancy fits the histogram */
+    fn capacity(&self) -> usize {
+        self.data.len() - 1
+    }
+
+    fn histogram(&self) -> OccupancyHistogram {
+        OccupancyHistogram {
+            buckets: core::array::from_fn(|i| self.histogram[i].load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Called by the consumer for every item leaving the queue
+    fn unweigh(&self, x: &T) {
+        if let Some((budget, weigh)) = &self.weight {
+            budget.release(weigh(x));
+        }
+    }
+}
+
+impl<T> Drop for QueueInner<T> {
+    fn drop(&mut self) {
+        let mut head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+
+        /* Initialized elements live in [head, tail) */
+        let mut leaked = 0;
+        while head != tail {
+            let item = unsafe { ptr::read(self.slot(head)).assume_init() };
+            leaked += self.drop_policy.dispose(item) as usize;
+            head = head.wrapping_add(1);
+        }
+        self.drop_policy.report_leaks(core::any::type_name::<Self>(), leaked);
+    }
+}
+
+/// Creates a single-producer single-consumer queue.
+///
+/// The whole point of the queue is moving items to another thread,
+/// so `T` has to be `Send`:
+///
+/// ```compile_fail
+/// let (tx, rx) = stacc_core::spsc_queue::queue::<std::rc::Rc<u32>>();
+/// ```
+pub fn queue<T: Send>() -> (QueueProducer<T>, QueueConsumer<T>) {
+    return with_inner(QueueInner::new(0, None));
+}
+
+/// Like [`queue`], with both indices starting at `start`, so tests can
+/// reach the wraparound of the indices quickly
+#[doc(hidden)]
+pub fn queue_starting_at<T: Send>(start: usize) -> (QueueProducer<T>, QueueConsumer<T>) {
+    return with_inner(QueueInner::new(start, None));
+}
+
+/// Like [`queue`], but items also cost `weigh(&item)` out of a budget of
+/// `limit`, e.g. their size in bytes. Pushes fail once either the slots
+/// or the budget run out.
+///
+/// ```
+/// let (mut tx, mut rx) = stacc_core::spsc_queue::weighted_queue(8, |s: &String| s.len());
+/// assert_eq!(tx.push("hello".to_string()), None);
+/// assert!(tx.push("world".to_string()).is_some());
+/// rx.pop();
+/// assert_eq!(tx.push("world".to_string()), None);
+/// ```
+pub fn weighted_queue<T: Send>(limit: usize, weigh: Weigher<T>) -> (QueueProducer<T>, QueueConsumer<T>) {
+    return with_inner(QueueInner::new(0, Some((WeightBudget::new(limit), weigh))));
+}
+
+fn with_inner<T>(mut inner: QueueInner<T>) -> (QueueProducer<T>, QueueConsumer<T>) {
+    let start = *inner.tail.get_mut();
+    let inner = Arc::new(inner);
+    let producer = QueueProducer {
+        inner: Arc::clone(&inner),
+        tail: start,
+        sample_every: 0,
+        until_sample: 0,
+        _not_sync: PhantomData,
+    };
+    let consumer = QueueConsumer {
+        inner,
+        head: start,
+        _not_sync: PhantomData,
+    };
+    return (producer, consumer);
+}
+
+/// An endpoint in transit between threads.
+///
+/// Endpoints are `Send` (when `T: Send`) but never `Sync`: at any time
+/// exactly one thread may use each side. Moving an endpoint with
+/// `thread::spawn`, a channel or a mutex already synchronizes everything,
+/// but runtimes that migrate endpoints through their own, possibly relaxed,
+/// atomics should wrap them in a `Handoff`. Creating it issues a release
+/// fence, [`take`](Self::take) issues the matching acquire fence, so the
+/// new thread sees the cached index and every slot the old thread touched.
+///
+/// ```
+/// use stacc_core::spsc_queue::*;
+///
+/// let (mut tx, rx) = queue::<u32>();
+/// let token = rx.handoff();
+/// std::thread::spawn(move || {
+///     let mut rx = token.take();
+///     while rx.pop().is_none() {}
+/// });
+/// assert_eq!(tx.push(1), None);
+/// ```
+#[must_use = "the endpoint is only usable after take()"]
+pub struct Handoff<E> {
+    endpoint: E,
+}
+
+impl<E> Handoff<E> {
+    fn new(endpoint: E) -> Self {
+        atomic::fence(Ordering::Release);
+        Self { endpoint }
+    }
+
+    /// Call on the receiving thread
+    pub fn take(self) -> E {
+        atomic::fence(Ordering::Acquire);
+        self.endpoint
+    }
+}
+
+/// Receiving side of the queue, see [`queue`]. Never `Sync`:
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<stacc_core::spsc_queue::QueueConsumer<u32>>();
+/// ```
+///
+/// and never `Clone`, there is exactly one consumer. To watch the items
+/// from somewhere else, use [`tee`](Self::tee).
+///
+/// ```compile_fail
+/// let (_tx, rx) = stacc_core::spsc_queue::queue::<u32>();
+/// let rx2 = rx.clone();
+/// ```
+pub struct QueueConsumer<T> {
+    inner: Arc<QueueInner<T>>,
+    /* Consumer "owns" head, so it keeps its own copy */
+    head: usize,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<T> QueueConsumer<T> {
+    /// Prepares this endpoint to be moved to another thread, see [`Handoff`]
+    pub fn handoff(self) -> Handoff<Self> {
+        Handoff::new(self)
+    }
+
+    /// Process-unique id of the queue, the same on both endpoints
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    /// Same as [`approx_len`](Self::approx_len)
+    pub fn len(&self) -> usize {
+        self.approx_len()
+    }
+
+    /// Whether nothing can be popped right now. Once false, it stays false
+    /// until this consumer pops.
+    pub fn is_empty(&self) -> bool {
+        self.approx_len() == 0
+    }
+
+    /// Cheap, racy length. Only the producer's index is loaded, so the
+    /// result is never above [`capacity`](Self::capacity), but the
+    /// producer may have pushed more in the meantime.
+    pub fn approx_len(&self) -> usize {
+        let tail = self.inner.tail.load(Ordering::Acquire);
+        return tail.wrapping_sub(self.head);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Whether every slot is taken. Once false, the producer may fill the
+    /// queue again any time.
+    pub fn is_full(&self) -> bool {
+        self.approx_len() == self.capacity()
+    }
+
+    /// Slots not taken, an upper bound since the producer may push more
+    /// in the meantime
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.approx_len()
+    }
+
+    /// Occupancy samples recorded by the producer so far
+    pub fn occupancy_histogram(&self) -> OccupancyHistogram {
+        self.inner.histogram()
+    }
+
+    /// Moving average of the occupancy over roughly the last 16 push
+    /// attempts, e.g. for deciding how many consumers to run
+    pub fn load_estimate(&self) -> f64 {
+        self.inner.load.get()
+    }
+
+    /// What happens to the items never popped, once both endpoints are gone
+    pub fn set_drop_policy(&self, policy: DropPolicy<T>) {
+        self.inner.drop_policy.set(policy);
+    }
+
+    /// Debugging aid: calls `f` with every item pushed to or popped from
+    /// the queue, `None` removes it. Pushes are reported before the item
+    /// is written, pops once it left the ring (before `f` runs for
+    /// `pop_with`). Only with the `intercept` feature.
+    #[cfg(feature = "intercept")]
+    pub fn set_interceptor(&self, f: Option<fn(&T, Op)>) {
+        self.inner.intercept.set(f);
+    }
+
+    /// Once this returns false, everything the other side did before
+    /// being dropped is visible, e.g. its last pushes can still be popped
+    pub fn other_side_alive(&self) -> bool {
+        if Arc::strong_count(&self.inner) == 2 {
+            return true;
+        }
+        /* Pairs with the release decrement in Arc::drop */
+        atomic::fence(Ordering::Acquire);
+        return false;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.head;
+        let tail = self.inner.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        let item = unsafe { ptr::read(self.inner.slot(head)).assume_init() };
+        /* The slot goes back before any user code runs, so if the weigher
+         * or the interceptor panics, only our copy gets dropped */
+        self.advance_head(1);
+        self.inner.unweigh(&item);
+        self.inner.intercept.call(&item, Op::Pop);
+
+        return Some(item);
+    }
+
+    /// Pops without looking at the producer's index, for hot loops that
+    /// already know how many items are there.
+    ///
+    /// # Safety
+    ///
+    /// An item must be ready: fewer pops since the last
+    /// [`approx_len`](Self::approx_len) (or [`peek`](Self::peek)) on this
+    /// consumer than it reported.
+    ///
+    /// ```
+    /// let (mut tx, mut rx) = stacc_core::spsc_queue::queue::<u32>();
+    /// assert_eq!(tx.push(1), None);
+    /// assert_eq!(tx.push(2), None);
+    /// for _ in 0..rx.approx_len() {
+    ///     let _ = unsafe { rx.pop_unchecked() };
+    /// }
+    /// assert_eq!(rx.pop(), None);
+    /// ```
+    pub unsafe fn pop_unchecked(&mut self) -> T {
+        /* The acquire load of tail that saw the item was done by the caller */
+        debug_assert!(self.head != self.inner.tail.load(Ordering::Relaxed), "pop_unchecked(): the queue is empty");
+        let item = ptr::read(self.inner.slot(self.head)).assume_init();
+        /* See pop() */
+        self.advance_head(1);
+        self.inner.unweigh(&item);
+        self.inner.intercept.call(&item, Op::Pop);
+        return item;
+    }
+
+    /// Pops an element and hands it to `f` by reference, right where it
+    /// lies in the ring, then drops it. The slot is given back to the
+    /// producer only afterwards, so large `T`s are never moved.
+    pub fn pop_with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let head = self.head;
+        let tail = self.inner.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        let slot = self.inner.slot(head);
+
+        /* Drops the element and releases the slot, even if `f` panics */
+        struct Guard<'a, T>(&'a mut QueueConsumer<T>, *mut MaybeUninit<T>);
+        impl<T> Drop for Guard<'_, T> {
+            fn drop(&mut self) {
+                unsafe { ptr::drop_in_place((*self.1).as_mut_ptr()) };
+                self.0.advance_head(1);
+            }
+        }
+
+        let guard = Guard(self, slot);
+        let item = unsafe { (*guard.1).assume_init_mut() };
+        guard.0.inner.intercept.call(item, Op::Pop);
+        /* Before `f` gets a chance to change the weight */
+        guard.0.inner.unweigh(item);
+        return Some(f(item));
+    }
+
+    /// Items ready to be popped, without popping them, as two slices
+    /// because the ring may wrap around. Process them in place, then
+    /// [`release`](Self::release) as many as you're done with.
+    pub fn peek(&self) -> (&[T], &[T]) {
+        let head = self.head;
+        let tail = self.inner.tail.load(Ordering::Acquire);
+
+        let data = &self.inner.data;
+        let start = head & self.inner.mask();
+        let len = tail.wrapping_sub(head);
+        let (first, second) = if start + len <= data.len() {
+            (&data[start..start + len], &data[..0])
+        } else {
+            (&data[start..], &data[..tail & self.inner.mask()])
+        };
+
+        /* SAFETY: slots in [head, tail) are initialized and only the
+         * consumer touches them, UnsafeCell<MaybeUninit<T>> has the same
+         * layout as T */
+        unsafe {
+            let first = core::slice::from_raw_parts(first.as_ptr() as *const T, first.len());
+            let second = core::slice::from_raw_parts(second.as_ptr() as *const T, second.len());
+            return (first, second);
+        }
+    }
+
+    /// Drops the first `n` items and gives their slots back to the producer
+    /// with a single store. Panics if fewer than `n` items are available.
+    pub fn release(&mut self, n: usize) {
+        let tail = self.inner.tail.load(Ordering::Acquire);
+        let available = tail.wrapping_sub(self.head);
+        assert!(n <= available, "releasing {} items, but only {} are available", n, available);
+
+        /* Gives back the slots of the items dropped so far, also if a
+         * drop (or the interceptor) panics. The item being dropped counts
+         * as gone already, it is moved out first so unwinding drops it */
+        struct Released<'a, T>(&'a mut QueueConsumer<T>, usize);
+        impl<T> Drop for Released<'_, T> {
+            fn drop(&mut self) {
+                self.0.advance_head(self.1);
+            }
+        }
+
+        let mut released = Released(self, 0);
+        while released.1 < n {
+            let consumer = &*released.0;
+            let item = unsafe { ptr::read(consumer.inner.slot(consumer.head.wrapping_add(released.1))).assume_init() };
+            released.1 += 1;
+            /* Same order as in pop_with() */
+            consumer.inner.intercept.call(&item, Op::Pop);
+            consumer.inner.unweigh(&item);
+            drop(item);
+        }
+    }
+
+    /// Gives `n` slots at head back to the producer
+    fn advance_head(&mut self, n: usize) {
+        let newhead = self.head.wrapping_add(n);
+        self.inner.head.store(newhead, Ordering::Release);
+        self.head = newhead;
+    }
+
+    /// Taps the queue: every item popped through the returned [`Tee`] is
+    /// also cloned into a second queue, whose consumer is returned too.
+    /// The tap never blocks the pipeline, if it is full, copies are dropped.
+    ///
+    /// ```
+    /// use stacc_core::spsc_queue::*;
+    ///
+    /// let (mut tx, rx) = queue::<u32>();
+    /// let (mut rx, mut tap) = rx.tee();
+    /// assert_eq!(tx.push(1), None);
+    /// assert_eq!(rx.pop(), Some(1));
+    /// assert_eq!(tap.pop(), Some(1));
+    /// ```
+    pub fn tee(self) -> (Tee<T>, QueueConsumer<T>)
+    where
+        T: Clone + Send,
+    {
+        self.tee_filtered(|_| true)
+    }
+
+    /// Like [`tee`](Self::tee), but only items for which `filter`
+    /// returns true are copied
+    pub fn tee_filtered<F>(self, filter: F) -> (Tee<T, F>, QueueConsumer<T>)
+    where
+        T: Clone + Send,
+        F: FnMut(&T) -> bool,
+    {
+        let (tap, tap_rx) = queue();
+        let tee = Tee {
+            rx: self,
+            tap,
+            filter,
+            dropped: 0,
+        };
+        return (tee, tap_rx);
+    }
+}
+
+/// A consumer whose items are also copied to a tap, see
+/// [`QueueConsumer::tee`]. Derefs to the consumer for inspection.
+pub struct Tee<T, F = fn(&T) -> bool> {
+    rx: QueueConsumer<T>,
+    tap: QueueProducer<T>,
+    filter: F,
+    dropped: usize,
+}
+
+impl<T: Clone, F: FnMut(&T) -> bool> Tee<T, F> {
+    pub fn pop(&mut self) -> Option<T> {
+        let x = self.rx.pop()?;
+        if (self.filter)(&x) && self.tap.push(x.clone()).is_some() {
+            self.dropped += 1;
+        }
+        return Some(x);
+    }
+
+    /// Copies that didn't fit into the tap
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Removes the tap, its consumer sees the producer side gone
+    pub fn into_inner(self) -> QueueConsumer<T> {
+        self.rx
+    }
+}
+
+impl<T, F> Deref for Tee<T, F> {
+    type Target = QueueConsumer<T>;
+
+    fn deref(&self) -> &QueueConsumer<T> {
+        &self.rx
+    }
+}
+
+/// Sending side of the queue, see [`queue`]. Never `Sync`:
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<stacc_core::spsc_queue::QueueProducer<u32>>();
+/// ```
+pub struct QueueProducer<T> {
+    inner: Arc<QueueInner<T>>,
+    /* Producer "owns" tail, so it keeps its own copy */
+    tail: usize,
+    /* Pushes between occupancy samples, 0 means off */
+    sample_every: usize,
+    until_sample: usize,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<T> QueueProducer<T> {
+    /// Prepares this endpoint to be moved to another thread, see [`Handoff`]
+    pub fn handoff(self) -> Handoff<Self> {
+        Handoff::new(self)
+    }
+
+    /// Process-unique id of the queue, the same on both endpoints
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    /// Same as [`approx_len`](Self::approx_len)
+    pub fn len(&self) -> usize {
+        self.approx_len()
+    }
+
+    /// Whether the consumer has popped everything published so far. Once
+    /// true, it stays true until this producer pushes.
+    pub fn is_empty(&self) -> bool {
+        self.approx_len() == 0
+    }
+
+    /// Cheap, racy length. Only the consumer's index is loaded, so the
+    /// result is never above [`capacity`](Self::capacity), but the
+    /// consumer may have popped more in the meantime.
+    pub fn approx_len(&self) -> usize {
+        let head = self.inner.head.load(Ordering::Acquire);
+        return self.tail.wrapping_sub(head);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// True if the next push would fail (unless the consumer pops first)
+    pub fn is_full(&self) -> bool {
+        self.approx_len() == self.capacity()
+    }
+
+    /// How many pushes are guaranteed to succeed, as far as slots go.
+    /// With [`weighted_queue`] the weight budget may run out first.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.approx_len()
+    }
+
+    /// Weight of the items in the queue, always 0 unless it was
+    /// created with [`weighted_queue`]
+    pub fn weight_used(&self) -> usize {
+        self.inner.weight.as_ref().map_or(0, |(budget, _)| budget.used())
+    }
+
+    /// Records the occupancy into a histogram on every `every`-th push
+    /// attempt, `0` turns sampling off. The histogram is kept in the queue
+    /// and can be read from either endpoint.
+    pub fn sample_occupancy(&mut self, every: usize) {
+        self.sample_every = every;
+        self.until_sample = every;
+    }
+
+    /// Occupancy samples recorded so far
+    pub fn occupancy_histogram(&self) -> OccupancyHistogram {
+        self.inner.histogram()
+    }
+
+    /// Same as [`QueueConsumer::set_interceptor`]
+    #[cfg(feature = "intercept")]
+    pub fn set_interceptor(&self, f: Option<fn(&T, Op)>) {
+        self.inner.intercept.set(f);
+    }
+
+    /// Same as [`QueueConsumer::load_estimate`]
+    pub fn load_estimate(&self) -> f64 {
+        self.inner.load.get()
+    }
+
+    /// Once this returns false, the consumer is gone for good and
+    /// nothing pushed from now on will ever be popped
+    pub fn other_side_alive(&self) -> bool {
+        if Arc::strong_count(&self.inner) == 2 {
+            return true;
+        }
+        /* Pairs with the release decrement in Arc::drop */
+        atomic::fence(Ordering::Acquire);
+        return false;
+    }
+
+    #[must_use = "the item is handed back if there is no room, use push_or_drop() to discard it"]
+    pub fn push(&mut self, x: T) -> Option<T> {
+        if let Some(x) = self.push_unpublished(x) {
+            return Some(x);
+        }
+        self.publish();
+        return None;
+    }
+
+    /// Pushes `x`, or drops it if the queue is full, for call sites where
+    /// losing the item is fine. Returns whether it was pushed.
+    pub fn push_or_drop(&mut self, x: T) -> bool {
+        self.push(x).is_none()
+    }
+
+    /// Writes an item without showing it to the consumer yet, so a burst
+    /// can be made visible with a single [`publish`](Self::publish).
+    /// Unpublished items still take up space, and are published when
+    /// the producer is dropped.
+    #[must_use = "the item is handed back if there is no room, use push_or_drop() to discard it"]
+    pub fn push_unpublished(&mut self, x: T) -> Option<T> {
+        let tail = self.tail;
+        let head = self.inner.head.load(Ordering::Acquire);
+        self.sample(tail, head);
+
+        if tail.wrapping_sub(head) == self.inner.capacity() {
+            return Some(x);
+        }
+        if let Some((budget, weigh)) = &self.inner.weight {
+            if !budget.try_acquire(weigh(&x)) {
+                return Some(x);
+            }
+        }
+
+        self.inner.intercept.call(&x, Op::Push);
+        unsafe {
+            ptr::write(self.inner.slot(tail), MaybeUninit::new(x));
+        }
+        self.tail = tail.wrapping_add(1);
+
+        return None;
+    }
+
+    /// Pushes an item built by `f` right inside the queue, so a large `T`
+    /// is never moved. `f` must initialize the slot it gets and return the
+    /// reference from [`MaybeUninit::write`], it panics otherwise. `f` is
+    /// not called if the queue is full. Returns whether an item was pushed,
+    /// in a weighted queue it is dropped again if it doesn't fit.
+    ///
+    /// ```
+    /// let (mut tx, mut rx) = stacc_core::spsc_queue::queue::<[u8; 4096]>();
+    /// assert!(tx.push_with(|slot| slot.write([7; 4096])));
+    /// assert_eq!(rx.pop().unwrap()[4095], 7);
+    /// ```
+    pub fn push_with(&mut self, f: impl FnOnce(&mut MaybeUninit<T>) -> &mut T) -> bool {
+        let tail = self.tail;
+        let head = self.inner.head.load(Ordering::Acquire);
+        self.sample(tail, head);
+
+        if tail.wrapping_sub(head) == self.inner.capacity() {
+            return false;
+        }
+
+        /* SAFETY: [tail, head + capacity) belongs to the producer */
+        let slot = unsafe { &mut *self.inner.slot(tail) };
+        let expected = slot.as_mut_ptr();
+        let item = f(slot) as *mut T;
+        assert!(item == expected, "push_with(): the closure must return the slot it initialized");
+
+        if let Some((budget, weigh)) = &self.inner.weight {
+            /* SAFETY: just checked that the slot was initialized */
+            if !budget.try_acquire(weigh(unsafe { &*item })) {
+                unsafe { ptr::drop_in_place(item) };
+                return false;
+            }
+        }
+        /* SAFETY: see above */
+        self.inner.intercept.call(unsafe { &*item }, Op::Push);
+
+        self.tail = tail.wrapping_add(1);
+        self.publish();
+        return true;
+    }
+
+    fn sample(&mut self, tail: usize, head: usize) {
+        self.inner.load.update(tail.wrapping_sub(head));
+        if self.sample_every == 0 {
+            return;
+        }
+        self.until_sample -= 1;
+        if self.until_sample == 0 {
+            self.until_sample = self.sample_every;
+            let occupancy = tail.wrapping_sub(head);
+            let bucket = (usize::BITS - occupancy.leading_zeros()) as usize;
+            /* We are the only writer, no need for a read-modify-write */
+            let counter = &self.inner.histogram[bucket];
+            counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+        }
+    }
+
+    /// Makes every item pushed so far visible to the consumer
+    pub fn publish(&mut self) {
+        /* Release makes the ptr::writes visible to the consumer's acquire
+         * load of tail, no extra fence needed (it was a full barrier on aarch64) */
+        self.inner.tail.store(self.tail, Ordering::Release);
+    }
+}
+
+impl<T> Drop for QueueProducer<T> {
+    fn drop(&mut self) {
+        self.publish();
+    }
+}
+
+impl<T> core::fmt::Debug for QueueProducer<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("QueueProducer")
+            .field("id", &self.id())
+            .field("len", &self.approx_len())
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> core::fmt::Debug for QueueConsumer<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("QueueConsumer")
+            .field("id", &self.id())
+            .field("len", &self.approx_len())
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,757 @@
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize, AtomicPtr};
+use core::mem::MaybeUninit;
+use core::ptr;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::cache_padded::CachePadded;
+use crate::drop_policy::{DropPolicy, PolicyCell};
+use crate::frozen::FrozenStack;
+use crate::intercept::{InterceptCell, Op};
+use crate::limit::NodeLimit;
+use crate::load::LenShard;
+#[cfg(not(feature = "no-stats"))]
+use crate::load::{LoadEstimate, ShardedLen};
+use crate::assert_ordering;
+use crate::ordering::Ordering;
+use crate::prefetch::{prefetch_read, spans_lines};
+use crate::sim_hook::yield_point;
+use crate::{next_id, try_alloc_box, AllocError, Full, HandleLimitReached, MAX_THREADS};
+
+/* Cached nodes freed between deadline checks in maintain() */
+const MAINTAIN_CHUNK: usize = 64;
+
+/// A stack node, opaque outside the crate. Like the handles, it is `Send`
+/// only when the payload is:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<stacc_core::stacc_lockfree_ebr::Node<std::rc::Rc<()>>>();
+/// ```
+pub struct Node<T> {
+    data: MaybeUninit<T>,
+    next: *const Node<T>,
+}
+
+/* Well, if you happen to own a Node, it means it is outside of stack.
+ * That means you can do whatever you want with it, as long as the payload
+ * itself may cross threads. Node is deliberately opaque outside the crate. */
+unsafe impl<T: Send> Send for Node<T> {}
+
+pub struct ThreadLocal {
+    current_epoch: AtomicUsize,
+    is_active: AtomicBool,
+    /* Owned by a live handle, see Shared::claim_slot() */
+    claimed: AtomicBool,
+}
+
+impl ThreadLocal {
+    const fn new() -> Self {
+        Self {
+            current_epoch: AtomicUsize::new(0),
+            is_active: AtomicBool::new(false),
+            claimed: AtomicBool::new(false),
+        }
+    }
+}
+
+pub struct Shared<T> {
+    top: AtomicPtr<Node<T>>,
+    /* See Local::id() */
+    id: u64,
+    threads: [CachePadded<ThreadLocal>; MAX_THREADS],
+    global_epoch: AtomicUsize,
+
+    /* (Optional) Purely for statistics, sharded by handle number so
+     * handles don't contend on it */
+    #[cfg(not(feature = "no-stats"))]
+    len: ShardedLen,
+    /* Average of `len`, sampled by add_len() and sub_len() */
+    #[cfg(not(feature = "no-stats"))]
+    load: LoadEstimate,
+    /* See with_max_nodes() */
+    limit: Option<NodeLimit>,
+    /* What happens to the elements left when the stack goes away */
+    drop_policy: PolicyCell<T>,
+    /* See set_interceptor() */
+    intercept: InterceptCell<T>,
+    /* TODO: When `Local` drops, but has still some things in limbo list, it goes here */
+    //global_garbage: Mutex<[Vec<*const T>; 3]>,
+}
+
+impl<T> Shared<T> {
+    /* The len counter is compiled out with `no-stats`, the node count is
+     * only kept with a limit, so by default the ops skip both */
+    #[inline]
+    fn add_len(&self, _shard: &mut LenShard, n: usize) {
+        if let Some(limit) = &self.limit {
+            limit.add(n);
+        }
+        #[cfg(not(feature = "no-stats"))]
+        if self.len.add(_shard, n) {
+            self.load.update(self.len.get());
+        }
+    }
+
+    #[inline]
+    fn sub_len(&self, _shard: &mut LenShard, n: usize) {
+        if let Some(limit) = &self.limit {
+            limit.sub(n);
+        }
+        #[cfg(not(feature = "no-stats"))]
+        if self.len.sub(_shard, n) {
+            self.load.update(self.len.get());
+        }
+    }
+
+    fn count_nodes(&mut self) -> usize {
+        let mut n = 0;
+        let mut top = *self.top.get_mut() as *const Node<T>;
+        while !top.is_null() {
+            /* SAFETY: we have exclusive access, so every node on the list is alive */
+            top = unsafe { (*top).next };
+            n += 1;
+        }
+        return n;
+    }
+
+    /// Drops and deallocates up to `budget` nodes from the top of the stack,
+    /// returns how many were freed
+    fn free_nodes(&mut self, budget: usize) -> usize {
+        let top = self.top.get_mut();
+        let mut freed = 0;
+        let mut leaked = 0;
+        while freed < budget && !top.is_null() {
+            /* SAFETY: the pointer is non-null, so it must come from Box::into_raw */
+            let boxed = unsafe { Box::from_raw(*top) };
+            /* SAFETY: boxed.data must be initialized, because its on stack */
+            let data = unsafe { ptr::read(boxed.data.as_ptr()) };
+            leaked += self.drop_policy.dispose(data) as usize;
+
+            *top = boxed.next as *mut _;
+            drop(boxed);
+            freed += 1;
+        }
+        self.drop_policy.report_leaks(core::any::type_name::<Self>(), leaked);
+        return freed;
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        self.free_nodes(usize::MAX);
+    }
+}
+
+impl<T> Shared<T> {
+    const fn new(id: u64) -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const THREAD_LOCAL: CachePadded<ThreadLocal> = CachePadded::new(ThreadLocal::new());
+        Self {
+            top: AtomicPtr::new(ptr::null_mut()),
+            id,
+            threads: [THREAD_LOCAL; MAX_THREADS],
+            global_epoch: AtomicUsize::new(0),
+            #[cfg(not(feature = "no-stats"))]
+            len: ShardedLen::new(),
+            #[cfg(not(feature = "no-stats"))]
+            load: LoadEstimate::new(),
+            limit: None,
+            drop_policy: PolicyCell::new(),
+            intercept: InterceptCell::new(),
+        }
+    }
+
+    /// Returns the previous observed epoch and the new one
+    fn start_shared_section(&self, thread_id: usize) -> (usize, usize) {
+        self.threads[thread_id].is_active.store(true, Ordering::SeqCst);
+
+        fence(Ordering::Acquire); // It's just nicer to have fresher data
+
+        let current_epoch = self.global_epoch.load(Ordering::Relaxed);
+        let old_epoch = self.threads[thread_id].current_epoch.swap(current_epoch, Ordering::Relaxed);
+        let have_all_threads_seen_epoch = self.threads
+            .iter()
+            .filter(|thread| thread.is_active.load(Ordering::Relaxed))
+            .map(|thread| thread.current_epoch.load(Ordering::Relaxed))
+            .all(|epoch| epoch == current_epoch);
+
+        if have_all_threads_seen_epoch {
+            return (old_epoch, current_epoch);
+        }
+
+        let next_epoch = match current_epoch.checked_add(1) {
+            Some(x) => x,
+            None => todo!(),
+        };
+
+        /* TODO: maybe if succeeded, clean global garbage */
+        /* Many threads can try to increment at the same time, so it is
+         * important to use compare_exchange in this place */
+        let _has_won_race = self.global_epoch.compare_exchange(
+            current_epoch,
+            next_epoch,
+            Ordering::Release,
+            Ordering::Relaxed
+        ).is_ok();
+
+        return (old_epoch, current_epoch);
+    }
+
+    fn end_shared_section(&self, thread_id: usize) {
+        self.threads[thread_id].is_active.store(false, Ordering::Release);
+    }
+
+    /// Takes a free thread slot for a new handle, slots of dropped handles
+    /// are reused, so the limit is on handles alive at the same time
+    fn claim_slot(&self) -> Option<usize> {
+        return self.threads.iter().position(|thread| {
+            /* Acquire pairs with release_slot(): the previous owner is done */
+            !thread.claimed.load(Ordering::Relaxed)
+                && thread.claimed.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        });
+    }
+
+    fn release_slot(&self, thread_id: usize) {
+        self.threads[thread_id].claimed.store(false, Ordering::Release);
+    }
+}
+
+/* Ends the shared section of push_unless() and keeps its node, if it
+ * already has one, when the predicate panics */
+struct PendingNode<'a, T> {
+    shared: &'a Shared<T>,
+    thread_id: usize,
+    garbage: &'a mut Vec<Box<Node<T>>>,
+    node: *mut Node<T>,
+}
+
+impl<T> Drop for PendingNode<'_, T> {
+    fn drop(&mut self) {
+        self.shared.end_shared_section(self.thread_id);
+        if !self.node.is_null() {
+            /* SAFETY: the node never left our hands */
+            self.garbage.push(unsafe { Box::from_raw(self.node) });
+        }
+    }
+}
+
+/// Per-thread handle to an epoch-protected stack.
+///
+/// Handles can be sent to other threads only when `T: Send`:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<stacc_core::stacc_lockfree_ebr::Local<std::rc::Rc<()>>>();
+/// ```
+pub struct Local<T> {
+    shared: Arc<Shared<T>>,
+    thread_id: usize,
+    /* Which part of the len counter this handle updates */
+    len_shard: LenShard,
+
+    limbo: [Vec<*const Node<T>>; 3],
+    garbage: Vec<Box<Node<T>>>,
+}
+
+impl<T> Local<T> {
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared::new(next_id()));
+        /* Nobody else can see the stack yet */
+        shared.threads[0].claimed.store(true, Ordering::Relaxed);
+        Self {
+            shared,
+            thread_id: 0,
+            len_shard: LenShard::new(0),
+            limbo: [Vec::new(), Vec::new(), Vec::new()],
+            garbage: Vec::new(),
+        }
+    }
+
+    /// Safety: `mark_use` must come in pair with `defer`
+    fn mark_use(&mut self) {
+        let (prev, next) = self.shared.start_shared_section(self.thread_id);
+        let diff = core::cmp::min(next - prev, self.limbo.len());
+
+        let iter = self.limbo[..diff]
+            .iter_mut()
+            .flat_map(|limbo| limbo.drain(..))
+            .map(|ptr| unsafe { Box::from_raw(ptr as *mut _) });
+        self.garbage.extend(iter);
+        self.limbo.rotate_left(diff);
+    }
+
+    /// Safety: you can't defer the same pointer more than once.
+    /// Must come after `mark_use`
+    unsafe fn defer(&mut self, ptr: *const Node<T>) {
+        self.shared.end_shared_section(self.thread_id);
+        let [.., last] = &mut self.limbo;
+        last.push(ptr);
+    }
+
+    fn get_node(&mut self, node: Node<T>) -> Box<Node<T>> {
+        let mut p = match self.garbage.pop() {
+            None => return Box::new(node),
+            Some(p) => p,
+        };
+
+        /* Node has no drop glue (data is MaybeUninit), so this runs no user
+         * code and can't panic halfway through */
+        *p = node;
+        return p;
+    }
+
+    fn try_get_node(&mut self, node: Node<T>) -> Result<Box<Node<T>>, Node<T>> {
+        let mut p = match self.garbage.pop() {
+            None => return try_alloc_box(node),
+            Some(p) => p,
+        };
+
+        *p = node;
+        return Ok(p);
+    }
+
+    pub fn push(&mut self, data: T) {
+        /* Reported before the node exists, so a panic leaves nothing behind */
+        self.shared.intercept.call(&data, Op::Push);
+        let node = self.get_node(Node {
+            next: ptr::null(),
+            data: MaybeUninit::new(data),
+        });
+        self.link(Box::into_raw(node));
+    }
+
+    /// Caps the stack at about `n` elements for
+    /// [`push_checked`](Self::push_checked), so a runaway producer can't
+    /// eat all memory. Racing pushes may overshoot by up to the number of
+    /// handles pushing at once. The other pushes ignore the cap, but still
+    /// count towards it. Panics if the stack already has other handles.
+    pub fn with_max_nodes(mut self, n: usize) -> Self {
+        let shared = Arc::get_mut(&mut self.shared).expect("with_max_nodes(): the stack already has other handles");
+        let limit = NodeLimit::new(n);
+        limit.add(shared.count_nodes());
+        shared.limit = Some(limit);
+        return self;
+    }
+
+    /// Like [`push`](Self::push), but hands the element back if the stack
+    /// is at its [`with_max_nodes`](Self::with_max_nodes) cap
+    pub fn push_checked(&mut self, data: T) -> Result<(), Full<T>> {
+        if let Some(limit) = &self.shared.limit {
+            if limit.is_reached() {
+                return Err(Full(data));
+            }
+        }
+        self.push(data);
+        return Ok(());
+    }
+
+    /// Like [`push`](Self::push), but returns the element instead of
+    /// aborting if a node can't be allocated
+    pub fn try_push(&mut self, data: T) -> Result<(), AllocError<T>> {
+        self.shared.intercept.call(&data, Op::Push);
+        let node = Node {
+            next: ptr::null(),
+            data: MaybeUninit::new(data),
+        };
+        match self.try_get_node(node) {
+            Ok(node) => self.link(Box::into_raw(node)),
+            /* SAFETY: data was just initialized above */
+            Err(node) => return Err(AllocError(unsafe { node.data.assume_init() })),
+        }
+        return Ok(());
+    }
+
+    /// Puts a fresh node on top of the stack
+    fn link(&mut self, node: *mut Node<T>) {
+        let mut top = self.shared.top.load(Ordering::Acquire);
+        /* SAFETY: the node is still private to us */
+        unsafe {
+            (*node).next = top;
+        }
+
+        yield_point("ebr::push::cas");
+        while let Err(newtop) =
+            self.shared
+                .top
+                .compare_exchange_weak(top, node, Ordering::Release, Ordering::Relaxed)
+        {
+            /* SAFETY: This pointer must be valid, because it comes from Box::into_raw above */
+            unsafe {
+                (*node).next = newtop;
+            }
+            top = newtop;
+        }
+
+        self.shared.add_len(&mut self.len_shard, 1);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.mark_use();
+        let mut top = self.shared.top.load(Ordering::Acquire);
+
+        let oldtop = loop {
+            if top.is_null() {
+                return None;
+            }
+
+            assert_ordering!(
+                self.shared.threads[self.thread_id].is_active.load(Ordering::SeqCst),
+                "dereferencing a node outside of a shared section"
+            );
+            /* SAFETY: because of EBR, `top` should still be valid */
+            let next = unsafe { (*top).next };
+            if spans_lines::<Node<T>>() {
+                prefetch_read(unsafe { (*top).data.as_ptr() });
+            }
+
+            yield_point("ebr::pop::cas");
+            let cas = self.shared.top.compare_exchange_weak(
+                top,
+                next as *mut _,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            );
+
+            match cas {
+                Ok(_) => {
+                    /* Most likely the next pop's top */
+                    prefetch_read(next);
+                    break top;
+                }
+                Err(newertop) => top = newertop,
+            }
+        };
+
+        self.shared.sub_len(&mut self.len_shard, 1);
+
+        /* SAFETY: only one thread can succeed at CAS, so we are the only
+         * ones reading oldtop.data */
+        let data = unsafe { ptr::read((*oldtop).data.as_ptr()) };
+        unsafe { self.defer(oldtop); }
+
+        /* Only once the shared section is over, a panic here must not
+         * leave the epoch stuck */
+        self.shared.intercept.call(&data, Op::Pop);
+        return Some(data);
+    }
+
+    /// Pushes `data` unless `pred` returns true for the current top element,
+    /// e.g. `push_unless(x, |top| *top == x)` skips consecutive duplicates.
+    /// If the top changes before the push lands, `pred` runs again on the
+    /// new top. Returns `data` back if it was rejected, an empty stack
+    /// always accepts. `T: Copy` for the same reason as in
+    /// [`peek_many`](Self::peek_many), `pred` gets a copy of the top.
+    #[must_use = "the item is handed back if it was rejected"]
+    pub fn push_unless(&mut self, data: T, mut pred: impl FnMut(&T) -> bool) -> Option<T>
+    where
+        T: Copy,
+    {
+        self.mark_use();
+        let mut top = self.shared.top.load(Ordering::Acquire);
+        let mut node: *mut Node<T> = ptr::null_mut();
+
+        loop {
+            if !top.is_null() {
+                /* SAFETY: because of EBR, `top` should still be valid */
+                let current = unsafe { *(*top).data.as_ptr() };
+                /* If `pred` panics, the node was never published */
+                let pending = PendingNode {
+                    shared: &self.shared,
+                    thread_id: self.thread_id,
+                    garbage: &mut self.garbage,
+                    node,
+                };
+                let rejected = pred(&current);
+                core::mem::forget(pending);
+                if rejected {
+                    self.shared.end_shared_section(self.thread_id);
+                    if !node.is_null() {
+                        /* SAFETY: the node never left our hands */
+                        self.garbage.push(unsafe { Box::from_raw(node) });
+                    }
+                    return Some(data);
+                }
+            }
+
+            if node.is_null() {
+                node = Box::into_raw(self.get_node(Node {
+                    next: ptr::null(),
+                    data: MaybeUninit::new(data),
+                }));
+            }
+            /* SAFETY: the new node is still private to us */
+            unsafe {
+                (*node).next = top;
+            }
+            let cas = self.shared.top.compare_exchange_weak(top, node, Ordering::AcqRel, Ordering::Acquire);
+            match cas {
+                Ok(_) => break,
+                Err(newertop) => top = newertop,
+            }
+        }
+
+        self.shared.end_shared_section(self.thread_id);
+        /* Already visible, but `data` is a copy nobody else can touch */
+        self.shared.intercept.call(&data, Op::Push);
+        self.shared.add_len(&mut self.len_shard, 1);
+        return None;
+    }
+
+    /// Coalescing push, e.g. for repeated "redraw" events: skips `data`
+    /// if it equals the current top. Returns whether it was pushed.
+    pub fn push_dedup(&mut self, data: T) -> bool
+    where
+        T: Copy + PartialEq,
+    {
+        self.push_unless(data, |top| *top == data).is_none()
+    }
+
+    /// Atomically replaces the top element with `data` and returns the old
+    /// one, on an empty stack `data` is just pushed. Unlike a pop followed
+    /// by a push, other handles never see the stack one element shorter.
+    pub fn swap_top(&mut self, data: T) -> Option<T> {
+        self.shared.intercept.call(&data, Op::Push);
+        let node = self.get_node(Node {
+            next: ptr::null(),
+            data: MaybeUninit::new(data),
+        });
+        let node = Box::into_raw(node);
+
+        self.mark_use();
+        let mut top = self.shared.top.load(Ordering::Acquire);
+
+        let oldtop = loop {
+            let next = if top.is_null() {
+                ptr::null()
+            } else {
+                /* SAFETY: because of EBR, `top` should still be valid */
+                unsafe { (*top).next }
+            };
+            /* SAFETY: the new node is still private to us */
+            unsafe {
+                (*node).next = next;
+            }
+
+            let cas = self.shared.top.compare_exchange_weak(top, node, Ordering::AcqRel, Ordering::Acquire);
+            match cas {
+                Ok(_) => break top,
+                Err(newertop) => top = newertop,
+            }
+        };
+
+        if oldtop.is_null() {
+            self.shared.end_shared_section(self.thread_id);
+            self.shared.add_len(&mut self.len_shard, 1);
+            return None;
+        }
+
+        /* SAFETY: only one thread can succeed at CAS, so we are the only
+         * ones reading oldtop.data */
+        let data = unsafe { ptr::read((*oldtop).data.as_ptr()) };
+        unsafe { self.defer(oldtop); }
+        /* See pop() */
+        self.shared.intercept.call(&data, Op::Pop);
+        return Some(data);
+    }
+
+    /// Copies up to `n` elements starting from the top, without removing them.
+    ///
+    /// The walk happens inside one epoch, so every node seen stays allocated,
+    /// but the result is not an atomic snapshot when other threads are busy.
+    /// `T: Copy` is required, because a concurrent pop may move the element
+    /// out (and start mutating or dropping it) while we are still reading it.
+    pub fn peek_many(&mut self, n: usize) -> Vec<T>
+    where
+        T: Copy,
+    {
+        let mut v = Vec::new();
+        self.mark_use();
+
+        let mut node = self.shared.top.load(Ordering::Acquire) as *const Node<T>;
+        while !node.is_null() && v.len() < n {
+            /* SAFETY: because of EBR, `node` is still valid */
+            let (data, next) = unsafe { (*(*node).data.as_ptr(), (*node).next) };
+            v.push(data);
+            node = next;
+        }
+
+        self.shared.end_shared_section(self.thread_id);
+        return v;
+    }
+
+    /// Detaches everything currently on the stack into a shared, immutable
+    /// snapshot, the stack itself continues empty. Pushes racing with this
+    /// land either in the snapshot or on the fresh stack, never both.
+    pub fn freeze(&mut self) -> FrozenStack<T> {
+        /* The nodes are retired in the current epoch, so enter it first */
+        self.mark_use();
+        let mut node = self.shared.top.swap(ptr::null_mut(), Ordering::AcqRel) as *const Node<T>;
+
+        let mut v = Vec::new();
+        let [.., last] = &mut self.limbo;
+        while !node.is_null() {
+            /* SAFETY: the chain is detached, so only we can read the data,
+             * others may still look at `next`, but it never changes */
+            let (data, next) = unsafe { (ptr::read((*node).data.as_ptr()), (*node).next) };
+            last.push(node);
+            v.push(data);
+            node = next;
+        }
+        self.shared.end_shared_section(self.thread_id);
+
+        self.shared.sub_len(&mut self.len_shard, v.len());
+        /* See pop(), a panic here drops what was taken with `v` */
+        for data in &v {
+            self.shared.intercept.call(data, Op::Pop);
+        }
+        return FrozenStack::from(v);
+    }
+
+    /// Releases excess capacity of this handle's bookkeeping vectors,
+    /// which otherwise stay at their peak size after a burst
+    pub fn shrink_to_fit(&mut self) {
+        for limbo in self.limbo.iter_mut() {
+            limbo.shrink_to_fit();
+        }
+        self.garbage.shrink_to_fit();
+    }
+
+    /// Housekeeping for idle time: tries to advance the epoch and reclaim
+    /// nodes from limbo, then frees cached nodes and shrinks the bookkeeping
+    /// vectors. `out_of_time` is checked between steps, there is no clock
+    /// in `no_std`, so pass e.g. `|| Instant::now() >= deadline`.
+    /// Returns true if everything got done in time.
+    pub fn maintain(&mut self, mut out_of_time: impl FnMut() -> bool) -> bool {
+        self.mark_use();
+        self.shared.end_shared_section(self.thread_id);
+
+        while !self.garbage.is_empty() {
+            if out_of_time() {
+                return false;
+            }
+            let keep = self.garbage.len().saturating_sub(MAINTAIN_CHUNK);
+            self.garbage.truncate(keep);
+        }
+
+        self.shrink_to_fit();
+        return true;
+    }
+
+    /// Process-unique id of the stack, the same for all its handles
+    pub fn id(&self) -> u64 {
+        self.shared.id
+    }
+
+    /// Same as [`approx_len`](Self::approx_len)
+    #[cfg(not(feature = "no-stats"))]
+    pub fn len(&self) -> usize {
+        self.approx_len()
+    }
+
+    /// Whether the stack looked empty at the time of the call. Reads the
+    /// top, not the counters, so it is there with `no-stats` too.
+    pub fn is_empty(&self) -> bool {
+        self.shared.top.load(Ordering::Acquire).is_null()
+    }
+
+    /// Cheap, racy length: the sum of relaxed per-handle counters, may lag
+    /// behind concurrent pushes and pops. Not there with the `no-stats` feature.
+    #[cfg(not(feature = "no-stats"))]
+    pub fn approx_len(&self) -> usize {
+        self.shared.len.get()
+    }
+
+    /// Moving average of the length, sampled every 8th push or pop of a
+    /// handle and covering roughly its last 128. Not there with the `no-stats` feature.
+    #[cfg(not(feature = "no-stats"))]
+    pub fn load_estimate(&self) -> f64 {
+        self.shared.load.get()
+    }
+
+    /// Exact length, computed by walking the list. Only possible when this
+    /// is the only handle to the stack, returns `None` otherwise.
+    pub fn len_exact(&mut self) -> Option<usize> {
+        let shared = Arc::get_mut(&mut self.shared)?;
+        return Some(shared.count_nodes());
+    }
+
+    /// What happens to the elements still on the stack when the last
+    /// handle goes away, or on [`dispose`](Self::dispose)
+    pub fn set_drop_policy(&self, policy: DropPolicy<T>) {
+        self.shared.drop_policy.set(policy);
+    }
+
+    /// Debugging aid: calls `f` with every element pushed to or popped
+    /// from the stack, through any handle, `None` removes it. Pushes are
+    /// reported before the element becomes visible, pops after it was
+    /// taken out. Only with the `intercept` feature.
+    #[cfg(feature = "intercept")]
+    pub fn set_interceptor(&self, f: Option<fn(&T, Op)>) {
+        self.shared.intercept.set(f);
+    }
+
+    /// Incremental teardown: frees at most `budget` elements from the top,
+    /// so dropping a huge stack can be spread over time instead of stalling
+    /// in `drop`. Returns the number of freed elements, less than `budget`
+    /// means the stack is now empty. Only possible when this is the only
+    /// handle to the stack, returns `None` otherwise.
+    pub fn dispose(&mut self, budget: usize) -> Option<usize> {
+        let shared = Arc::get_mut(&mut self.shared)?;
+        let freed = shared.free_nodes(budget);
+        shared.sub_len(&mut LenShard::new(0), freed);
+        return Some(freed);
+    }
+}
+
+unsafe impl<T: Send> Send for Local<T> {}
+
+impl<T> core::fmt::Debug for Local<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut d = f.debug_struct("Local");
+        d.field("id", &self.id()).field("thread_id", &self.thread_id);
+        #[cfg(not(feature = "no-stats"))]
+        d.field("len", &self.len());
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T> Local<T> {
+    /// Like `clone()`, but fails instead of panicking when all
+    /// [`MAX_THREADS`] handle slots are taken
+    pub fn try_clone(&self) -> Result<Self, HandleLimitReached> {
+        let thread_id = self.shared.claim_slot().ok_or(HandleLimitReached)?;
+        return Ok(Self {
+            shared: Arc::clone(&self.shared),
+            thread_id,
+            len_shard: LenShard::new(thread_id),
+            limbo: [Vec::new(), Vec::new(), Vec::new()],
+            garbage: Vec::new(),
+        });
+    }
+}
+
+impl<T> Clone for Local<T> {
+    /// Panics if all [`MAX_THREADS`] handle slots are taken, see
+    /// [`try_clone`](Local::try_clone)
+    fn clone(&self) -> Self {
+        match self.try_clone() {
+            Ok(handle) => handle,
+            Err(HandleLimitReached) => panic!(
+                "Local::clone(): all {} handle slots are taken, see the threads-* features",
+                MAX_THREADS
+            ),
+        }
+    }
+}
+
+impl<T> Drop for Local<T> {
+    fn drop(&mut self) {
+        self.mark_use();
+        /* TODO: don't leak pointers in limbo */
+        self.shared.end_shared_section(self.thread_id);
+        self.shared.release_slot(self.thread_id);
+    }
+}
+
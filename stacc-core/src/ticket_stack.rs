@@ -0,0 +1,120 @@
+/* Bounded stack of zero-sized values, e.g. tickets or permits.
+ *
+ * A ZST carries no data, so there is nothing to store: the stack is just
+ * the number of values it holds. A push forgets the value and bumps the
+ * count, a pop takes the count down and conjures a value back. That is
+ * sound because every conjured value stands for one that was given to us
+ * and forgotten, so a ZST with a private constructor can't be forged.
+ *
+ * There is no data, but there is still ordering: a push releases and a
+ * pop acquires, so whatever was done before giving a permit back is seen
+ * by whoever takes it next, as with the other stacks. */
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
+use core::sync::atomic::AtomicUsize;
+
+use crate::ordering::Ordering;
+
+/// Same API as the bounded `Stacc`, for zero-sized `T` only, with one
+/// atomic counter in place of the slots. Not being a ZST is a compile
+/// error:
+///
+/// ```compile_fail
+/// let _ = stacc_core::ticket_stack::TicketStack::<u8>::new(1);
+/// ```
+///
+/// ```
+/// use stacc_core::ticket_stack::TicketStack;
+///
+/// let permits = TicketStack::new(2);
+/// assert_eq!(permits.push(()), None);
+/// assert_eq!(permits.pop(), Some(()));
+/// assert_eq!(permits.pop(), None);
+/// ```
+pub struct TicketStack<T> {
+    len: AtomicUsize,
+    capacity: usize,
+    /* Owns the values it counts */
+    _values: PhantomData<T>,
+}
+
+/* SAFETY: values go in on one thread and may come out on another, which
+ * only needs them to be Send, they are never shared */
+unsafe impl<T: Send> Send for TicketStack<T> {}
+unsafe impl<T: Send> Sync for TicketStack<T> {}
+
+impl<T> TicketStack<T> {
+    const ZERO_SIZED: () = assert!(mem::size_of::<T>() == 0, "TicketStack only holds zero-sized types");
+
+    /// Empty stack for up to `capacity` values
+    pub const fn new(capacity: usize) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ZERO_SIZED;
+        Self {
+            len: AtomicUsize::new(0),
+            capacity,
+            _values: PhantomData,
+        }
+    }
+
+    /// Hands `x` back if the stack is full
+    #[must_use = "the value is handed back if the stack is full"]
+    pub fn push(&self, x: T) -> Option<T> {
+        let pushed = self.len.fetch_update(Ordering::Release, Ordering::Relaxed, |len| {
+            if len < self.capacity {
+                Some(len + 1)
+            } else {
+                None
+            }
+        });
+        if pushed.is_err() {
+            return Some(x);
+        }
+        mem::forget(x);
+        return None;
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let popped = self.len.fetch_update(Ordering::Acquire, Ordering::Relaxed, |len| len.checked_sub(1));
+        if popped.is_err() {
+            return None;
+        }
+        /* SAFETY: T is a ZST, so a dangling pointer is valid for reads, and
+         * the value stands for one that was pushed and forgotten */
+        return Some(unsafe { NonNull::<T>::dangling().as_ptr().read() });
+    }
+
+    /// Exact, there are no other counters to race with
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<T> Drop for TicketStack<T> {
+    fn drop(&mut self) {
+        /* The values may have a Drop impl, give each one its turn */
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> core::fmt::Debug for TicketStack<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TicketStack")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<T> crate::progress::LockFreePush for TicketStack<T> {}
+impl<T> crate::progress::LockFreePop for TicketStack<T> {}
@@ -0,0 +1,63 @@
+/* Weighted capacity: each item costs a caller-supplied weight (e.g. its
+ * serialized size) and pushes fail once the budget would be exceeded,
+ * on top of the usual limit on the number of items. */
+
+use core::sync::atomic::AtomicUsize;
+
+use crate::ordering::Ordering;
+
+/// How much an item costs, must give the same answer every time
+/// it is asked about the same item
+pub type Weigher<T> = fn(&T) -> usize;
+
+/// A shared weight budget
+pub struct WeightBudget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl WeightBudget {
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes `w` out of the budget, or returns false if it doesn't fit.
+    /// An item heavier than the whole limit never fits.
+    pub fn try_acquire(&self, w: usize) -> bool {
+        /* Budget is only an admission check, it doesn't publish anything */
+        return self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(w).filter(|&sum| sum <= self.limit)
+            })
+            .is_ok();
+    }
+
+    /// Takes `w` out of the budget unconditionally, may go over the limit
+    pub fn charge(&self, w: usize) {
+        self.used.fetch_add(w, Ordering::Relaxed);
+    }
+
+    /// Gives `w` back
+    pub fn release(&self, w: usize) {
+        let old = self.used.fetch_sub(w, Ordering::Relaxed);
+        debug_assert!(old >= w, "released more weight than was acquired");
+    }
+
+    /// Puts the whole budget back, needs exclusive access
+    pub fn reset(&mut self) {
+        *self.used.get_mut() = 0;
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Weight currently taken, racy
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{ErrorKind, Read, Write};
+
+use stacc::byte_pipe::byte_pipe;
+
+#[test]
+fn roundtrip() {
+    let (mut w, mut r) = byte_pipe();
+    let mut buf = [0; 16];
+    assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+
+    w.write_all(b"hello").unwrap();
+    assert_eq!(r.read(&mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"hello");
+
+    drop(w);
+    assert_eq!(r.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn full_and_wrapping() {
+    let (mut w, mut r) = byte_pipe();
+    let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+
+    /* Goes around the ring a few times, in uneven chunks */
+    let mut sent = 0;
+    let mut received = Vec::new();
+    while received.len() < data.len() {
+        match w.write(&data[sent..]) {
+            Ok(n) => sent += n,
+            Err(e) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+        }
+        let mut buf = [0; 100];
+        match r.read(&mut buf) {
+            Ok(n) => received.extend_from_slice(&buf[..n]),
+            Err(e) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+        }
+    }
+    assert_eq!(received, data);
+}
+
+#[test]
+fn broken_pipe() {
+    let (mut w, r) = byte_pipe();
+    drop(r);
+    assert_eq!(w.write(b"x").unwrap_err().kind(), ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,15 @@
+use std::mem;
+use stacc::cache_padded::*;
+
+#[test]
+fn alignment() {
+    let padded = [CachePadded::new(1u8), CachePadded::new(2u8)];
+
+    assert!(mem::align_of::<CachePadded<u8>>() >= 64);
+    assert_eq!(mem::size_of::<CachePadded<u8>>(), mem::align_of::<CachePadded<u8>>());
+
+    let a = &*padded[0] as *const u8 as usize;
+    let b = &*padded[1] as *const u8 as usize;
+    assert!(b - a >= 64);
+    assert_eq!(*padded[1], 2);
+}
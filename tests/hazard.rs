@@ -0,0 +1,194 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use stacc::hazard::*;
+use stacc::stacc_lockfree_hp::*;
+
+#[test]
+fn shared_domain() {
+    let domain = Arc::new(Domain::new());
+
+    let mut threads = Vec::new();
+    for _ in 0..4 {
+        let mut s = LockFreeStacc::with_domain(&domain);
+        threads.push(thread::spawn(move || {
+            let mut sum = 0u64;
+            for i in 0..10_000 {
+                s.push(i);
+                sum += s.pop().unwrap();
+            }
+            sum
+        }));
+    }
+    for t in threads {
+        assert_eq!(t.join().unwrap(), 10_000 * 9_999 / 2);
+    }
+
+    let stats = domain.stats();
+    /* The stacks died with their threads */
+    assert_eq!(stats.handles, 0);
+    assert!(stats.records >= 1 && stats.records <= 4);
+    assert!(stats.scans > 0);
+    domain.scan();
+    assert_eq!(domain.stats().pending, 0);
+}
+
+#[test]
+fn reclaimer() {
+    let domain = Arc::new(Domain::new());
+    let reclaimer = domain.spawn_reclaimer(Duration::from_millis(1));
+
+    let mut a = LockFreeStacc::with_domain(&domain);
+    let mut b = a.clone();
+    let t = thread::spawn(move || {
+        for i in 0..10_000 {
+            b.push(i);
+        }
+    });
+    let mut popped = 0;
+    while popped < 10_000 {
+        if a.pop().is_some() {
+            popped += 1;
+        } else {
+            thread::yield_now();
+        }
+    }
+    t.join().unwrap();
+
+    drop(reclaimer);
+    drop(a);
+    assert_eq!(domain.stats().pending, 0);
+    assert!(domain.stats().reclaimed > 0);
+}
+
+#[test]
+fn slots_are_reused() {
+    let domain = Arc::new(Domain::new());
+    let s = LockFreeStacc::<u32>::with_domain(&domain);
+    for _ in 0..1000 {
+        drop(s.clone());
+    }
+    let stats = domain.stats();
+    assert_eq!(stats.handles, 1);
+    assert_eq!(stats.records, 2);
+}
+
+#[test]
+fn retire_threshold() {
+    let domain = Arc::new(Domain::new());
+    let mut s = LockFreeStacc::with_domain(&domain);
+    let mut eager = LockFreeStacc::with_domain(&domain).with_retire_threshold(1);
+    for i in 0..10 {
+        s.push(i);
+        s.pop();
+    }
+    assert_eq!(domain.stats().scans, 0);
+
+    for i in 0..10 {
+        eager.push(i);
+        eager.pop();
+    }
+    assert_eq!(domain.stats().scans, 10);
+    assert_eq!(domain.stats().reclaimed, 10);
+}
+
+#[test]
+fn more_hazards() {
+    let domain = Arc::new(Domain::with_hazards(4));
+    assert_eq!(domain.hazards_per_handle(), 4);
+    let mut s = LockFreeStacc::with_domain(&domain);
+    let mut t = s.clone();
+    for i in 0..100 {
+        s.push(i);
+    }
+    assert_eq!(t.pop(), Some(99));
+    assert_eq!(t.peek_many(3), vec![98, 97, 96]);
+    drop(t);
+    assert_eq!(domain.stats().handles, 1);
+}
+
+#[test]
+#[should_panic]
+fn too_few_hazards() {
+    Domain::with_hazards(1);
+}
+
+#[test]
+fn register_during_scans() {
+    /* Records are pushed onto a lock-free list, scans walk it while new
+     * handles keep showing up */
+    let domain = Arc::new(Domain::new());
+    let s = LockFreeStacc::with_domain(&domain);
+    let done = std::sync::atomic::AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                domain.scan();
+                thread::yield_now();
+            }
+        });
+        let sums = stacc::threads::with_threads(16, &s, |i, mut h| {
+            let mut extra: Vec<_> = (0..4).map(|_| h.clone()).collect();
+            for e in &mut extra {
+                e.push(i);
+            }
+            (0..4).map(|_| h.pop().unwrap()).sum::<usize>()
+        });
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(sums.iter().sum::<usize>(), 4 * (16 * 15 / 2));
+    });
+    assert_eq!(domain.stats().handles, 1);
+}
+
+#[test]
+fn adaptive_threshold() {
+    let domain = Arc::new(Domain::new());
+    let mut s = LockFreeStacc::with_domain(&domain);
+    for i in 0..50 {
+        s.push(i);
+        s.pop();
+    }
+    /* One handle scans every 42 retired nodes */
+    assert_eq!(domain.stats().scans, 1);
+
+    /* 40 handles with 2 hazards each, the next scan is at 160 */
+    let _others: Vec<_> = (1..40).map(|_| s.clone()).collect();
+    for i in 0..100 {
+        s.push(i);
+        s.pop();
+    }
+    assert_eq!(domain.stats().scans, 1);
+    for i in 0..100 {
+        s.push(i);
+        s.pop();
+    }
+    assert_eq!(domain.stats().scans, 2);
+}
+
+#[test]
+fn guard() {
+    use stacc::drop_counter::DropTracker;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    let domain = Domain::new();
+    let tracker = DropTracker::new();
+    let cell = AtomicPtr::new(Box::into_raw(Box::new(tracker.wrap(1))));
+
+    let reader = domain.guard();
+    let mut writer = domain.guard();
+    let p = reader.protect(0, &cell);
+
+    let old = cell.swap(Box::into_raw(Box::new(tracker.wrap(2))), Ordering::AcqRel);
+    assert_eq!(old, p);
+    unsafe { writer.retire(old) };
+    /* Still protected by the reader */
+    assert_eq!(writer.scan(), 0);
+    assert_eq!(**unsafe { &*p }, 1);
+
+    reader.clear(0);
+    assert_eq!(writer.scan(), 1);
+
+    drop((reader, writer));
+    drop(unsafe { Box::from_raw(cell.into_inner()) });
+    tracker.assert_balanced();
+}
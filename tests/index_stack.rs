@@ -0,0 +1,42 @@
+#![cfg(target_has_atomic = "64")]
+
+use stacc::index_stack::*;
+
+#[test]
+fn single() {
+    let s = IndexStack::new(4);
+    assert_eq!(s.pop(), None);
+    s.push(2);
+    s.push(0);
+    assert_eq!(s.pop(), Some(0));
+    assert_eq!(s.pop(), Some(2));
+    assert!(s.is_empty());
+
+    let s = IndexStack::full(3);
+    assert_eq!(s.pop(), Some(0));
+    assert_eq!(s.pop(), Some(1));
+    assert_eq!(s.pop(), Some(2));
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn free_list() {
+    let s = IndexStack::full(64);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..10_000 {
+                    let a = s.pop().unwrap();
+                    let b = s.pop().unwrap();
+                    assert_ne!(a, b);
+                    s.push(a);
+                    s.push(b);
+                }
+            });
+        }
+    });
+
+    let mut all: Vec<u32> = std::iter::from_fn(|| s.pop()).collect();
+    all.sort();
+    assert_eq!(all, (0..64).collect::<Vec<_>>());
+}
@@ -0,0 +1,140 @@
+#![cfg(feature = "intercept")]
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Mutex;
+
+use stacc::drop_counter::{DropCounter, DropTracker};
+use stacc::intercept::Op;
+use stacc::spsc_queue::queue;
+use stacc::stacc_lockfree_ebr::Local;
+use stacc::stacc_lockfree_hp::LockFreeStacc;
+
+thread_local! {
+    /* Single-threaded tests, so every test gets its own set */
+    static LIVE: RefCell<HashSet<u64>> = RefCell::new(HashSet::new());
+}
+
+/* Task ids must be unique while they are inside the structure */
+fn unique_ids(id: &u64, op: Op) {
+    LIVE.with(|live| match op {
+        Op::Push => assert!(live.borrow_mut().insert(*id), "task {} pushed twice", id),
+        Op::Pop => assert!(live.borrow_mut().remove(id), "task {} popped but never pushed", id),
+    });
+}
+
+static OPS: Mutex<Vec<(u32, Op)>> = Mutex::new(Vec::new());
+
+fn record(x: &u32, op: Op) {
+    OPS.lock().unwrap().push((*x, op));
+}
+
+#[test]
+fn hp_unique_ids() {
+    let mut s = LockFreeStacc::new();
+    s.set_interceptor(Some(unique_ids));
+    s.push(1);
+    s.push(2);
+    assert_eq!(s.pop(), Some(2));
+    s.push(2);
+    assert_eq!(s.take_all(), vec![2, 1]);
+}
+
+#[test]
+#[should_panic(expected = "pushed twice")]
+fn hp_duplicate_id() {
+    let mut s = LockFreeStacc::new();
+    s.set_interceptor(Some(unique_ids));
+    s.push(7);
+    s.push(7);
+}
+
+#[test]
+fn ebr_and_queue() {
+    let mut s = Local::new();
+    s.set_interceptor(Some(record));
+    s.push(1);
+    assert_eq!(s.swap_top(2), Some(1));
+    s.set_interceptor(None);
+    assert_eq!(s.pop(), Some(2));
+
+    let (mut tx, mut rx) = queue();
+    tx.set_interceptor(Some(record));
+    assert!(tx.push_or_drop(3));
+    assert_eq!(rx.pop_with(|x| *x), Some(3));
+
+    let ops = OPS.lock().unwrap();
+    assert_eq!(*ops, [(1, Op::Push), (2, Op::Push), (1, Op::Pop), (3, Op::Push), (3, Op::Pop)]);
+}
+
+fn refuse_pops(_: &DropCounter<u32>, op: Op) {
+    if let Op::Pop = op {
+        panic!("pop refused");
+    }
+}
+
+/* A panicking interceptor drops the popped element once, and leaves the
+ * structures usable */
+#[test]
+fn panicking_interceptor() {
+    let tracker = DropTracker::new();
+
+    let mut hp = LockFreeStacc::new();
+    let mut ebr = Local::new();
+    let (mut tx, mut rx) = queue();
+    for i in 0..3 {
+        hp.push(tracker.wrap(i));
+        ebr.push(tracker.wrap(i));
+        assert!(tx.push(tracker.wrap(i)).is_none());
+    }
+    hp.set_interceptor(Some(refuse_pops));
+    ebr.set_interceptor(Some(refuse_pops));
+    rx.set_interceptor(Some(refuse_pops));
+
+    assert!(catch_unwind(AssertUnwindSafe(|| hp.pop())).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| ebr.pop())).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| rx.pop())).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| hp.take_all())).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| ebr.freeze())).is_err());
+    tracker.assert_alive(2);
+
+    hp.set_interceptor(None);
+    ebr.set_interceptor(None);
+    rx.set_interceptor(None);
+    hp.push(tracker.wrap(3));
+    assert_eq!(hp.pop().map(|x| *x), Some(3));
+    ebr.push(tracker.wrap(3));
+    assert_eq!(ebr.pop().map(|x| *x), Some(3));
+    assert_eq!(rx.pop().map(|x| *x), Some(1));
+
+    drop((hp, ebr, tx, rx));
+    tracker.assert_balanced();
+}
+
+#[test]
+fn queue_release() {
+    let (mut tx, mut rx) = queue();
+    tx.set_interceptor(Some(unique_ids));
+    for id in 0..4 {
+        assert!(tx.push(id).is_none());
+    }
+    assert_eq!(rx.peek().0, [0, 1, 2, 3]);
+    rx.release(3);
+    assert_eq!(rx.pop(), Some(3));
+    LIVE.with(|live| assert!(live.borrow().is_empty()));
+
+    /* A refused pop is still released, and dropped once */
+    let tracker = DropTracker::new();
+    let (mut tx, mut rx) = queue();
+    for i in 0..3 {
+        assert!(tx.push(tracker.wrap(i)).is_none());
+    }
+    rx.set_interceptor(Some(refuse_pops));
+    assert!(catch_unwind(AssertUnwindSafe(|| rx.release(2))).is_err());
+    tracker.assert_alive(2);
+    rx.set_interceptor(None);
+    assert_eq!(rx.pop().map(|x| *x), Some(1));
+    drop((tx, rx));
+    tracker.assert_balanced();
+}
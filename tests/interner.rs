@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use stacc::interner::*;
+
+#[test]
+fn dedup() {
+    let interner = Interner::with_capacity(8);
+    let a = interner.intern(vec![1u8; 1024]);
+    let b = interner.intern(vec![1u8; 1024]);
+    let c = interner.intern(vec![2u8; 1024]);
+    assert!(Arc::ptr_eq(&a, &b));
+    assert!(!Arc::ptr_eq(&a, &c));
+    assert_eq!(Arc::strong_count(&a), 3);
+}
+
+#[test]
+fn full() {
+    let interner = Interner::with_capacity(1);
+    let values: Vec<_> = (0..interner.capacity() as u32).map(|i| interner.intern(i)).collect();
+    /* No room left, still handed out, but not shared */
+    let a = interner.intern(100);
+    let b = interner.intern(100);
+    assert!(!Arc::ptr_eq(&a, &b));
+    assert!(Arc::ptr_eq(&values[0], &interner.intern(0)));
+}
+
+#[test]
+fn purge() {
+    let interner = Interner::with_capacity(4);
+    let kept = interner.intern(1);
+    drop(interner.intern(2));
+    drop(interner.intern(3));
+    assert_eq!(interner.purge(), 2);
+    assert_eq!(interner.purge(), 0);
+    assert!(Arc::ptr_eq(&kept, &interner.intern(1)));
+
+    /* Removed entries are reused */
+    for i in 10..20 {
+        drop(interner.intern(i));
+        assert_eq!(interner.purge(), 1);
+    }
+}
+
+#[test]
+fn with_threads() {
+    let interner = Arc::new(Interner::with_capacity(64));
+    let arcs = stacc::threads::with_threads(4, &interner, |i, interner| {
+        for j in 0..200 {
+            drop(interner.intern(j % 32));
+            if j % 50 == i {
+                interner.purge();
+            }
+        }
+        interner.intern(7)
+    });
+    assert!(arcs.iter().all(|a| **a == 7));
+    drop(arcs);
+    interner.purge();
+    assert_eq!(interner.purge(), 0);
+    assert!(Arc::ptr_eq(&interner.intern(5), &interner.intern(5)));
+}
+
+/* Entries get purged and refilled with other values while lookups walk
+ * over them */
+#[test]
+fn intern_purge_stress() {
+    let interner = Arc::new(Interner::with_capacity(4));
+    let arcs = stacc::threads::with_threads(4, &interner, |i, interner| {
+        for j in 0..20_000u32 {
+            let x = (j + i as u32) % 16;
+            assert_eq!(*interner.intern(x), x);
+            if i == 0 {
+                interner.purge();
+            }
+        }
+        interner.intern(3)
+    });
+    assert!(arcs.iter().all(|a| **a == 3));
+}
@@ -0,0 +1,59 @@
+use stacc::irq_spsc::*;
+
+#[test]
+fn single() {
+    let mut q = IrqSafeSpsc::<u32, 4>::new();
+    let (mut tx, mut rx) = q.split();
+
+    /* Wrap around a few times */
+    for round in 0..5 {
+        for i in 0..3 {
+            assert_eq!(tx.push(round * 3 + i), None);
+        }
+        assert!(tx.is_full());
+        assert_eq!(tx.push(100), Some(100));
+        assert_eq!(rx.len(), 3);
+
+        for i in 0..3 {
+            assert_eq!(rx.pop(), Some(round * 3 + i));
+        }
+        assert!(rx.is_empty());
+        assert_eq!(rx.pop(), None);
+    }
+}
+
+#[test]
+fn single_core_drop() {
+    use std::sync::Arc;
+
+    let item = Arc::new(());
+    /* SAFETY: both endpoints stay on this thread */
+    let mut q = unsafe { IrqSafeSpsc::<Arc<()>, 8>::new_single_core() };
+    assert_eq!(q.capacity(), 7);
+
+    let (mut tx, mut rx) = q.split();
+    for _ in 0..5 {
+        assert!(tx.push(Arc::clone(&item)).is_none());
+    }
+    rx.pop();
+
+    drop(q);
+    assert_eq!(Arc::strong_count(&item), 1);
+}
+
+#[test]
+fn drops() {
+    use stacc::drop_counter::DropTracker;
+
+    let tracker = DropTracker::new();
+    let mut q = IrqSafeSpsc::<_, 8>::new();
+    let (mut tx, mut rx) = q.split();
+    for i in 0..8 {
+        tx.push_or_drop(tracker.wrap(i));
+    }
+    tracker.assert_alive(7);
+    drop(rx.pop());
+
+    drop(q);
+    tracker.assert_balanced();
+}
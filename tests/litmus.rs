@@ -0,0 +1,92 @@
+/* Store-buffering litmus tests for the ordering shapes the stacks rely on,
+ * see the notes in stacc-core/src/ordering.rs. They can only fail on weak
+ * hardware (or with a broken compiler), so they are most useful on aarch64. */
+
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const ROUNDS: usize = 20_000;
+
+#[derive(Default)]
+struct Round {
+    /* Stands in for a hazard slot and for the stack top */
+    hazard: AtomicUsize,
+    top: AtomicUsize,
+    /* Both threads wait here at the start of every round */
+    ready: AtomicUsize,
+    done: AtomicUsize,
+}
+
+fn wait(counter: &AtomicUsize, target: usize) {
+    counter.fetch_add(1, Ordering::AcqRel);
+    while counter.load(Ordering::Acquire) < target {
+        thread::yield_now();
+    }
+}
+
+/// Popper: publishes a hazard, re-reads top (SeqCst store + SeqCst load).
+/// Reclaimer: unlinks with an AcqRel RMW, SeqCst fence, relaxed hazard read.
+/// At least one of them has to see the other.
+#[test]
+fn hazard_protect_vs_scan() {
+    let r = Arc::new(Round::default());
+
+    let rc = Arc::clone(&r);
+    let reclaimer = thread::spawn(move || {
+        let mut missed = Vec::new();
+        for round in 1..=ROUNDS {
+            wait(&rc.ready, 2 * round);
+            rc.top.fetch_add(1, Ordering::AcqRel);
+            fence(Ordering::SeqCst);
+            missed.push(rc.hazard.load(Ordering::Relaxed) < round);
+            wait(&rc.done, 2 * round);
+        }
+        missed
+    });
+
+    let mut stale = Vec::new();
+    for round in 1..=ROUNDS {
+        wait(&r.ready, 2 * round);
+        r.hazard.store(round, Ordering::SeqCst);
+        stale.push(r.top.load(Ordering::SeqCst) < round);
+        wait(&r.done, 2 * round);
+    }
+
+    let missed = reclaimer.join().unwrap();
+    for (round, (m, s)) in missed.iter().zip(stale.iter()).enumerate() {
+        assert!(!(*m && *s), "both sides missed each other in round {}", round + 1);
+    }
+}
+
+/// Pusher: SeqCst CAS on top, SeqCst load of the waiter counter.
+/// Waiter: SeqCst increment of the counter, SeqCst re-load of top.
+#[test]
+fn push_vs_register_waker() {
+    let r = Arc::new(Round::default());
+
+    let rc = Arc::clone(&r);
+    let pusher = thread::spawn(move || {
+        let mut missed = Vec::new();
+        for round in 1..=ROUNDS {
+            wait(&rc.ready, 2 * round);
+            let _ = rc.top.compare_exchange(round - 1, round, Ordering::SeqCst, Ordering::Relaxed);
+            missed.push(rc.hazard.load(Ordering::SeqCst) < round);
+            wait(&rc.done, 2 * round);
+        }
+        missed
+    });
+
+    let mut stale = Vec::new();
+    for round in 1..=ROUNDS {
+        wait(&r.ready, 2 * round);
+        r.hazard.fetch_add(1, Ordering::SeqCst);
+        stale.push(r.top.load(Ordering::SeqCst) < round);
+        wait(&r.done, 2 * round);
+    }
+
+    let missed = pusher.join().unwrap();
+    for (round, (m, s)) in missed.iter().zip(stale.iter()).enumerate() {
+        assert!(!(*m && *s), "both sides missed each other in round {}", round + 1);
+    }
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "metrics")]
+
+use stacc::metrics::Metrics;
+use stacc::stacc::Stacc;
+
+#[test]
+fn render() {
+    let a = Stacc::new(2);
+    let b = Stacc::<u32>::new(2);
+    assert_eq!(a.push(1), None);
+    assert_eq!(a.pop(), Some(1));
+    assert_eq!(a.pop(), None);
+
+    let mut out = String::new();
+    a.render_metrics(&mut out, &[("name", "a")]);
+    b.render_metrics(&mut out, &[("name", "say \"b\"")]);
+
+    let a_labels = format!("name=\"a\",id=\"{}\"", a.id());
+    assert!(out.contains(&format!("stacc_len{{{}}} 0\n", a_labels)));
+    assert!(out.contains(&format!("stacc_capacity{{{}}} 4\n", a_labels)));
+    assert!(out.contains(&format!("stacc_pop_misses_total{{{}}} 1\n", a_labels)));
+    assert!(out.contains(&format!("stacc_swaps_total{{{}}} 1\n", a_labels)));
+    assert!(out.contains(&format!("stacc_len{{name=\"say \\\"b\\\"\",id=\"{}\"}} 0\n", b.id())));
+    assert_eq!(out.matches("# TYPE stacc_len gauge\n").count(), 1);
+}
@@ -0,0 +1,63 @@
+use stacc::pipeline::*;
+
+#[test]
+fn stages() {
+    let mut p = Pipeline::new()
+        .stage(|x: u64| x * 2)
+        .stage(|x| x + 1)
+        .stage(|x| x.to_string());
+
+    /* More than the queues can hold, so backpressure kicks in
+     * and we have to keep draining the output while sending */
+    let mut out = Vec::new();
+    for i in 0..10_000 {
+        while let Some(back) = p.push(i) {
+            assert_eq!(back, i);
+            out.extend(p.pop());
+        }
+    }
+
+    out.extend(p.finish());
+    assert_eq!(out.len(), 10_000);
+    for (i, s) in out.iter().enumerate() {
+        assert_eq!(*s, (i * 2 + 1).to_string());
+    }
+}
+
+#[test]
+fn passthrough() {
+    let mut p = Pipeline::new();
+    assert_eq!(p.push(1), None);
+    assert_eq!(p.pop(), Some(1));
+    assert_eq!(p.finish(), Vec::<i32>::new());
+}
+
+#[test]
+#[should_panic(expected = "stage failed")]
+fn stage_panic() {
+    let mut p = Pipeline::new().stage(|x: u32| {
+        if x == 3 {
+            panic!("stage failed");
+        }
+        x
+    });
+
+    /* Once the stage is gone, the rest is handed back */
+    let refused = (0..10).filter_map(|i| p.send(i).err()).count();
+    assert!(refused <= 6);
+    p.finish();
+}
+
+#[test]
+fn send_to_dead_stage() {
+    let mut p = Pipeline::new().stage(|x: u32| -> u32 { panic!("stage {} failed", x) });
+    assert_eq!(p.send(0), Ok(()));
+    /* Sooner or later the stage is gone, and the item comes back */
+    let mut i = 1;
+    while let Ok(()) = p.send(i) {
+        i += 1;
+    }
+    assert_eq!(p.send(100), Err(100));
+    let finished = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| p.finish()));
+    assert!(finished.is_err());
+}
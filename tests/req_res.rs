@@ -0,0 +1,88 @@
+use std::thread;
+
+use stacc::req_res::*;
+
+#[test]
+fn out_of_order() {
+    let (mut client, mut server) = req_res::<u32, u32>();
+    let a = client.request(1).unwrap();
+    let b = client.request(2).unwrap();
+    assert_ne!(a, b);
+    assert_eq!(client.in_flight(), 2);
+
+    let (first, x) = server.next_request().unwrap();
+    let (second, y) = server.next_request().unwrap();
+    assert_eq!((first, x), (a, 1));
+    assert_eq!(server.next_request(), None);
+    server.respond(second, y * 10).unwrap();
+    server.respond(first, x * 10).unwrap();
+
+    assert_eq!(client.response(), Some((b, 20)));
+    assert_eq!(client.response(), Some((a, 10)));
+    assert_eq!(client.response(), None);
+    assert_eq!(client.in_flight(), 0);
+}
+
+#[test]
+fn full() {
+    let (mut client, mut server) = req_res::<usize, usize>();
+    let mut sent = 0;
+    while client.request(sent).is_ok() {
+        sent += 1;
+    }
+    assert_eq!(client.request(sent), Err(sent));
+
+    /* Responses fill up too, serve() leaves the rest queued */
+    assert_eq!(server.serve(|x| x), sent);
+    client.request(0).unwrap();
+    assert_eq!(server.serve(|x| x), 0);
+    let (id, _) = server.next_request().unwrap();
+    assert_eq!(server.respond(id, 0), Err(0));
+
+    assert!(client.response().is_some());
+    assert_eq!(client.in_flight(), sent);
+}
+
+#[test]
+fn liveness() {
+    let (mut client, server) = req_res::<u32, u32>();
+    assert!(client.other_side_alive() && server.other_side_alive());
+    client.request(1).unwrap();
+    drop(client);
+
+    let mut server = server;
+    assert!(!server.other_side_alive());
+    assert_eq!(server.next_request().map(|(_, x)| x), Some(1));
+
+    let (client, server) = req_res::<u32, u32>();
+    drop(server);
+    assert!(!client.other_side_alive());
+}
+
+#[test]
+fn threads() {
+    let (mut client, mut server) = req_res::<u64, u64>();
+    let t = thread::spawn(move || {
+        let mut served = 0;
+        while server.other_side_alive() {
+            served += server.serve(|x| x * 2);
+            thread::yield_now();
+        }
+        served
+    });
+
+    let mut sum = 0;
+    let mut next = 0;
+    while next < 1000 || client.in_flight() != 0 {
+        if next < 1000 && client.request(next).is_ok() {
+            next += 1;
+        }
+        match client.response() {
+            Some((_, x)) => sum += x,
+            None => thread::yield_now(),
+        }
+    }
+    drop(client);
+    assert_eq!(t.join().unwrap(), 1000);
+    assert_eq!(sum, 999 * 1000);
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use stacc::select::*;
+use stacc::spsc_queue::queue;
+use stacc::stacc_lockfree_hp::HpStack;
+
+#[test]
+fn timeout() {
+    let select = Select::new();
+    let (_tx, rx) = queue::<u32>();
+    let stack = HpStack::<u32>::new();
+
+    assert_eq!(select.ready(&[&rx, &stack]), None);
+    assert_eq!(select.wait_timeout(&[&rx, &stack], Duration::from_millis(10)), None);
+}
+
+#[test]
+fn queue_or_stack() {
+    const N: u32 = 200;
+
+    let select = Arc::new(Select::new());
+    let stack = HpStack::new();
+    let (mut tx, mut commands) = queue::<u32>();
+
+    let producers = [
+        {
+            let (select, mut work) = (Arc::clone(&select), stack.handle());
+            thread::spawn(move || {
+                for i in 0..N {
+                    work.push(i);
+                    select.notify();
+                }
+            })
+        },
+        {
+            let select = Arc::clone(&select);
+            thread::spawn(move || {
+                for i in 0..N {
+                    while tx.push(i).is_some() {
+                        thread::yield_now();
+                    }
+                    select.notify();
+                }
+            })
+        },
+    ];
+
+    let mut work = stack.handle();
+    let (mut from_queue, mut from_stack) = (0, 0);
+    while from_queue + from_stack < 2 * N {
+        match select.wait(&[&commands, &work]) {
+            0 => from_queue += commands.pop().is_some() as u32,
+            1 => from_stack += work.pop().is_some() as u32,
+            _ => unreachable!(),
+        }
+    }
+
+    for p in producers {
+        p.join().unwrap();
+    }
+    assert_eq!((from_queue, from_stack), (N, N));
+}
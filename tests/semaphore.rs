@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use stacc::cancel::*;
+use stacc::semaphore::*;
+use stacc::stacc_lockfree_hp::LockFreeStacc;
+
+#[test]
+fn single() {
+    let sem = Semaphore::new(2);
+    let a = sem.acquire();
+    let b = sem.try_acquire().unwrap();
+    assert!(sem.try_acquire().is_none());
+    assert!(sem.acquire_timeout(Duration::from_millis(10)).is_none());
+    drop(a);
+    assert_eq!(sem.available_permits(), 1);
+    b.forget();
+    drop(sem.acquire());
+    assert_eq!(sem.available_permits(), 1);
+    sem.release(1);
+    assert_eq!(sem.available_permits(), 2);
+}
+
+#[test]
+fn cancel() {
+    let sem = Semaphore::new(0);
+    let token = CancellationToken::new();
+    thread::scope(|scope| {
+        let waiter = scope.spawn(|| sem.acquire_cancellable(&token).map(Permit::forget));
+        thread::sleep(Duration::from_millis(10));
+        token.cancel();
+        assert_eq!(waiter.join().unwrap(), Err(Cancelled));
+    });
+    assert_eq!(sem.acquire_cancellable(&token).map(Permit::forget), Err(Cancelled));
+}
+
+#[test]
+fn bounded_stack() {
+    /* At most 3 items in flight on an unbounded stack */
+    let sem = Semaphore::new(3);
+    let in_flight = AtomicUsize::new(0);
+    let s = LockFreeStacc::new();
+    stacc::threads::with_threads(4, &s, |i, mut h| {
+        for j in 0..50 {
+            if (i + j) % 2 == 0 {
+                /* Blocking here could wait for pops that never come */
+                match sem.try_acquire() {
+                    Some(permit) => permit.forget(),
+                    None => continue,
+                }
+                assert!(in_flight.fetch_add(1, Ordering::SeqCst) < 3);
+                h.push(j);
+            } else if h.pop().is_some() {
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                sem.release(1);
+            } else {
+                thread::yield_now();
+            }
+        }
+    });
+}
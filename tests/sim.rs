@@ -0,0 +1,64 @@
+#![cfg(feature = "sim")]
+
+use std::sync::Mutex;
+use stacc::sim::*;
+use stacc::stacc_lockfree_hp::LockFreeStacc;
+
+/* Two handles push two elements each and pop two, returns the trace and
+ * everything that was popped */
+fn push_pop(schedule: Schedule) -> (Vec<Step>, Vec<u32>) {
+    let s = LockFreeStacc::new();
+    let popped = Mutex::new(Vec::new());
+    let script = |base: u32| {
+        let mut h = s.clone();
+        let popped = &popped;
+        move || {
+            h.push(base);
+            h.push(base + 1);
+            let a = h.pop();
+            let b = h.pop();
+            popped.lock().unwrap().extend(a.into_iter().chain(b));
+        }
+    };
+
+    let trace = Sim::new().handle(script(0)).handle(script(10)).run(schedule);
+    let mut popped = popped.into_inner().unwrap();
+    popped.sort_unstable();
+    (trace, popped)
+}
+
+#[test]
+fn deterministic() {
+    for seed in 1..20 {
+        let (trace, popped) = push_pop(Schedule::Random(seed));
+        assert_eq!(popped, vec![0, 1, 10, 11]);
+        assert_eq!(push_pop(Schedule::replay(&trace)).0, trace);
+        assert_eq!(push_pop(Schedule::Random(seed)).0, trace);
+    }
+}
+
+#[test]
+fn interleaved_pop() {
+    /* Handle 0 protects the top, then handle 1 pushes on top of it before
+     * the re-check, so handle 0 has to protect the new top */
+    let s = LockFreeStacc::new();
+    let mut a = s.clone();
+    let mut b = s.clone();
+    a.push(1);
+    let trace = Sim::new()
+        .handle(move || assert_eq!(a.pop(), Some(2)))
+        .handle(move || b.push(2))
+        .run(Schedule::Fixed(vec![0, 1, 1, 0]));
+
+    let points: Vec<_> = trace.iter().map(|step| (step.handle, step.point)).collect();
+    assert_eq!(
+        &points[..4],
+        &[(0, "hp::pop::protected"), (1, "hp::push::cas"), (1, "done"), (0, "hp::pop::protected")]
+    );
+}
+
+#[test]
+#[should_panic(expected = "boom")]
+fn script_panics() {
+    Sim::new().handle(|| panic!("boom")).handle(|| ()).run(Schedule::Random(1));
+}
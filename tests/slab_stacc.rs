@@ -0,0 +1,69 @@
+#![cfg(target_has_atomic = "64")]
+
+use stacc::slab_stacc::*;
+
+#[test]
+fn single() {
+    let slab = SlabStacc::new(3);
+    let a = slab.insert(1).unwrap();
+    let mut b = slab.insert(2).unwrap();
+    assert_ne!(a.index(), b.index());
+
+    *slab.get_mut(&mut b) += 10;
+    assert_eq!(slab.get(&b), &12);
+    assert_eq!(slab.remove(a), 1);
+
+    let c = slab.insert(3).unwrap();
+    let d = slab.insert(4).unwrap();
+    assert!(slab.is_full());
+    assert_eq!(slab.insert(5).unwrap_err().0, 5);
+    assert_eq!(slab.remove(c), 3);
+    assert_eq!(slab.remove(d), 4);
+    assert_eq!(slab.remove(b), 12);
+}
+
+#[test]
+#[should_panic(expected = "key of another SlabStacc")]
+fn foreign_key() {
+    let a = SlabStacc::new(1);
+    let b = SlabStacc::<u32>::new(1);
+    let key = a.insert(1).unwrap();
+    b.remove(key);
+}
+
+#[test]
+fn drops() {
+    let tracker = stacc::drop_counter::DropTracker::new();
+    let slab = SlabStacc::new(8);
+    let mut keys = Vec::new();
+    for i in 0..8 {
+        keys.push(slab.insert(tracker.wrap(i)).unwrap());
+    }
+    for key in keys.drain(..4) {
+        drop(slab.remove(key));
+    }
+    /* The rest goes with the slab */
+    drop(slab);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn threads() {
+    let slab = SlabStacc::new(16);
+    std::thread::scope(|scope| {
+        for t in 0..4u32 {
+            let slab = &slab;
+            scope.spawn(move || {
+                for i in 0..5_000 {
+                    let x = t * 100_000 + i;
+                    let key = slab.insert(x).unwrap();
+                    let other = slab.insert(x + 1).unwrap();
+                    assert_eq!(*slab.get(&key), x);
+                    assert_eq!(slab.remove(other), x + 1);
+                    assert_eq!(slab.remove(key), x);
+                }
+            });
+        }
+    });
+    assert!(!slab.is_full());
+}
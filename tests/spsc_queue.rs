@@ -0,0 +1,435 @@
+use stacc::spsc_queue::*;
+
+#[test]
+fn single() {
+    let (mut tx, mut rx) = queue();
+    assert!(tx.is_empty() && rx.is_empty());
+
+    for i in 0..4 {
+        assert_eq!(tx.push(i), None);
+    }
+    assert!(!tx.is_empty() && !rx.is_empty());
+    for i in 0..4 {
+        assert_eq!(rx.pop(), Some(i));
+    }
+    assert_eq!(rx.pop(), None);
+    assert!(tx.is_empty() && rx.is_empty());
+}
+
+#[test]
+fn len_never_exceeds_capacity() {
+    let (mut tx, mut rx) = queue();
+    let cap = tx.capacity();
+    assert_eq!(tx.free_space(), cap);
+
+    /* Go around the ring a few times */
+    for round in 0..3 {
+        let mut pushed = 0;
+        while tx.push(round).is_none() {
+            pushed += 1;
+            assert!(tx.len() <= cap);
+            assert!(rx.len() <= cap);
+        }
+        assert_eq!(pushed, cap);
+        assert!(tx.is_full() && rx.is_full());
+        assert_eq!(tx.free_space(), 0);
+        assert_eq!(rx.free_space(), 0);
+        assert_eq!(rx.len(), cap);
+
+        rx.pop();
+        assert!(!tx.is_full() && !rx.is_full());
+        assert_eq!(tx.free_space(), 1);
+        assert_eq!(rx.free_space(), 1);
+
+        while rx.pop().is_some() {
+            assert!(tx.len() <= cap);
+        }
+        assert_eq!(tx.len(), 0);
+        assert_eq!(rx.len(), 0);
+    }
+}
+
+#[test]
+fn drop_leftovers() {
+    use std::sync::Arc;
+
+    let item = Arc::new(());
+    let (mut tx, mut rx) = queue();
+    for _ in 0..10 {
+        assert!(tx.push(Arc::clone(&item)).is_none());
+    }
+    for _ in 0..3 {
+        rx.pop();
+    }
+    drop(tx);
+    drop(rx);
+    assert_eq!(Arc::strong_count(&item), 1);
+}
+
+#[test]
+fn handoff_between_threads() {
+    use std::thread;
+
+    let (tx, rx) = queue();
+    let tx = tx.handoff();
+    let rx = rx.handoff();
+
+    let producer = thread::spawn(move || {
+        let mut tx = tx.take();
+        for i in 0..100_000u64 {
+            let mut x = i;
+            while let Some(back) = tx.push(x) {
+                x = back;
+            }
+        }
+        tx.handoff()
+    });
+
+    let consumer = thread::spawn(move || {
+        let mut rx = rx.take();
+        let mut sum = 0;
+        for _ in 0..100_000u64 {
+            sum += loop {
+                if let Some(x) = rx.pop() {
+                    break x;
+                }
+            };
+        }
+        (sum, rx.handoff())
+    });
+
+    let tx = producer.join().unwrap().take();
+    let (sum, rx) = consumer.join().unwrap();
+    let rx = rx.take();
+
+    assert_eq!(sum, 100_000 * 99_999 / 2);
+    assert_eq!(tx.len(), 0);
+    assert_eq!(rx.len(), 0);
+}
+
+#[test]
+fn pop_with() {
+    let (mut tx, mut rx) = queue();
+    assert_eq!(rx.pop_with(|x: &mut [u8; 512]| x[0]), None);
+
+    assert_eq!(tx.push([7u8; 512]), None);
+    assert_eq!(tx.push([8u8; 512]), None);
+    assert_eq!(rx.pop_with(|x| x[511]), Some(7));
+    assert_eq!(rx.len(), 1);
+    assert_eq!(rx.pop().map(|x| x[0]), Some(8));
+}
+
+#[test]
+fn push_unpublished() {
+    let (mut tx, mut rx) = queue();
+
+    for i in 0..8 {
+        assert_eq!(tx.push_unpublished(i), None);
+    }
+    assert_eq!(rx.pop(), None);
+    tx.publish();
+    assert_eq!(rx.len(), 8);
+
+    assert_eq!(tx.push_unpublished(8), None);
+    assert_eq!(tx.push_unpublished(9), None);
+    /* Dropping the producer publishes the rest */
+    drop(tx);
+    let v: Vec<_> = std::iter::from_fn(|| rx.pop()).collect();
+    assert_eq!(v, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn peek_release() {
+    let (mut tx, mut rx) = queue();
+    assert_eq!(rx.peek(), (&[][..], &[][..]));
+
+    /* Move the indices close to the end of the ring */
+    for i in 0..250 {
+        assert_eq!(tx.push(i), None);
+        rx.pop();
+    }
+    for i in 0..10 {
+        assert_eq!(tx.push(i), None);
+    }
+
+    let (a, b) = rx.peek();
+    assert_eq!(a, [0, 1, 2, 3, 4, 5]);
+    assert_eq!(b, [6, 7, 8, 9]);
+
+    rx.release(7);
+    assert_eq!(rx.peek(), (&[7, 8, 9][..], &[][..]));
+    assert_eq!(rx.pop(), Some(7));
+}
+
+#[test]
+#[should_panic]
+fn release_too_many() {
+    let (mut tx, mut rx) = queue();
+    assert_eq!(tx.push(1), None);
+    rx.release(2);
+}
+
+#[test]
+fn push_or_drop() {
+    let (mut tx, mut rx) = queue();
+    for i in 0..tx.capacity() {
+        assert!(tx.push_or_drop(i));
+    }
+    assert!(!tx.push_or_drop(0));
+    assert_eq!(rx.pop(), Some(0));
+}
+
+#[test]
+fn weighted() {
+    let (mut tx, mut rx) = weighted_queue(10, |x: &Vec<u8>| x.len());
+    assert_eq!(tx.push(vec![0; 6]), None);
+    assert_eq!(tx.push(vec![0; 5]), Some(vec![0; 5]));
+    assert_eq!(tx.push(vec![0; 4]), None);
+    assert_eq!(tx.weight_used(), 10);
+
+    assert_eq!(rx.pop_with(|x| x.len()), Some(6));
+    assert_eq!(tx.weight_used(), 4);
+    assert_eq!(tx.push(vec![0; 5]), None);
+    rx.release(2);
+    assert_eq!(tx.weight_used(), 0);
+}
+
+#[test]
+fn tee_filtered() {
+    let (mut tx, rx) = queue::<u32>();
+    let (mut rx, mut tap) = rx.tee_filtered(|x| x % 2 == 0);
+
+    for i in 0..300 {
+        assert_eq!(tx.push(i), None);
+        assert_eq!(rx.pop(), Some(i));
+    }
+    /* 150 even numbers, the tap holds at most 255 */
+    assert_eq!(rx.dropped(), 0);
+    assert_eq!(tap.len(), 150);
+    assert_eq!(tap.pop(), Some(0));
+    assert_eq!(tap.pop(), Some(2));
+
+    let rx = rx.into_inner();
+    assert!(!tap.other_side_alive());
+    drop(rx);
+}
+
+#[test]
+fn drops() {
+    use stacc::drop_counter::DropTracker;
+
+    let tracker = DropTracker::new();
+    let (mut tx, mut rx) = queue();
+    for i in 0..300 {
+        let _ = tx.push(tracker.wrap(i));
+    }
+    tracker.assert_alive(255);
+
+    drop(rx.pop());
+    assert_eq!(rx.pop_with(|x| **x), Some(1));
+    rx.release(3);
+    assert!(tx.push_unpublished(tracker.wrap(0)).is_none());
+    tracker.assert_alive(251);
+
+    /* Unpublished and unconsumed items go with the last endpoint */
+    drop(tx);
+    drop(rx);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn occupancy_histogram() {
+    let (mut tx, mut rx) = queue();
+    tx.sample_occupancy(4);
+
+    /* Samples at occupancy 3, 7, 11, 15 */
+    for i in 0..16 {
+        assert_eq!(tx.push(i), None);
+    }
+    let h = rx.occupancy_histogram();
+    assert_eq!(h.samples(), 4);
+    assert_eq!(h.buckets[2], 1);
+    assert_eq!(h.buckets[3], 1);
+    assert_eq!(h.buckets[4], 2);
+
+    while rx.pop().is_some() {}
+    for i in 0..4 {
+        assert_eq!(tx.push(i), None);
+        rx.pop();
+    }
+    assert_eq!(tx.occupancy_histogram().buckets[0], 1);
+}
+
+#[test]
+fn drop_policy() {
+    use std::sync::mpsc;
+    use stacc::drop_policy::DropPolicy;
+
+    let (tx, rx) = mpsc::channel();
+    let (mut p, c) = queue();
+    c.set_drop_policy(DropPolicy::Callback(Box::new(move |x| tx.send(x).unwrap())));
+    for i in 0..3 {
+        assert_eq!(p.push(i), None);
+    }
+    drop(c);
+    /* Pushed after the consumer is gone, still never popped */
+    assert_eq!(p.push(3), None);
+    drop(p);
+    assert_eq!(rx.iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn id_and_debug() {
+    let (tx, rx) = queue::<u32>();
+    let (tx2, _rx2) = queue::<u32>();
+    assert_eq!(tx.id(), rx.id());
+    assert_ne!(tx.id(), tx2.id());
+    assert!(format!("{:?}", rx).contains(&format!("id: {}", tx.id())));
+    assert!(format!("{:?}", tx).contains(&format!("capacity: {}", tx.capacity())));
+}
+
+#[test]
+fn push_with() {
+    let (mut tx, mut rx) = weighted_queue::<Vec<u8>>(10, |v| v.len());
+    assert!(tx.push_with(|slot| slot.write(vec![1; 6])));
+    /* Over the weight limit, built and dropped again */
+    assert!(!tx.push_with(|slot| slot.write(vec![2; 6])));
+    assert_eq!(rx.pop(), Some(vec![1; 6]));
+    assert_eq!(rx.pop(), None);
+
+    let (mut tx, _rx) = queue::<u32>();
+    for i in 0..tx.capacity() as u32 {
+        assert!(tx.push_with(|slot| slot.write(i)));
+    }
+    assert!(!tx.push_with(|_| unreachable!()));
+}
+
+#[test]
+#[should_panic(expected = "must return the slot")]
+fn push_with_other_reference() {
+    let (mut tx, _rx) = queue::<u32>();
+    tx.push_with(|_| Box::leak(Box::new(1)));
+}
+
+#[test]
+fn load_estimate() {
+    let (mut tx, mut rx) = queue::<u32>();
+    for i in 0..100 {
+        assert!(tx.push_or_drop(i));
+    }
+    let busy = rx.load_estimate();
+    assert!(busy > 70.0 && busy < 100.0, "{}", busy);
+
+    while rx.pop().is_some() {}
+    for i in 0..100 {
+        assert!(tx.push_or_drop(i));
+        rx.pop();
+    }
+    assert!(tx.load_estimate() < 1.0, "{}", tx.load_estimate());
+}
+
+#[test]
+fn pop_unchecked() {
+    let (mut tx, mut rx) = queue();
+    for i in 0..10 {
+        assert_eq!(tx.push(i), None);
+    }
+    let n = rx.approx_len();
+    let popped: Vec<_> = (0..n).map(|_| unsafe { rx.pop_unchecked() }).collect();
+    assert_eq!(popped, (0..10).collect::<Vec<_>>());
+    assert_eq!(rx.pop(), None);
+}
+
+#[test]
+fn len_across_index_wraparound() {
+    /* Start a bit before the indices wrap, so every fill crosses it */
+    let (mut tx, mut rx) = queue_starting_at(usize::MAX - 300);
+    let cap = tx.capacity();
+
+    for round in 0..4 {
+        for i in 0..cap {
+            assert_eq!(tx.len(), i);
+            assert_eq!(rx.len(), i);
+            assert_eq!(tx.push(round), None);
+        }
+        assert!(tx.is_full());
+        assert_eq!(tx.push(round), Some(round));
+        assert_eq!(rx.len(), cap);
+
+        let (a, b) = rx.peek();
+        assert_eq!(a.len() + b.len(), cap);
+
+        rx.release(cap / 2);
+        assert_eq!(tx.len(), cap - cap / 2);
+        while rx.pop().is_some() {}
+        assert_eq!(tx.len(), 0);
+        assert_eq!(rx.len(), 0);
+    }
+}
+
+#[test]
+fn wraparound_stress() {
+    const N: usize = 20_000;
+
+    let (mut tx, mut rx) = queue_starting_at(usize::MAX - 1000);
+    let cap = tx.capacity();
+
+    let producer = std::thread::spawn(move || {
+        for i in 0..N {
+            while tx.push(i).is_some() {
+                assert!(tx.len() <= cap);
+                std::thread::yield_now();
+            }
+            assert!(tx.len() <= cap);
+        }
+    });
+
+    let mut expected = 0;
+    loop {
+        assert!(rx.len() <= cap);
+        match rx.pop() {
+            Some(x) => {
+                assert_eq!(x, expected);
+                expected += 1;
+            }
+            None if !rx.other_side_alive() => break,
+            None => std::thread::yield_now(),
+        }
+    }
+    producer.join().unwrap();
+
+    assert_eq!(expected, N);
+    assert_eq!(rx.len(), 0);
+    assert_eq!(rx.pop(), None);
+}
+
+#[test]
+fn release_panicking_drop() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct PanicOnDrop(bool, Arc<AtomicUsize>);
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            if self.0 {
+                panic!("drop failed");
+            }
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (mut tx, mut rx) = queue();
+    for i in 0..5 {
+        assert!(tx.push(PanicOnDrop(i == 1, Arc::clone(&drops))).is_none());
+    }
+
+    /* The second drop panics, the first two are gone, the rest stays */
+    assert!(catch_unwind(AssertUnwindSafe(|| rx.release(3))).is_err());
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+    assert_eq!(rx.len(), 3);
+
+    drop((tx, rx));
+    assert_eq!(drops.load(Ordering::Relaxed), 5);
+}
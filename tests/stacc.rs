@@ -8,10 +8,12 @@ fn single() {
     for i in 0..4 {
         assert_eq!(v.push(i), None);
     }
+    assert_eq!(v.len_exact(), 4);
     for i in (0..4).rev() {
         let x = v.pop();
         assert_eq!(x, Some(i));
     }
+    assert_eq!(v.approx_len(), 0);
 }
 
 #[test]
@@ -77,7 +79,7 @@ fn multi3() {
     let v = Stacc::new(4096);
 
     for _ in 0..1024 {
-        v.push(1);
+        assert_eq!(v.push(1), None);
     }
 
     let mut threads = Vec::with_capacity(4);
@@ -135,3 +137,274 @@ fn multi4() {
     eprintln!("{}", v.len());
 }
 
+
+#[test]
+fn auto_traits() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Stacc<Vec<u8>>>();
+}
+
+#[test]
+fn pop_with() {
+    use std::sync::Arc;
+
+    let v = Stacc::new(2);
+    let item = Arc::new(());
+    assert_eq!(v.pop_with(|x: &mut Arc<()>| Arc::strong_count(x)), None);
+
+    assert!(v.push(Arc::clone(&item)).is_none());
+    assert_eq!(v.pop_with(|x| Arc::strong_count(x)), Some(2));
+    /* The element was dropped after the closure */
+    assert_eq!(Arc::strong_count(&item), 1);
+}
+
+#[test]
+fn reserve_push() {
+    let v = Stacc::new(4);
+
+    let mut a = v.reserve_push().unwrap();
+    a.slot().write(1);
+    let b = v.reserve_push().unwrap();
+    let c = v.reserve_push().unwrap();
+    unsafe { a.commit() };
+    /* b leaves a hole below c */
+    drop(b);
+    c.write(3);
+    v.reserve_push().unwrap().write(4);
+    /* Abandoning the top slot gives it back right away */
+    drop(v.reserve_push().unwrap());
+
+    assert_eq!(v.len_exact(), 3);
+    let mut popped = Vec::new();
+    while let Some(x) = v.pop() {
+        popped.push(x);
+    }
+    popped.sort();
+    assert_eq!(popped, [1, 3, 4]);
+}
+
+#[test]
+fn reserve_push_discard() {
+    use stacc::drop_counter::DropTracker;
+
+    let tracker = DropTracker::new();
+    let v = Stacc::new(1);
+    let mut slot = v.reserve_push().unwrap();
+    slot.slot().write(tracker.wrap(1));
+    unsafe { slot.discard() };
+    tracker.assert_balanced();
+
+    /* The slot is free again */
+    v.reserve_push().unwrap().write(tracker.wrap(2));
+    assert_eq!(v.pop().map(|x| *x), Some(2));
+    tracker.assert_balanced();
+}
+
+#[test]
+fn blocking() {
+    for backoff in [Backoff::Spin, Backoff::Yield, Backoff::Park] {
+        let v = Stacc::with_backoff(2, backoff);
+
+        let vc = v.clone();
+        let producer = thread::spawn(move || {
+            for i in 0..1000u64 {
+                vc.push_blocking(i);
+            }
+        });
+
+        let mut sum = 0;
+        for _ in 0..1000 {
+            sum += v.pop_blocking();
+        }
+        producer.join().unwrap();
+
+        assert_eq!(sum, 1000 * (1000 - 1) / 2);
+        assert_eq!(v.pop(), None);
+    }
+}
+
+#[test]
+fn park_stress() {
+    /* Capacity 1, so nearly every push and pop goes through the parked
+     * path, where a successful retry notifies while we are a sleeper */
+    let v = Stacc::with_backoff(1, Backoff::Park);
+    let n = 2_000_000u64;
+
+    let vc = v.clone();
+    let producer = thread::spawn(move || {
+        for i in 0..n {
+            vc.push_blocking(i);
+        }
+    });
+
+    let mut sum = 0;
+    for _ in 0..n {
+        sum += v.pop_blocking();
+    }
+    producer.join().unwrap();
+
+    assert_eq!(sum, n * (n - 1) / 2);
+    assert_eq!(v.pop(), None);
+}
+
+#[test]
+fn map_drain() {
+    let v = Stacc::new(4);
+    for i in 0..6 {
+        assert_eq!(v.push(i), None);
+    }
+    /* Move part of it to the other buffer */
+    assert_eq!(v.pop(), Some(3));
+
+    let w = v.map_drain(|x| x.to_string());
+    assert_eq!(v.len_exact(), 0);
+    assert_eq!(w.len_exact(), 5);
+
+    let mut popped = Vec::new();
+    while let Some(x) = w.pop() {
+        popped.push(x);
+    }
+    assert_eq!(popped, ["2", "1", "0", "5", "4"]);
+}
+
+#[test]
+fn blocking_cancelled() {
+    use stacc::cancel::*;
+
+    for backoff in [Backoff::Yield, Backoff::Park] {
+        let v = Stacc::with_backoff(1, backoff);
+        let token = CancellationToken::new();
+
+        let (vc, tc) = (v.clone(), token.clone());
+        let consumer = thread::spawn(move || vc.pop_blocking_cancellable(&tc));
+        thread::sleep(std::time::Duration::from_millis(10));
+        token.cancel();
+        assert_eq!(consumer.join().unwrap(), Err(Cancelled));
+
+        /* Fill both buffers, then a push has nowhere to go */
+        assert_eq!(v.push(1), None);
+        assert_eq!(v.push(2), None);
+        assert_eq!(v.push_blocking_cancellable(3, &token), Err(3));
+    }
+}
+
+#[test]
+fn push_or_drop() {
+    let v = Stacc::new(1);
+    assert!(v.push_or_drop(1));
+    assert!(v.push_or_drop(2));
+    assert!(!v.push_or_drop(3));
+    assert_eq!(v.len_exact(), 2);
+}
+
+#[test]
+fn weight_limit() {
+    let v = Stacc::with_weight_limit(16, 10, |x: &Vec<u8>| x.len());
+    assert_eq!(v.push(vec![0; 6]), None);
+    assert_eq!(v.push(vec![0; 5]), Some(vec![0; 5]));
+    assert_eq!(v.push(vec![0; 4]), None);
+    assert_eq!(v.weight_used(), 10);
+
+    assert_eq!(v.pop_with(|x| x.len()), Some(4));
+    v.reserve_push().unwrap().write(vec![0; 2]);
+    assert_eq!(v.weight_used(), 8);
+
+    while v.pop().is_some() {}
+    assert_eq!(v.weight_used(), 0);
+}
+
+#[test]
+fn drops() {
+    use stacc::drop_counter::DropTracker;
+
+    let tracker = DropTracker::new();
+    let v = Stacc::new(4);
+    for i in 0..8 {
+        assert!(v.push(tracker.wrap(i)).is_none());
+    }
+    assert!(v.push(tracker.wrap(8)).is_some());
+    tracker.assert_alive(8);
+
+    drop(v.pop());
+    assert_eq!(v.pop_with(|x| **x), Some(2));
+    drop(v.reserve_push());
+    tracker.assert_alive(6);
+
+    let w = v.map_drain(|x| x);
+    while w.pop().is_some() {}
+    tracker.assert_balanced();
+}
+
+#[test]
+fn with_threads() {
+    let v = Stacc::new(4096);
+    let pushed = stacc::threads::with_threads(4, &v, |i, v| {
+        for j in 0..1024 {
+            assert_eq!(v.push(i * 1024 + j), None);
+        }
+        i
+    });
+    assert_eq!(pushed, [0, 1, 2, 3]);
+    assert_eq!(v.len_exact(), 4096);
+}
+
+#[test]
+fn drop_policy() {
+    use std::sync::mpsc;
+    use stacc::drop_counter::DropTracker;
+    use stacc::drop_policy::DropPolicy;
+
+    let tracker = DropTracker::new();
+    let v = Stacc::new(4);
+    for i in 0..6 {
+        assert!(v.push(tracker.wrap(i)).is_none());
+    }
+    drop(v.reserve_push());
+    drop(v);
+    tracker.assert_balanced();
+
+    let (tx, rx) = mpsc::channel();
+    let v = Stacc::new(4);
+    v.set_drop_policy(DropPolicy::Callback(Box::new(move |x| tx.send(x).unwrap())));
+    for i in 0..6 {
+        assert_eq!(v.push(i), None);
+    }
+    drop(v);
+    let mut left: Vec<i32> = rx.iter().collect();
+    left.sort();
+    assert_eq!(left, [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn id_and_debug() {
+    let a = Stacc::<u32>::new(1);
+    let b = Stacc::<u32>::new(1);
+    assert_ne!(a.id(), b.id());
+    assert_eq!(a.id(), a.clone().id());
+    assert_eq!(format!("{:?}", a), format!("Stacc {{ id: {}, len: 0, .. }}", a.id()));
+}
+
+#[test]
+fn pop_unchecked() {
+    let v = Stacc::new(4);
+    let mut pushed = 0;
+    for i in 0..6 {
+        pushed += v.push_or_drop(i) as usize;
+    }
+
+    /* Some of them are on the push side, the slow path swaps them over */
+    let mut popped: Vec<_> = (0..pushed).map(|_| unsafe { v.pop_unchecked() }).collect();
+    popped.sort_unstable();
+    assert_eq!(popped, (0..pushed as i32).collect::<Vec<_>>());
+    assert_eq!(v.pop(), None);
+}
+
+#[test]
+fn is_empty() {
+    let s = Stacc::new(4);
+    assert!(s.is_empty());
+    assert_eq!(s.push(1), None);
+    assert!(!s.is_empty());
+    assert_eq!(s.pop(), Some(1));
+    assert!(s.is_empty());
+}
@@ -66,3 +66,254 @@ fn ebr_consumer_producer() {
     reciever.join().unwrap();
     reciever2.join().unwrap();
 }
+
+#[test]
+fn ebr_len() {
+    let mut s = Local::new();
+    for i in 0..10 {
+        s.push(i);
+    }
+    s.pop();
+
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.approx_len(), 9);
+    assert_eq!(s.len_exact(), Some(9));
+
+    let mut other = s.clone();
+    assert_eq!(other.len_exact(), None);
+    drop(other);
+    assert_eq!(s.len_exact(), Some(9));
+}
+
+#[test]
+fn ebr_dispose() {
+    let mut s = Local::new();
+    for i in 0..1000 {
+        s.push(i);
+    }
+
+    let other = s.clone();
+    assert_eq!(s.dispose(100), None);
+    drop(other);
+
+    assert_eq!(s.dispose(600), Some(600));
+    assert_eq!(s.pop(), Some(399));
+    assert_eq!(s.dispose(600), Some(399));
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn ebr_peek_many() {
+    let mut s = Local::new();
+    assert_eq!(s.peek_many(3), Vec::<u32>::new());
+
+    for i in 0..5 {
+        s.push(i);
+    }
+
+    assert_eq!(s.peek_many(3), vec![4, 3, 2]);
+    assert_eq!(s.peek_many(10), vec![4, 3, 2, 1, 0]);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 5);
+    assert_eq!(s.pop(), Some(4));
+}
+
+#[test]
+fn ebr_shrink_to_fit() {
+    let mut s = Local::new();
+    for i in 0..10_000 {
+        s.push(i);
+    }
+    for _ in 0..10_000 {
+        s.pop();
+    }
+
+    s.shrink_to_fit();
+    s.push(1);
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn ebr_swap_top() {
+    let mut s = Local::new();
+    assert_eq!(s.swap_top(1), None);
+    s.push(2);
+    assert_eq!(s.swap_top(3), Some(2));
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.pop(), Some(3));
+    assert_eq!(s.pop(), Some(1));
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn ebr_push_unless() {
+    let mut s = Local::new();
+    assert_eq!(s.push_unless(1, |top| *top == 1), None);
+    assert_eq!(s.push_unless(1, |top| *top == 1), Some(1));
+    assert_eq!(s.push_unless(2, |top| *top == 2), None);
+
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.peek_many(3), vec![2, 1]);
+}
+
+#[test]
+fn ebr_push_unless_panicking_pred() {
+    let mut a = Local::new();
+    let mut b = a.clone();
+    a.push(1);
+    let pushed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| a.push_unless(2, |_| panic!("no"))));
+    assert!(pushed.is_err());
+
+    /* Nothing pushed, and the epoch still moves on for the other handle */
+    assert_eq!(b.peek_many(2), vec![1]);
+    for i in 0..100 {
+        b.push(i);
+        assert_eq!(b.pop(), Some(i));
+    }
+    assert!(a.push_dedup(3));
+    assert_eq!(b.pop(), Some(3));
+    assert_eq!(a.pop(), Some(1));
+}
+
+#[test]
+fn ebr_freeze() {
+    let mut s = Local::new();
+    for i in 0..5 {
+        s.push(i);
+    }
+
+    let frozen = s.freeze();
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+    assert_eq!(s.pop(), None);
+    s.push(10);
+
+    assert_eq!(&frozen[..], [4, 3, 2, 1, 0]);
+    assert_eq!(s.pop(), Some(10));
+}
+
+#[test]
+fn ebr_try_push() {
+    let mut s = Local::new();
+    assert!(s.try_push(1).is_ok());
+    assert!(s.try_push(2).is_ok());
+
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.pop(), Some(2));
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn ebr_maintain() {
+    let mut s = Local::new();
+    for i in 0..1000 {
+        s.push(i);
+    }
+    for _ in 0..1000 {
+        s.pop();
+    }
+
+    /* Whether anything was left depends on how far the epoch got */
+    s.maintain(|| true);
+    assert!(s.maintain(|| false));
+    s.push(1);
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn ebr_drops() {
+    use stacc::drop_counter::DropTracker;
+
+    let tracker = DropTracker::new();
+    let mut a = Local::new();
+    let mut b = a.clone();
+    for i in 0..1000 {
+        a.push(tracker.wrap(i));
+    }
+    for _ in 0..500 {
+        drop(b.pop());
+    }
+    assert_eq!(a.swap_top(tracker.wrap(0)).map(|x| *x), Some(499));
+    assert!(a.try_push(tracker.wrap(0)).is_ok());
+    tracker.assert_alive(501);
+
+    drop(b);
+    drop(a);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn ebr_with_threads() {
+    let s = Local::new();
+    let popped: usize = stacc::threads::with_threads(4, &s, |i, mut s| {
+        let mut popped = 0;
+        for j in 0..1000 {
+            s.push(i * 1000 + j);
+            popped += s.pop().is_some() as usize;
+        }
+        popped
+    })
+    .into_iter()
+    .sum();
+    assert_eq!(popped, 4000);
+}
+
+#[test]
+fn ebr_push_dedup() {
+    let mut s = Local::new();
+    assert!(s.push_dedup(1));
+    assert!(!s.push_dedup(1));
+    assert!(s.push_dedup(2));
+    assert!(s.push_dedup(1));
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 3);
+}
+
+#[test]
+fn ebr_handle_limit() {
+    let s = Local::<u32>::new();
+    let mut handles: Vec<_> = (1..stacc::MAX_THREADS).map(|_| s.try_clone().unwrap()).collect();
+    assert_eq!(s.try_clone().err(), Some(stacc::HandleLimitReached));
+
+    /* Slots of dropped handles are reused */
+    handles.pop();
+    handles.push(s.try_clone().unwrap());
+    assert!(s.try_clone().is_err());
+}
+
+#[test]
+#[should_panic(expected = "handle slots are taken")]
+fn ebr_clone_over_limit() {
+    let s = Local::<u32>::new();
+    let _handles: Vec<_> = (0..stacc::MAX_THREADS).map(|_| s.clone()).collect();
+}
+
+#[test]
+fn ebr_max_nodes() {
+    let mut s = Local::new().with_max_nodes(2);
+    let mut other = s.clone();
+
+    assert!(s.push_checked(1).is_ok());
+    assert!(other.push_checked(2).is_ok());
+    assert_eq!(other.push_checked(3).unwrap_err().0, 3);
+
+    assert_eq!(s.pop(), Some(2));
+    assert!(other.push_checked(3).is_ok());
+    assert!(s.push_checked(4).is_err());
+}
+
+#[test]
+fn ebr_is_empty() {
+    let mut s = Local::new();
+    let other = s.clone();
+    assert!(s.is_empty());
+    s.push(1);
+    assert!(!other.is_empty());
+    assert_eq!(s.pop(), Some(1));
+    assert!(other.is_empty());
+}
@@ -66,3 +66,865 @@ fn consumer_producer() {
     reciever.join().unwrap();
     reciever2.join().unwrap();
 }
+
+#[test]
+fn reuse_cached_nodes() {
+    let mut s = LockFreeStacc::new();
+
+    /* Enough rounds for retired nodes to be scanned and handed out again */
+    for round in 0..10 {
+        for i in 0..100 {
+            s.push(round * 100 + i);
+        }
+        for i in (0..100).rev() {
+            assert_eq!(s.pop(), Some(round * 100 + i));
+        }
+    }
+}
+
+#[test]
+fn len() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..10 {
+        s.push(i);
+    }
+    s.pop();
+
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.approx_len(), 9);
+    assert_eq!(s.len_exact(), Some(9));
+
+    let mut other = s.clone();
+    assert_eq!(other.len_exact(), None);
+    drop(other);
+    assert_eq!(s.len_exact(), Some(9));
+}
+
+#[test]
+fn dispose() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..1000 {
+        s.push(i);
+    }
+
+    let other = s.clone();
+    assert_eq!(s.dispose(100), None);
+    drop(other);
+
+    assert_eq!(s.dispose(600), Some(600));
+    assert_eq!(s.pop(), Some(399));
+    assert_eq!(s.dispose(600), Some(399));
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn fairness_window() {
+    let mut s = LockFreeStacc::new();
+    s.set_fairness_window(Some(3));
+
+    for i in 0..4 {
+        s.push(i);
+    }
+
+    assert_eq!(s.pop(), Some(3));
+    assert_eq!(s.pop(), Some(2));
+    /* Third pop reverses what is left */
+    assert_eq!(s.pop(), Some(0));
+    assert_eq!(s.pop(), Some(1));
+    assert_eq!(s.pop(), None);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+}
+
+#[test]
+fn peek_many() {
+    let mut s = LockFreeStacc::new();
+    assert_eq!(s.peek_many(3), Vec::<u32>::new());
+
+    for i in 0..5 {
+        s.push(i);
+    }
+
+    assert_eq!(s.peek_many(3), vec![4, 3, 2]);
+    assert_eq!(s.peek_many(10), vec![4, 3, 2, 1, 0]);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 5);
+    assert_eq!(s.pop(), Some(4));
+}
+
+mod executor {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    pub fn waker() -> Waker {
+        Arc::new(ThreadWaker(thread::current())).into()
+    }
+
+    pub fn block_on<F: Future>(mut fut: F) -> F::Output {
+        let waker = waker();
+        let mut cx = Context::from_waker(&waker);
+        /* SAFETY: fut is never moved after being pinned */
+        let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(x) => return x,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+}
+
+#[test]
+fn pop_async() {
+    let mut s = LockFreeStacc::new();
+
+    let mut vc = s.clone();
+    let sender = thread::spawn(move || {
+        for i in 0..1000 {
+            vc.push(i);
+            if i % 100 == 0 {
+                thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+    });
+
+    let mut sum = 0;
+    for _ in 0..1000 {
+        sum += executor::block_on(s.pop_async());
+    }
+    sender.join().unwrap();
+    assert_eq!(sum, 1000 * 999 / 2);
+}
+
+#[test]
+fn pop_async_cancelled() {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let mut a = LockFreeStacc::<u32>::new();
+    let mut b = a.clone();
+    let mut producer = a.clone();
+
+    let wakes_a = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let wakes_b = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker_a = Waker::from(Arc::clone(&wakes_a));
+    let waker_b = Waker::from(Arc::clone(&wakes_b));
+
+    let mut fut_a = a.pop_async();
+    let mut fut_b = b.pop_async();
+    assert_eq!(Pin::new(&mut fut_a).poll(&mut Context::from_waker(&waker_a)), Poll::Pending);
+    assert_eq!(Pin::new(&mut fut_b).poll(&mut Context::from_waker(&waker_b)), Poll::Pending);
+
+    /* The push wakes `a`, which gets dropped without taking the item,
+     * so the wakeup has to be forwarded to `b` */
+    producer.push(7);
+    assert_eq!(wakes_a.0.load(Ordering::SeqCst) + wakes_b.0.load(Ordering::SeqCst), 1);
+    drop(fut_a);
+    assert_eq!(wakes_b.0.load(Ordering::SeqCst), 1);
+
+    assert_eq!(Pin::new(&mut fut_b).poll(&mut Context::from_waker(&waker_b)), Poll::Ready(7));
+}
+
+#[test]
+fn shrink_to_fit() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..10_000 {
+        s.push(i);
+    }
+    for _ in 0..10_000 {
+        s.pop();
+    }
+
+    s.shrink_to_fit();
+    s.push(1);
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn swap_top() {
+    let mut s = LockFreeStacc::new();
+    assert_eq!(s.swap_top(1), None);
+    s.push(2);
+    assert_eq!(s.swap_top(3), Some(2));
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 2);
+
+    /* Swapping never leaves the stack empty, not even for a moment */
+    let mut swapper = s.clone();
+    let t = thread::spawn(move || {
+        for i in 0..100_000 {
+            assert!(swapper.swap_top(i).is_some());
+        }
+    });
+    for _ in 0..100_000 {
+        assert_eq!(s.peek_many(1).len(), 1);
+    }
+    t.join().unwrap();
+
+    assert_eq!(s.len_exact(), Some(2));
+    assert_eq!(s.pop(), Some(99_999));
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn push_unless() {
+    let mut s = LockFreeStacc::new();
+    assert_eq!(s.push_unless(1, |top| *top == 1), None);
+    assert_eq!(s.push_unless(1, |top| *top == 1), Some(1));
+    assert_eq!(s.push_unless(2, |top| *top == 2), None);
+    assert_eq!(s.push_unless(1, |top| *top == 1), None);
+
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 3);
+    assert_eq!(s.peek_many(3), vec![1, 2, 1]);
+}
+
+#[test]
+fn freeze() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..5 {
+        s.push(i);
+    }
+
+    let frozen = s.freeze();
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+    assert_eq!(s.pop(), None);
+    s.push(10);
+
+    let shared = frozen.clone();
+    let t = thread::spawn(move || shared.iter().sum::<i32>());
+    assert_eq!(t.join().unwrap(), 10);
+    assert_eq!(&frozen[..], [4, 3, 2, 1, 0]);
+    assert_eq!(s.pop(), Some(10));
+}
+
+#[test]
+fn pop_async_with_cancel() {
+    use stacc::cancel::*;
+
+    let mut s = LockFreeStacc::<u32>::new();
+    let token = CancellationToken::new();
+
+    let tc = token.clone();
+    let t = thread::spawn(move || {
+        thread::sleep(std::time::Duration::from_millis(10));
+        tc.cancel();
+    });
+    assert_eq!(executor::block_on(token.run(s.pop_async())), Err(Cancelled));
+    t.join().unwrap();
+
+    /* Nothing was taken by the cancelled pop */
+    s.push(1);
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn try_push() {
+    let mut s = LockFreeStacc::new();
+    assert!(s.try_push(1).is_ok());
+    /* Enough pops to trigger a scan, so the next node comes from the cache */
+    for i in 0..100 {
+        s.push(i);
+    }
+    for _ in 0..100 {
+        s.pop();
+    }
+    assert!(s.try_push(3).is_ok());
+
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.pop(), Some(3));
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn steal_from() {
+    let mut a = LockFreeStacc::new();
+    let mut b = LockFreeStacc::new();
+    for i in 0..5 {
+        a.push(i);
+    }
+    b.push(10);
+
+    assert_eq!(b.steal_from(&mut a, 2), 2);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(a.len(), 3);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(b.len(), 3);
+    assert_eq!(b.peek_many(3), vec![4, 3, 10]);
+
+    assert_eq!(b.steal_from(&mut a, usize::MAX), 3);
+    assert_eq!(b.steal_from(&mut a, 1), 0);
+    assert_eq!(b.peek_many(10), vec![2, 1, 0, 4, 3, 10]);
+    assert_eq!(a.pop(), None);
+}
+
+#[test]
+fn steal_from_concurrent() {
+    let mut a = LockFreeStacc::new();
+    let mut b = LockFreeStacc::new();
+
+    let mut popper = a.clone();
+    let t = thread::spawn(move || {
+        let mut popped = 0u64;
+        for _ in 0..10_000 {
+            popped += popper.pop().unwrap_or(0);
+        }
+        popped
+    });
+
+    for i in 1..=10_000u64 {
+        a.push(i);
+        if i % 7 == 0 {
+            b.steal_from(&mut a, 3);
+        }
+    }
+    let popped = t.join().unwrap();
+
+    let mut rest = 0;
+    while let Some(x) = a.pop() {
+        rest += x;
+    }
+    while let Some(x) = b.pop() {
+        rest += x;
+    }
+    assert_eq!(popped + rest, 10_000 * 10_001 / 2);
+}
+
+#[test]
+fn maintain() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..10_000 {
+        s.push(i);
+    }
+    for _ in 0..10_000 {
+        s.pop();
+    }
+
+    /* Out of time before the scan, nothing is reclaimed */
+    let pending = s.stats().pending;
+    assert!(!s.maintain(|| true));
+    assert_eq!(s.stats().pending, pending);
+
+    /* Time for the scan only, the cache is left alone */
+    let mut checks = 0;
+    assert!(!s.maintain(|| {
+        checks += 1;
+        checks > 1
+    }));
+    assert!(s.maintain(|| false));
+    s.push(1);
+    assert_eq!(s.pop(), Some(1));
+}
+
+#[test]
+fn drops() {
+    use stacc::drop_counter::DropTracker;
+
+    let tracker = DropTracker::new();
+    let mut a = LockFreeStacc::new();
+    let mut b = a.clone();
+    for i in 0..1000 {
+        a.push(tracker.wrap(i));
+    }
+    for _ in 0..500 {
+        drop(b.pop());
+    }
+    assert_eq!(a.swap_top(tracker.wrap(0)).map(|x| *x), Some(499));
+    assert!(a.try_push(tracker.wrap(0)).is_ok());
+    tracker.assert_alive(501);
+
+    drop(b);
+    drop(a);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn push_dedup() {
+    let mut s = LockFreeStacc::new();
+    assert!(s.push_dedup(1));
+    assert!(!s.push_dedup(1));
+    assert!(s.push_dedup(2));
+    assert!(s.push_dedup(1));
+    assert_eq!(s.len_exact(), Some(3));
+}
+
+#[test]
+fn drop_policy() {
+    use stacc::drop_counter::DropTracker;
+    use stacc::drop_policy::DropPolicy;
+
+    let tracker = DropTracker::new();
+    let mut s = LockFreeStacc::new();
+    s.set_drop_policy(DropPolicy::LeakAndWarn);
+    for i in 0..10 {
+        s.push(tracker.wrap(i));
+    }
+    drop(s.pop());
+    drop(s);
+    tracker.assert_alive(9);
+}
+
+#[test]
+fn many_handles() {
+    let mut s = LockFreeStacc::new();
+    let handles: Vec<_> = (0..100).map(|_| s.clone()).collect();
+    let sum: u64 = stacc::threads::with_threads(100, &s, |i, mut h| {
+        h.push(i as u64);
+        h.pop().unwrap()
+    })
+    .into_iter()
+    .sum();
+    assert_eq!(sum, 100 * 99 / 2);
+    drop(handles);
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn force_reclaim() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..10 {
+        s.push(i);
+    }
+    for _ in 0..10 {
+        s.pop();
+    }
+    /* Below the retire threshold, nothing was scanned yet */
+    assert_eq!(s.force_reclaim(), 10);
+    assert_eq!(s.force_reclaim(), 0);
+}
+
+#[test]
+fn role_hints() {
+    let mut producer = LockFreeStacc::new();
+    let mut consumer = producer.clone();
+    producer.hint_producer();
+    consumer.hint_consumer();
+
+    for i in 0..100 {
+        producer.push(i);
+    }
+    let mut sum = 0;
+    while let Some(x) = consumer.pop() {
+        sum += x;
+    }
+    assert_eq!(sum, 100 * 99 / 2);
+    /* The consumer already scanned most of its retired nodes away */
+    assert!(consumer.force_reclaim() < 10);
+}
+
+#[test]
+fn peek_with() {
+    let mut s = LockFreeStacc::new();
+    assert_eq!(s.peek_with(|&x: &u32| x), None);
+    s.push(1);
+    s.push(2);
+    assert_eq!(s.peek_with(|&x| x + 10), Some(12));
+    assert_eq!(s.pop(), Some(2));
+    assert_eq!(s.peek_with(|&x| x), Some(1));
+}
+
+#[test]
+fn take_all() {
+    let mut s = LockFreeStacc::new();
+    let mut other = s.clone();
+    assert!(s.take_all().is_empty());
+    for i in 0..5 {
+        other.push(i);
+    }
+    assert_eq!(s.take_all(), vec![4, 3, 2, 1, 0]);
+    assert_eq!(other.pop(), None);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+}
+
+#[test]
+fn pop_batch() {
+    let mut s = LockFreeStacc::new();
+    let mut other = s.clone();
+    assert!(s.pop_batch(4).is_empty());
+    for i in 0..5 {
+        other.push(i);
+    }
+    assert!(s.pop_batch(0).is_empty());
+    assert_eq!(s.pop_batch(2), vec![4, 3]);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 3);
+    assert_eq!(s.pop_batch(usize::MAX), vec![2, 1, 0]);
+    assert_eq!(other.pop(), None);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+}
+
+#[test]
+fn pop_batch_concurrent() {
+    let mut s = LockFreeStacc::new();
+    let mut pusher = s.clone();
+    let t = thread::spawn(move || {
+        for i in 1..=2000u64 {
+            pusher.push(i);
+        }
+    });
+
+    let mut sum = 0;
+    let mut popped = 0;
+    while popped < 2000 {
+        let batch = s.pop_batch(7);
+        assert!(batch.len() <= 7);
+        popped += batch.len();
+        sum += batch.iter().sum::<u64>();
+    }
+    t.join().unwrap();
+    assert_eq!(sum, 2000 * 2001 / 2);
+    assert!(s.is_empty());
+}
+
+#[test]
+fn push_chain() {
+    let tracker = stacc::drop_counter::DropTracker::new();
+    let mut s = LockFreeStacc::new();
+    s.push(tracker.wrap(0));
+
+    let mut chain = Chain::new();
+    for i in 1..4 {
+        chain.push(tracker.wrap(i));
+    }
+    assert_eq!(chain.len(), 3);
+    s.push_chain(chain);
+    s.push_chain(Chain::new());
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 4);
+    for i in (0..4).rev() {
+        assert_eq!(*s.pop().unwrap(), i);
+    }
+
+    /* Dropped without being pushed */
+    let mut chain = Chain::new();
+    chain.push(tracker.wrap(5));
+    drop(chain);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn cache_limit() {
+    let mut s = LockFreeStacc::new().with_cache_limit(4);
+    for i in 0..10 {
+        s.push(i);
+    }
+    while s.pop().is_some() {}
+    s.force_reclaim();
+    assert_eq!(s.shrink_caches(), 4);
+    assert_eq!(s.shrink_caches(), 0);
+
+    let mut s = LockFreeStacc::new();
+    for i in 0..10 {
+        s.push(i);
+    }
+    while s.pop().is_some() {}
+    s.force_reclaim();
+    /* The 10 reclaimed nodes and the rest of their chunk */
+    assert_eq!(s.shrink_caches(), 64);
+}
+
+#[test]
+fn node_chunks() {
+    let tracker = stacc::drop_counter::DropTracker::new();
+    let mut s = LockFreeStacc::new();
+    s.push(tracker.wrap(0));
+    assert_eq!(s.stats().cached, 63);
+
+    /* Chunk nodes get freed by other handles, threads and the domain */
+    let mut other = s.clone();
+    let t = thread::spawn(move || {
+        for i in 1..200 {
+            other.push(tracker.wrap(i));
+            other.pop();
+        }
+        other.pop();
+        tracker
+    });
+    let tracker = t.join().unwrap();
+    for i in 0..100 {
+        s.push(tracker.wrap(i));
+    }
+    /* A spare node out of a chunk is a box of its own */
+    let (x, node) = s.pop_node().unwrap();
+    drop(x);
+    drop(node);
+    drop(s);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn reserve_nodes() {
+    let mut s = LockFreeStacc::new().with_cache_limit(0);
+    s.reserve_nodes(8);
+    s.reserve_nodes(4);
+    for i in 0..4 {
+        s.push(i);
+    }
+    assert_eq!(s.shrink_caches(), 4);
+}
+
+#[test]
+fn node_recycling() {
+    let tracker = stacc::drop_counter::DropTracker::new();
+    let mut s = LockFreeStacc::new();
+    let mut other = s.clone();
+    s.push_node(Node::empty(), tracker.wrap(0));
+    for i in 1..100 {
+        let (x, node) = other.pop_node().unwrap();
+        assert_eq!(*x, i - 1);
+        other.push_node(node, tracker.wrap(i));
+    }
+    /* A spare node dropped without being used */
+    drop(other.pop_node());
+    assert_eq!(s.pop(), None);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn pop_node_reuses_nodes() {
+    let mut s = LockFreeStacc::new();
+    for _ in 0..3 {
+        s.push(0);
+    }
+    /* The cache holds a chunk now, the first spare is allocated */
+    let (_, node) = s.pop_node().unwrap();
+    let spare: *const Node<u32> = &*node;
+    s.push_node(node, 1);
+    assert_eq!(s.pop(), Some(1));
+    /* A chunk node is reclaimed after the spare */
+    assert_eq!(s.pop(), Some(0));
+    s.force_reclaim();
+
+    let (_, node) = s.pop_node().unwrap();
+    assert!(std::ptr::eq(&*node, spare));
+}
+
+#[test]
+fn stack_and_handles() {
+    let stack = HpStack::new();
+    let mut producer = stack.handle();
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for i in 0..100 {
+                producer.push(i);
+            }
+        });
+    });
+
+    let mut consumer = stack.handle();
+    assert_eq!(consumer.id(), stack.id());
+    assert_eq!(consumer.stack().id(), stack.id());
+    drop(stack);
+
+    /* The handles keep the stack alive */
+    let sum: i32 = std::iter::from_fn(|| consumer.pop()).sum();
+    assert_eq!(sum, (0..100).sum());
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn load_estimate() {
+    let mut s = LockFreeStacc::new();
+    assert_eq!(s.load_estimate(), 0.0);
+
+    for i in 0..1000 {
+        s.push(i);
+    }
+    let busy = s.load_estimate();
+    assert!(busy > 750.0 && busy <= 1000.0, "{}", busy);
+
+    while s.pop().is_some() {}
+    for i in 0..1000 {
+        s.push(i);
+        s.pop();
+    }
+    assert!(s.load_estimate() < 2.0, "{}", s.load_estimate());
+}
+
+#[test]
+fn stats() {
+    let mut s = LockFreeStacc::new().with_retire_threshold(4);
+    for i in 0..10 {
+        s.push(i);
+    }
+    for _ in 0..10 {
+        s.pop();
+    }
+
+    let stats = s.stats();
+    assert_eq!(stats.retired, 10);
+    assert_eq!(stats.scans, 2);
+    assert_eq!(stats.reclaimed, 8);
+    assert_eq!(stats.pending, 2);
+    /* 8 reclaimed and what is left of the chunk the pushes took */
+    assert_eq!(stats.cached, 62);
+    /* Nobody to race with */
+    assert_eq!(stats.cas_retries, 0);
+    assert_eq!(s.domain_stats().scans, 2);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn len_across_handles() {
+    /* Each handle counts in its own shard, the pops make b's go negative */
+    let mut a = LockFreeStacc::new();
+    let mut b = a.clone();
+    for i in 0..100 {
+        a.push(i);
+    }
+    for _ in 0..60 {
+        b.pop();
+    }
+    assert_eq!(a.approx_len(), 40);
+    assert_eq!(b.approx_len(), 40);
+    assert_eq!(a.stack().approx_len(), 40);
+
+    std::thread::spawn(move || while b.pop().is_some() {}).join().unwrap();
+    assert_eq!(a.approx_len(), 0);
+}
+
+#[test]
+fn max_nodes() {
+    let mut s = LockFreeStacc::new();
+    s.push(0);
+    let mut s = s.with_max_nodes(3);
+    let mut other = s.clone();
+
+    assert!(s.push_checked(1).is_ok());
+    assert!(other.push_checked(2).is_ok());
+    assert_eq!(s.push_checked(3).unwrap_err().0, 3);
+
+    /* Popping on any handle makes room again */
+    assert_eq!(other.pop(), Some(2));
+    assert!(s.push_checked(3).is_ok());
+    assert!(s.push_checked(4).is_err());
+
+    /* Plain pushes ignore the cap, but count */
+    s.push(4);
+    s.pop();
+    assert!(s.push_checked(4).is_err());
+}
+
+#[test]
+#[should_panic(expected = "other handles")]
+fn max_nodes_shared() {
+    let s = LockFreeStacc::<u32>::new();
+    let _other = s.clone();
+    let _ = s.with_max_nodes(1);
+}
+
+#[test]
+fn max_nodes_unlimited() {
+    let mut s = LockFreeStacc::new().with_max_nodes(usize::MAX);
+    for i in 0..4 {
+        assert!(s.push_checked(i).is_ok());
+    }
+    assert_eq!(s.pop(), Some(3));
+}
+
+#[test]
+fn try_drain() {
+    let tracker = stacc::drop_counter::DropTracker::new();
+    let mut s = LockFreeStacc::new().with_max_nodes(4);
+    for i in 0..4 {
+        s.push(tracker.wrap(i));
+    }
+
+    let other = s.clone();
+    assert!(s.try_drain().is_none());
+    drop(other);
+
+    let mut drain = s.try_drain().unwrap();
+    assert_eq!(*drain.next().unwrap(), 3);
+    /* The rest goes with the iterator */
+    drop(drain);
+    assert!(s.is_empty());
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 0);
+    assert!(s.push_checked(tracker.wrap(4)).is_ok());
+    drop(s);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn into_iter() {
+    let mut s = LockFreeStacc::new();
+    for i in 0..5 {
+        s.push(i);
+    }
+    assert_eq!(s.into_iter().collect::<Vec<_>>(), [4, 3, 2, 1, 0]);
+
+    /* With other handles around, the stack is taken with one exchange */
+    let tracker = stacc::drop_counter::DropTracker::new();
+    let mut s = LockFreeStacc::new();
+    let mut other = s.clone();
+    for i in 0..5 {
+        s.push(tracker.wrap(i));
+    }
+    let mut iter = s.into_iter();
+    other.push(tracker.wrap(5));
+    assert_eq!(*iter.next().unwrap(), 4);
+    drop(iter);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(other.len(), 1);
+    assert_eq!(other.into_iter().map(|x| *x).collect::<Vec<_>>(), [5]);
+    tracker.assert_balanced();
+}
+
+#[test]
+fn extend_and_collect() {
+    let mut s: LockFreeStacc<u32> = (0..100).collect();
+    let mut other = s.clone();
+    other.extend(std::iter::empty());
+    other.extend([100, 101]);
+    #[cfg(not(feature = "no-stats"))]
+    assert_eq!(s.len(), 102);
+    assert_eq!(s.peek_many(3), vec![101, 100, 99]);
+
+    let popped: Vec<u32> = s.into_iter().collect();
+    assert_eq!(popped, (0..102).rev().collect::<Vec<_>>());
+    assert!(other.is_empty());
+}
+
+#[test]
+fn push_unless_panicking_pred() {
+    let mut s = LockFreeStacc::new().with_cache_limit(0);
+    s.push(1);
+    let pushed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| s.push_unless(2, |_| panic!("no"))));
+    assert!(pushed.is_err());
+
+    /* Nothing pushed, and the old top isn't held as hazard anymore */
+    assert_eq!(s.pop(), Some(1));
+    assert_eq!(s.force_reclaim(), 1);
+    assert!(s.push_dedup(3));
+    assert_eq!(s.pop(), Some(3));
+}
@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use stacc::ticket_stack::TicketStack;
+
+#[test]
+fn bounded() {
+    let s = TicketStack::new(2);
+    assert!(s.is_empty());
+    assert_eq!(s.push(()), None);
+    assert_eq!(s.push(()), None);
+    assert_eq!(s.push(()), Some(()));
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.pop(), Some(()));
+    assert_eq!(s.pop(), Some(()));
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn drops_leftovers() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Permit;
+    impl Drop for Permit {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let s = TicketStack::new(8);
+    for _ in 0..3 {
+        assert!(s.push(Permit).is_none());
+    }
+    drop(s.pop());
+    assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+    drop(s);
+    assert_eq!(DROPPED.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn threads() {
+    let s = TicketStack::new(1000);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..250 {
+                    assert_eq!(s.push(()), None);
+                }
+            });
+        }
+    });
+    assert_eq!(s.len(), 1000);
+}